@@ -0,0 +1,32 @@
+struct BlurUniforms {
+    direction: vec2<i32>,  // (1, 0) for the horizontal pass, (0, 1) for the vertical one
+    radius: i32,
+    _padding: i32,
+};
+
+@group(0) @binding(0) var t_source: texture_2d<f32>;
+@group(0) @binding(1) var<uniform> uniforms: BlurUniforms;
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    // Single triangle covering the whole target
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+@fragment
+fn fs_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let size = vec2<i32>(textureDimensions(t_source));
+    let center = vec2<i32>(position.xy);
+    let sigma = max(f32(uniforms.radius) / 2.0, 1.0);
+
+    var sum = vec4<f32>(0.0);
+    var total_weight = 0.0;
+    for (var i = -uniforms.radius; i <= uniforms.radius; i++) {
+        let pos = clamp(center + uniforms.direction * i, vec2<i32>(0), size - 1);
+        let weight = exp(-f32(i * i) / (2.0 * sigma * sigma));
+        sum += textureLoad(t_source, pos, 0) * weight;
+        total_weight += weight;
+    }
+    return sum / total_weight;
+}
@@ -16,10 +16,37 @@ struct Uniforms {
     selection_end: vec2<f32>,
     time: f32,
     is_dragging: u32,
+    selection_adjusted: u32,  // 1 if the saved crop will differ from the selection
+    grid_size: f32,           // 0 = No grid
+    view_offset: vec2<f32>,   // Image coords of the top left of the screen
+    zoom: f32,
+    dim_style: u32,           // 0 = None, 1 = Dim, 2 = Blur
+    corner_radius: f32,
+    show_loupe: u32,
+    mouse: vec2<f32>,         // Image coords of the cursor
+    monitor_label: u32,       // 0 = None, otherwise the number shown by the monitor picker
+    show_hud: u32,
+    hud_stats: vec4<u32>,     // Capture, upload, frame and GPU time in tenths of a ms, 0xffffffff = Unknown
+    frame_times: array<vec4<f32>, 8>,  // Last 32 frame times in ms, oldest first
+    input_len: u32,           // 0 = No coordinate input, otherwise 1 + the characters typed
+    ui_scale: f32,            // Screen pixels per logical pixel of the loupe, labels and HUD
+    live: u32,                // 1 = The screen shows through with --live, t_diffuse isn't drawn
+    input_glyphs: array<vec4<u32>, 2>,  // Typed characters as glyph numbers, 4 to a u32 from the low byte
+    drag_label_len: u32,      // 0 = No drag, otherwise the characters in drag_label
+    drag_label: array<vec4<u32>, 2>,  // "WxH @ (X,Y)" of the drag, packed like input_glyphs
+    padded_bounds: vec4<f32>, // Selection grown by --pad as min x, min y, max x, max y, all 0 = No padding
+    text_bounds: vec4<f32>,   // Line of text under the cursor with --ocr-select, laid out like padded_bounds
+    border_color: vec4<f32>,  // Selection outline, alpha is how much it covers the capture
+    dim_opacity: f32,
+    border_thickness: f32,    // Screen pixels
+    border_style: u32,        // 0 = Dashed, 1 = Solid
+    show_thirds: u32,
 };
 
 @group(0) @binding(0) var t_diffuse: texture_2d<f32>;
 @group(0) @binding(1) var s_diffuse: sampler;
+@group(0) @binding(2) var t_background: texture_2d<f32>;  // Blurred copy of t_diffuse when dim_style is Blur
+@group(0) @binding(3) var t_overlay: texture_2d<f32>;     // Annotations, same size as t_diffuse
 @group(1) @binding(0) var<uniform> uniforms: Uniforms;
 
 @vertex
@@ -55,6 +82,20 @@ fn is_in_drag(coord: vec2<f32>) -> bool {
            coord.y >= min_pos.y && coord.y <= max_pos.y;
 }
 
+fn has_padding() -> bool {
+    return (uniforms.is_dragging == 2u || uniforms.is_dragging == 3u) && any(uniforms.padded_bounds != vec4<f32>(0.0));
+}
+
+fn is_in_padding(coord: vec2<f32>) -> bool {
+    return has_padding() && all(coord >= uniforms.padded_bounds.xy) && all(coord <= uniforms.padded_bounds.zw);
+}
+
+fn is_in_region(coord: vec2<f32>) -> bool {
+    let in_drag = (uniforms.is_dragging == 1u || uniforms.is_dragging == 3u) && is_in_drag(coord);
+    let in_selection = (uniforms.is_dragging == 2u || uniforms.is_dragging == 3u) && is_in_selection(coord);
+    return in_drag || in_selection || is_in_padding(coord);
+}
+
 fn is_on_border(coord: vec2<f32>, region_start: vec2<f32>, region_end: vec2<f32>, thickness: f32) -> bool {
   let min_pos = min(region_start, region_end);
   let max_pos = max(region_start, region_end);
@@ -63,6 +104,9 @@ fn is_on_border(coord: vec2<f32>, region_start: vec2<f32>, region_end: vec2<f32>
   let border_x = abs(coord.x - min_pos.x) < thickness || abs(coord.x - max_pos.x) < thickness;
   let border_y = abs(coord.y - min_pos.y) < thickness || abs(coord.y - max_pos.y) < thickness;
   
+  if (border_x || border_y) && uniforms.border_style == 1u {
+    return true;
+  }
   if border_x || border_y {
     // Create dashed effect
     let dash_length = 10.0;
@@ -80,6 +124,49 @@ fn is_on_border(coord: vec2<f32>, region_start: vec2<f32>, region_end: vec2<f32>
   return false;
 }
 
+fn is_on_grid(coord: vec2<f32>) -> bool {
+  if uniforms.grid_size < 2.0 {
+    return false;
+  }
+  let cell = coord % uniforms.grid_size;
+  let line_width = 1.0 / uniforms.zoom;
+  return cell.x < line_width || cell.y < line_width;
+}
+
+// Rule-of-thirds lines inside a region, a screen pixel wide
+fn is_on_thirds(coord: vec2<f32>, region_start: vec2<f32>, region_end: vec2<f32>) -> bool {
+  if uniforms.show_thirds == 0u {
+    return false;
+  }
+  let min_pos = min(region_start, region_end);
+  let third = (max(region_start, region_end) - min_pos) / 3.0;
+  let line_width = 1.0 / uniforms.zoom;
+  let offset = coord - min_pos;
+  let near_x = abs(offset.x - third.x) < line_width || abs(offset.x - 2.0 * third.x) < line_width;
+  let near_y = abs(offset.y - third.y) < line_width || abs(offset.y - 2.0 * third.y) < line_width;
+  return near_x || near_y;
+}
+
+// Signed distance to the selection with rounded corners, negative inside
+fn selection_distance(coord: vec2<f32>) -> f32 {
+  let min_pos = min(uniforms.selection_start, uniforms.selection_end);
+  let max_pos = max(uniforms.selection_start, uniforms.selection_end);
+  let center = (min_pos + max_pos) * 0.5;
+  let half_size = (max_pos - min_pos) * 0.5;
+  let radius = clamp(uniforms.corner_radius, 0.0, min(half_size.x, half_size.y));
+  let q = abs(coord - center) - half_size + vec2<f32>(radius);
+  return length(max(q, vec2<f32>(0.0))) + min(max(q.x, q.y), 0.0) - radius;
+}
+
+// Grey checkerboard used to show where the capture will be transparent
+fn transparency_pattern(coord: vec2<f32>) -> vec4<f32> {
+  let cell = floor(coord / 8.0);
+  if (cell.x + cell.y) % 2.0 < 1.0 {
+    return vec4<f32>(0.8, 0.8, 0.8, 1.0);
+  }
+  return vec4<f32>(0.6, 0.6, 0.6, 1.0);
+}
+
 fn get_stripe_pattern(coord: vec2<f32>) -> bool {
   let stripe_width = 10.0;  // Width of each stripe
   let stripe_spacing = 25.0; // Space between each stripe
@@ -88,17 +175,349 @@ fn get_stripe_pattern(coord: vec2<f32>) -> bool {
   return fract(pos) < (stripe_width / (stripe_width + stripe_spacing));
 }
 
+const LOUPE_RADIUS: f32 = 64.0;        // Logical pixels
+const LOUPE_MAGNIFICATION: f32 = 8.0;  // Logical pixels per image pixel
+const FONT_SCALE: f32 = 2.0;           // Logical pixels per font pixel
+
+// Size of the screen in logical pixels, which the loupe, labels and HUD are laid out in
+fn ui_size() -> vec2<f32> {
+  return uniforms.screen_size / uniforms.ui_scale;
+}
+
+// Keeps the loupe beside the cursor, flipping to the other side near the edges of the screen
+fn loupe_center(cursor: vec2<f32>) -> vec2<f32> {
+  let offset = LOUPE_RADIUS + 24.0;
+  var center = cursor + vec2<f32>(offset);
+  if center.x + LOUPE_RADIUS > ui_size().x {
+    center.x = cursor.x - offset;
+  }
+  // Leave room for the coordinates below the loupe
+  if center.y + LOUPE_RADIUS + 24.0 > ui_size().y {
+    center.y = cursor.y - offset;
+  }
+  return center;
+}
+
+// 3x5 bitmaps for the digits, then a comma, a dot, the HUD's row letters (C, U, F, G),
+// a dash, a colon, an underscore, x, @, brackets and the capital letters A to Z, one bit
+// per pixel starting at the top left
+fn glyph_bits(c: u32) -> u32 {
+  switch c {
+    case 0u: { return 0x7b6fu; }
+    case 1u: { return 0x749au; }
+    case 2u: { return 0x73e7u; }
+    case 3u: { return 0x79e7u; }
+    case 4u: { return 0x49edu; }
+    case 5u: { return 0x79cfu; }
+    case 6u: { return 0x7bcfu; }
+    case 7u: { return 0x2527u; }
+    case 8u: { return 0x7befu; }
+    case 9u: { return 0x79efu; }
+    case 10u: { return 0x1400u; }
+    case 11u: { return 0x2000u; }
+    case 12u: { return 0x724fu; }
+    case 13u: { return 0x7b6du; }
+    case 14u: { return 0x12cfu; }
+    case 15u: { return 0x7b4fu; }
+    case 16u: { return 0x01c0u; }
+    case 17u: { return 0x0410u; }
+    case 18u: { return 0x7000u; }
+    case 19u: { return 0x0aa8u; }
+    case 20u: { return 0x63eau; }
+    case 21u: { return 0x224au; }
+    case 22u: { return 0x2922u; }
+    case 23u: { return 0x5beau; }
+    case 24u: { return 0x3aebu; }
+    case 25u: { return 0x624eu; }
+    case 26u: { return 0x3b6bu; }
+    case 27u: { return 0x72cfu; }
+    case 28u: { return 0x12cfu; }
+    case 29u: { return 0x6b4eu; }
+    case 30u: { return 0x5bedu; }
+    case 31u: { return 0x7497u; }
+    case 32u: { return 0x2b24u; }
+    case 33u: { return 0x5aedu; }
+    case 34u: { return 0x7249u; }
+    case 35u: { return 0x5bfdu; }
+    case 36u: { return 0x5b6bu; }
+    case 37u: { return 0x2b6au; }
+    case 38u: { return 0x12ebu; }
+    case 39u: { return 0x676au; }
+    case 40u: { return 0x5aebu; }
+    case 41u: { return 0x388eu; }
+    case 42u: { return 0x2497u; }
+    case 43u: { return 0x7b6du; }
+    case 44u: { return 0x2b6du; }
+    case 45u: { return 0x5fedu; }
+    case 46u: { return 0x5aadu; }
+    case 47u: { return 0x24adu; }
+    case 48u: { return 0x72a7u; }
+    default: { return 0u; }
+  }
+}
+
+fn digit_count(n: u32) -> u32 {
+  var count = 1u;
+  var rest = n / 10u;
+  while rest > 0u {
+    count += 1u;
+    rest /= 10u;
+  }
+  return count;
+}
+
+// Digit `index` of `n` counting from the left, where `n` has `len` digits
+fn nth_digit(n: u32, index: u32, len: u32) -> u32 {
+  var divisor = 1u;
+  for (var k = index + 1u; k < len; k++) {
+    divisor *= 10u;
+  }
+  return (n / divisor) % 10u;
+}
+
+// Glyph of the character at `index` in the label "x,y"
+fn label_glyph(index: u32, value: vec2<u32>) -> u32 {
+  let x_len = digit_count(value.x);
+  if index == x_len {
+    return 10u;
+  }
+  if index > x_len {
+    return nth_digit(value.y, index - x_len - 1u, digit_count(value.y));
+  }
+  return nth_digit(value.x, index, x_len);
+}
+
+// Glyph `i` of text packed 4 to a u32 from the low byte, `chunk` holding the i / 16th vec4
+fn unpack_glyph(chunk: vec4<u32>, i: u32) -> u32 {
+  return (chunk[(i / 4u) % 4u] >> ((i % 4u) * 8u)) & 0xffu;
+}
+
+fn glyph_pixel(glyph: u32, column: u32, row: u32) -> bool {
+  return column < 3u && ((glyph_bits(glyph) >> (row * 3u + column)) & 1u) == 1u;
+}
+
+// Magnified view of the image around the cursor, with the pixel coordinates underneath
+fn draw_loupe(screen: vec2<f32>, color: vec4<f32>) -> vec4<f32> {
+  let cursor = (uniforms.mouse - uniforms.view_offset) * uniforms.zoom / uniforms.ui_scale;
+  let center = loupe_center(cursor);
+  let from_center = screen - center;
+  let dist = length(from_center);
+  var out = color;
+
+  if dist < LOUPE_RADIUS - 2.0 {
+    let source = uniforms.mouse + from_center / LOUPE_MAGNIFICATION;
+    out = textureSampleLevel(t_diffuse, s_diffuse, source / uniforms.screen_size, 0.0);
+    // Outline the pixel under the cursor
+    let edge = min(fract(source), 1.0 - fract(source)) * LOUPE_MAGNIFICATION;
+    if all(floor(source) == floor(uniforms.mouse)) && min(edge.x, edge.y) < 1.0 {
+      out = vec4<f32>(1.0, 0.2, 0.2, 1.0);
+    }
+  } else if dist < LOUPE_RADIUS {
+    out = vec4<f32>(1.0, 1.0, 1.0, 1.0);
+  } else if dist < LOUPE_RADIUS + 1.0 {
+    out = vec4<f32>(0.0, 0.0, 0.0, 1.0);
+  }
+
+  let value = vec2<u32>(max(floor(uniforms.mouse), vec2<f32>(0.0)));
+  let chars = digit_count(value.x) + digit_count(value.y) + 1u;
+  let size = vec2<f32>(f32(chars * 4u - 1u), 5.0) * FONT_SCALE;
+  let origin = center + vec2<f32>(-size.x * 0.5, LOUPE_RADIUS + 6.0);
+  let local = screen - origin;
+  if all(local >= vec2<f32>(-3.0)) && all(local < size + 3.0) {
+    out = mix(out, vec4<f32>(0.0, 0.0, 0.0, 1.0), 0.75);
+    if all(local >= vec2<f32>(0.0)) && all(local < size) {
+      let cell = vec2<u32>(local / FONT_SCALE);
+      if glyph_pixel(label_glyph(cell.x / 4u, value), cell.x % 4u, cell.y) {
+        out = vec4<f32>(1.0, 1.0, 1.0, 1.0);
+      }
+    }
+  }
+  return out;
+}
+
+// Size and position of the drag, under its bottom left corner or above it near the bottom of the screen
+fn draw_drag_label(screen: vec2<f32>, color: vec4<f32>) -> vec4<f32> {
+  let size = vec2<f32>(f32(uniforms.drag_label_len * 4u - 1u), 5.0) * FONT_SCALE;
+  let to_ui = uniforms.zoom / uniforms.ui_scale;
+  let top_left = (min(uniforms.drag_start, uniforms.drag_end) - uniforms.view_offset) * to_ui;
+  let bottom = (max(uniforms.drag_start, uniforms.drag_end).y - uniforms.view_offset.y) * to_ui;
+  var origin = vec2<f32>(top_left.x, bottom + 8.0);
+  if origin.y + size.y + 3.0 > ui_size().y {
+    origin.y = top_left.y - size.y - 8.0;
+  }
+  origin.x = clamp(origin.x, 3.0, max(ui_size().x - size.x - 3.0, 3.0));
+  let local = screen - origin;
+  var out = color;
+  if all(local >= vec2<f32>(-3.0)) && all(local < size + 3.0) {
+    out = mix(out, vec4<f32>(0.0, 0.0, 0.0, 1.0), 0.75);
+    if all(local >= vec2<f32>(0.0)) && all(local < size) {
+      let cell = vec2<u32>(local / FONT_SCALE);
+      let i = cell.x / 4u;
+      if glyph_pixel(unpack_glyph(uniforms.drag_label[i / 16u], i), cell.x % 4u, cell.y) {
+        out = vec4<f32>(1.0, 1.0, 1.0, 1.0);
+      }
+    }
+  }
+  return out;
+}
+
+const PICKER_SCALE: f32 = 40.0;  // Screen pixels per font pixel of the monitor number
+
+// Darkens the capture and shows the monitor's number in the middle of it
+fn draw_monitor_label(screen: vec2<f32>, color: vec4<f32>) -> vec4<f32> {
+  let len = digit_count(uniforms.monitor_label);
+  let size = vec2<f32>(f32(len * 4u - 1u), 5.0) * PICKER_SCALE;
+  let local = screen - (uniforms.screen_size - size) * 0.5;
+  if all(local >= vec2<f32>(0.0)) && all(local < size) {
+    let cell = vec2<u32>(local / PICKER_SCALE);
+    if glyph_pixel(nth_digit(uniforms.monitor_label, cell.x / 4u, len), cell.x % 4u, cell.y) {
+      return vec4<f32>(1.0, 1.0, 1.0, 1.0);
+    }
+  }
+  return vec4<f32>(color.rgb * 0.4, color.a);
+}
+
+const HUD_ORIGIN: vec2<f32> = vec2<f32>(12.0, 12.0);
+const HUD_PADDING: f32 = 6.0;
+const HUD_ROW_HEIGHT: f32 = 14.0;
+const HUD_GRAPH_SIZE: vec2<f32> = vec2<f32>(128.0, 48.0);  // 32 bars of 4 pixels
+const HUD_GRAPH_BUDGET: f32 = 33.3;  // Frame time in ms that fills the graph
+const HUD_TARGET: f32 = 16.7;        // Frame time in ms marked on the graph
+
+// Glyph of the character at `index` in a HUD row like "F 16.7"
+fn hud_glyph(letter: u32, tenths: u32, index: u32) -> u32 {
+  if index == 0u {
+    return letter;
+  }
+  if index == 1u {
+    return 99u;
+  }
+  if tenths == 0xffffffffu {
+    return select(99u, 16u, index == 2u);
+  }
+  let whole = tenths / 10u;
+  let len = digit_count(whole);
+  let i = index - 2u;
+  if i < len {
+    return nth_digit(whole, i, len);
+  }
+  if i == len {
+    return 11u;
+  }
+  if i == len + 1u {
+    return tenths % 10u;
+  }
+  return 99u;
+}
+
+// Capture, upload, frame and GPU times in the top left corner, over a graph of recent frames
+fn draw_hud(screen: vec2<f32>, color: vec4<f32>) -> vec4<f32> {
+  let text_height = HUD_ROW_HEIGHT * 4.0;
+  let size = vec2<f32>(HUD_GRAPH_SIZE.x, text_height + HUD_GRAPH_SIZE.y) + HUD_PADDING * 2.0;
+  let local = screen - HUD_ORIGIN;
+  if any(local < vec2<f32>(0.0)) || any(local >= size) {
+    return color;
+  }
+  var out = mix(color, vec4<f32>(0.0, 0.0, 0.0, 1.0), 0.75);
+
+  let text = local - HUD_PADDING;
+  if all(text >= vec2<f32>(0.0)) && text.y < text_height {
+    let row = u32(text.y / HUD_ROW_HEIGHT);
+    let cell = vec2<u32>(vec2<f32>(text.x, text.y - f32(row) * HUD_ROW_HEIGHT) / FONT_SCALE);
+    let glyph = hud_glyph(12u + row, uniforms.hud_stats[row], cell.x / 4u);
+    if cell.y < 5u && glyph_pixel(glyph, cell.x % 4u, cell.y) {
+      out = vec4<f32>(1.0, 1.0, 1.0, 1.0);
+    }
+  }
+
+  let graph = text - vec2<f32>(0.0, text_height);
+  if all(graph >= vec2<f32>(0.0)) && all(graph < HUD_GRAPH_SIZE) {
+    let bar = u32(graph.x / 4.0);
+    let ms = uniforms.frame_times[bar / 4u][bar % 4u];
+    let height = clamp(ms / HUD_GRAPH_BUDGET, 0.0, 1.0) * HUD_GRAPH_SIZE.y;
+    let target_y = HUD_GRAPH_SIZE.y * (1.0 - HUD_TARGET / HUD_GRAPH_BUDGET);
+    if graph.x % 4.0 < 3.0 && graph.y >= HUD_GRAPH_SIZE.y - height {
+      if ms <= HUD_TARGET {
+        out = vec4<f32>(0.2, 0.9, 0.3, 1.0);
+      } else {
+        out = vec4<f32>(1.0, 0.7, 0.0, 1.0);
+      }
+    } else if abs(graph.y - target_y) < 0.5 {
+      out = mix(out, vec4<f32>(1.0, 1.0, 1.0, 1.0), 0.4);
+    }
+  }
+  return out;
+}
+
+const INPUT_SCALE: f32 = 4.0;  // Logical pixels per font pixel of the coordinate input
+
+// Glyph of the character at `index` in the coordinate input, after the ':' prompt
+fn input_glyph(index: u32) -> u32 {
+  if index == 0u {
+    return 17u;
+  }
+  if index == uniforms.input_len {
+    // Blinking caret
+    return select(99u, 18u, fract(uniforms.time) < 0.5);
+  }
+  let i = index - 1u;
+  return unpack_glyph(uniforms.input_glyphs[i / 16u], i);
+}
+
+// What has been typed for the selection's coordinates, at the top of the screen
+fn draw_coordinate_input(screen: vec2<f32>, color: vec4<f32>) -> vec4<f32> {
+  let chars = uniforms.input_len + 1u;
+  let size = vec2<f32>(f32(chars * 4u - 1u), 5.0) * INPUT_SCALE;
+  let origin = vec2<f32>((ui_size().x - size.x) * 0.5, 24.0);
+  let local = screen - origin;
+  var out = color;
+  if all(local >= vec2<f32>(-8.0)) && all(local < size + 8.0) {
+    out = mix(out, vec4<f32>(0.0, 0.0, 0.0, 1.0), 0.75);
+    if all(local >= vec2<f32>(0.0)) && all(local < size) {
+      let cell = vec2<u32>(local / INPUT_SCALE);
+      if glyph_pixel(input_glyph(cell.x / 4u), cell.x % 4u, cell.y) {
+        out = vec4<f32>(1.0, 1.0, 1.0, 1.0);
+      }
+    }
+  }
+  return out;
+}
+
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    let coord = in.tex_coords * uniforms.screen_size;
-    let tex = textureSample(t_diffuse, s_diffuse, in.tex_coords);
+    // Everything below works in image coords, which differ from the screen when zoomed in
+    let coord = uniforms.view_offset + in.tex_coords * uniforms.screen_size / uniforms.zoom;
+    let tex = textureSample(t_diffuse, s_diffuse, coord / uniforms.screen_size);
+    
+    let overlay = textureSample(t_overlay, s_diffuse, coord / uniforms.screen_size);
     
-    var color = tex;
-    let border_thickness = 2.0;
+    var color = vec4<f32>(mix(tex.rgb, overlay.rgb, overlay.a), tex.a);
+    if uniforms.live == 1u {
+        // Premultiplied, so the mixes below blend with the screen behind the window
+        color = vec4<f32>(overlay.rgb * overlay.a, overlay.a);
+    }
+    let border_thickness = uniforms.border_thickness / uniforms.zoom;
+
+    if uniforms.is_dragging != 0u && !is_in_region(coord) {
+        if uniforms.live == 1u && uniforms.dim_style != 0u {
+            // Blurring needs the capture, so the screen is only dimmed
+            color = mix(color, vec4<f32>(0.0, 0.0, 0.0, 1.0), uniforms.dim_opacity);
+        } else if uniforms.dim_style == 1u {
+            color = vec4<f32>(color.rgb * (1.0 - uniforms.dim_opacity), color.a);
+        } else if uniforms.dim_style == 2u {
+            color = textureSample(t_background, s_diffuse, coord / uniforms.screen_size);
+        }
+    }
+
+    if is_on_grid(coord) {
+        color = mix(color, vec4<f32>(1.0, 1.0, 1.0, 1.0), 0.15);  // Faint grid lines
+    }
     
     if (uniforms.is_dragging == 1u || uniforms.is_dragging == 3u) && is_in_drag(coord) {
         if is_on_border(coord, uniforms.drag_start, uniforms.drag_end, border_thickness) {
             color = vec4<f32>(0.0, 0.5, 1.0, 1.0);  // Blue border
+        } else if is_on_thirds(coord, uniforms.drag_start, uniforms.drag_end) {
+            color = mix(color, vec4<f32>(1.0, 1.0, 1.0, 1.0), 0.4);
         }
         //  else if get_stripe_pattern(coord) {
         //     color = mix(color, vec4<f32>(0.0, 0.5, 1.0, 0.3), 0.3);  // Semi-transparent blue stripes
@@ -107,11 +526,51 @@ fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
     
     if (uniforms.is_dragging == 2u || uniforms.is_dragging == 3u) && is_in_selection(coord) {
         if is_on_border(coord, uniforms.selection_start, uniforms.selection_end, border_thickness) {
-            color = mix(color, vec4<f32>(0.0, 1.0, 0.0, 1.0), 0.5);  // Green border
+            if uniforms.selection_adjusted == 1u {
+                color = mix(color, vec4<f32>(1.0, 0.7, 0.0, 1.0), 0.5);  // Amber border, crop will be trimmed
+            } else {
+                color = mix(color, vec4<f32>(uniforms.border_color.rgb, 1.0), uniforms.border_color.a);
+            }
+        } else if uniforms.corner_radius > 0.0 && selection_distance(coord) > 0.0 {
+            color = transparency_pattern(coord);  // Cut off by the rounded corners
+        } else if is_on_thirds(coord, uniforms.selection_start, uniforms.selection_end) {
+            color = mix(color, vec4<f32>(1.0, 1.0, 1.0, 1.0), 0.4);
         } else if get_stripe_pattern(coord) {
             color = mix(color, vec4<f32>(0.0, 0.5, 1.0, 0.3), 0.1);  // Semi-transparent blue stripes
         }
     }
+
+    // What --pad adds around the selection, which is cropped along with it
+    if is_in_padding(coord) && !is_in_selection(coord)
+        && is_on_border(coord, uniforms.padded_bounds.xy, uniforms.padded_bounds.zw, border_thickness) {
+        color = mix(color, vec4<f32>(1.0, 1.0, 1.0, 1.0), 0.5);  // White border
+    }
+
+    // The line of text a click selects with --ocr-select
+    if any(uniforms.text_bounds != vec4<f32>(0.0))
+        && all(coord >= uniforms.text_bounds.xy) && all(coord <= uniforms.text_bounds.zw) {
+        color = mix(color, vec4<f32>(1.0, 0.85, 0.0, 1.0), 0.3);  // Yellow highlight
+    }
+
+    if uniforms.drag_label_len != 0u {
+        color = draw_drag_label(in.tex_coords * ui_size(), color);
+    }
+
+    if uniforms.show_loupe == 1u {
+        color = draw_loupe(in.tex_coords * ui_size(), color);
+    }
+
+    if uniforms.monitor_label != 0u {
+        color = draw_monitor_label(in.tex_coords * uniforms.screen_size, color);
+    }
+
+    if uniforms.input_len != 0u {
+        color = draw_coordinate_input(in.tex_coords * ui_size(), color);
+    }
+
+    if uniforms.show_hud == 1u {
+        color = draw_hud(in.tex_coords * ui_size(), color);
+    }
     
     return color;
 }
\ No newline at end of file
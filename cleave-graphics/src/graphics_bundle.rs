@@ -1,10 +1,13 @@
-use image::DynamicImage;
+use image::RgbaImage;
 use wgpu::{
     util::DeviceExt, BindGroupDescriptor, BindGroupLayoutDescriptor, Device, PipelineLayout,
     PipelineLayoutDescriptor, PrimitiveTopology, RenderPipeline, TextureFormat,
 };
 
-use crate::texture::{self, TextureBundle};
+use crate::{
+    blur,
+    texture::{self, TextureBundle},
+};
 
 pub struct GraphicsBundle<U> {
     pipeline: wgpu::RenderPipeline,
@@ -23,14 +26,13 @@ where
     U: Default + bytemuck::Pod + bytemuck::Zeroable + Copy,
 {
     pub fn new(
-        img: DynamicImage,
+        img: &RgbaImage,
         device: &wgpu::Device,
         queue: &wgpu::Queue,
         topology: PrimitiveTopology,
         format: TextureFormat,
     ) -> Self {
-        let texture = texture::RenderTexture::from_image(device, queue, &img, None)
-            .expect("Could not load texture");
+        let texture = texture::RenderTexture::from_rgba(device, queue, img, None);
         let uniforms = U::default();
         let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: None,
@@ -107,6 +109,23 @@ where
         Self { uniforms, ..self }
     }
 
+    /// Binds a gaussian blurred copy of the image as the background texture
+    pub fn with_blurred_background(
+        mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        radius: u32,
+    ) -> Self {
+        let blurred = blur::blur_texture(device, queue, &self.texture_bundle.texture, radius);
+        self.texture_bundle.set_background(device, blurred);
+        self
+    }
+
+    /// Replaces the image drawn over the capture, which must be the same size as it
+    pub fn set_overlay(&self, queue: &wgpu::Queue, overlay: &image::RgbaImage) {
+        self.texture_bundle.overlay.write(queue, overlay);
+    }
+
     pub fn draw(&self, pass: &mut wgpu::RenderPass<'_>) {
         pass.set_pipeline(&self.pipeline);
         pass.set_bind_group(0, &self.texture_bundle.bind_group, &[]);
@@ -9,10 +9,11 @@ use wgpu::{
     SurfaceConfiguration, SurfaceTexture, TextureView,
 };
 
-use crate::{error::CleaveGraphicsError, GraphicsResult};
+use crate::{error::CleaveGraphicsError, timer::GpuTimer, GraphicsResult};
 
 // use crate::DrawCommand;
 
+// Fields drop in order, the surface has to go before the window it was made from
 pub struct Graphics<W> {
     pub device: Device,
     // pipeline: RenderPipeline,
@@ -22,6 +23,10 @@ pub struct Graphics<W> {
     pub size: UVec2,
     // pub font_handler: FontHandler,
     pub window: Arc<W>,
+    // Set while render passes are being timed
+    timer: Option<GpuTimer>,
+    // How the surface can blend with what's behind the window, if it can
+    transparent_mode: Option<wgpu::CompositeAlphaMode>,
 }
 
 impl<W> Deref for Graphics<W> {
@@ -42,46 +47,123 @@ impl GraphicsOutput {
     }
 }
 
+/// Sets up a device without a window, the same way the overlay would, and
+/// describes the adapter that was picked
+pub async fn probe_device() -> GraphicsResult<wgpu::AdapterInfo> {
+    let gpu = Gpu::new().await?;
+    Ok(gpu.adapter.get_info())
+}
+
+/// A device set up before there's a window to draw to, so the slow part of starting
+/// can run on another thread while something else happens
+pub struct Gpu {
+    instance: wgpu::Instance,
+    adapter: wgpu::Adapter,
+    device: Device,
+    queue: Queue,
+}
+
+impl Gpu {
+    pub async fn new() -> GraphicsResult<Self> {
+        let instance = new_instance();
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::HighPerformance,
+                compatible_surface: None,
+                force_fallback_adapter: false,
+            })
+            .await
+            .ok_or(CleaveGraphicsError::MissingAdapter)?;
+        let (device, queue) = request_device(&adapter).await?;
+        Ok(Self {
+            instance,
+            adapter,
+            device,
+            queue,
+        })
+    }
+}
+
+fn new_instance() -> wgpu::Instance {
+    wgpu::Instance::new(InstanceDescriptor {
+        backends: wgpu::Backends::PRIMARY,
+        ..Default::default()
+    })
+}
+
+async fn request_device(adapter: &wgpu::Adapter) -> GraphicsResult<(Device, Queue)> {
+    let device = adapter
+        .request_device(
+            &wgpu::DeviceDescriptor {
+                label: None,
+                // Only used to time frames, so not required
+                required_features: adapter.features() & wgpu::Features::TIMESTAMP_QUERY,
+                required_limits: wgpu::Limits {
+                    // max_buffer_size: 786_432_000,
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            None,
+        )
+        .await?;
+    Ok(device)
+}
+
 impl<W> Graphics<W>
 where
     W: HasWindowHandle + HasDisplayHandle + Send + Sync + 'static,
 {
     pub async fn new(window: W, width: u32, height: u32) -> GraphicsResult<Self> {
+        Self::with_gpu(window, width, height, None).await
+    }
+
+    /// Like `new`, drawing with `gpu` when it's given and can present to the window
+    pub async fn with_gpu(
+        window: W,
+        width: u32,
+        height: u32,
+        gpu: Option<Gpu>,
+    ) -> GraphicsResult<Self> {
         let window = Arc::new(window);
-        // Create a surface from the window.
-        let instance = wgpu::Instance::new(InstanceDescriptor {
-            backends: wgpu::Backends::PRIMARY,
-            ..Default::default()
-        });
-        // Create a surface from the window.
-        let surface = instance.create_surface(window.clone())?;
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::HighPerformance,
-                compatible_surface: Some(&surface),
-                force_fallback_adapter: false,
-            })
-            .await;
-        let Some(adapter) = adapter else {
-            return Err(CleaveGraphicsError::MissingAdapter);
+        let prepared = match gpu {
+            Some(gpu) => {
+                let surface = gpu.instance.create_surface(window.clone())?;
+                // An adapter picked without the window may not be able to show anything in it
+                gpu.adapter.is_surface_supported(&surface).then_some((
+                    surface,
+                    gpu.adapter,
+                    gpu.device,
+                    gpu.queue,
+                ))
+            }
+            None => None,
+        };
+        let (surface, adapter, device, queue) = match prepared {
+            Some(prepared) => prepared,
+            None => {
+                let instance = new_instance();
+                let surface = instance.create_surface(window.clone())?;
+                let adapter = instance
+                    .request_adapter(&wgpu::RequestAdapterOptions {
+                        power_preference: wgpu::PowerPreference::HighPerformance,
+                        compatible_surface: Some(&surface),
+                        force_fallback_adapter: false,
+                    })
+                    .await
+                    .ok_or(CleaveGraphicsError::MissingAdapter)?;
+                let (device, queue) = request_device(&adapter).await?;
+                (surface, adapter, device, queue)
+            }
         };
         let size = UVec2::new(width, height);
         let config = find_config(&surface, &adapter, size);
-        let (device, queue) = adapter
-            .request_device(
-                &wgpu::DeviceDescriptor {
-                    label: None,
-                    required_features: wgpu::Features::empty(),
-                    required_limits: wgpu::Limits {
-                        // max_buffer_size: 786_432_000,
-                        ..Default::default()
-                    },
-                    ..Default::default()
-                },
-                None,
-            )
-            .await?;
         surface.configure(&device, &config);
+        let transparent_mode = surface
+            .get_capabilities(&adapter)
+            .alpha_modes
+            .into_iter()
+            .find(|mode| *mode == wgpu::CompositeAlphaMode::PreMultiplied);
         // let font_handler = FontHandler::new(&window, &device, &queue, config.format);
 
         Ok(Graphics {
@@ -92,9 +174,53 @@ where
             surface,
             window,
             // font_handler,
+            timer: None,
+            transparent_mode,
         })
     }
 
+    /// Lets what's drawn with less than full alpha show the windows behind, which needs
+    /// a compositor. Colors are taken as premultiplied. Returns whether it's supported
+    pub fn set_transparent(&mut self) -> bool {
+        let Some(mode) = self.transparent_mode else {
+            return false;
+        };
+        self.config.alpha_mode = mode;
+        self.surface.configure(&self.device, &self.config);
+        true
+    }
+
+    /// Starts or stops timing render passes on the GPU. Returns whether they're
+    /// being timed, which needs timestamp query support
+    pub fn measure_gpu(&mut self, enabled: bool) -> bool {
+        self.timer = enabled
+            .then(|| GpuTimer::new(&self.device, &self.queue))
+            .flatten();
+        self.timer.is_some()
+    }
+
+    /// GPU time of a recent render pass, while `measure_gpu` is on
+    pub fn gpu_time(&self) -> Option<std::time::Duration> {
+        self.timer.as_ref()?.elapsed(&self.device)
+    }
+
+    /// Blocks until the GPU has finished everything submitted, so nothing is still
+    /// drawing to the surface when it's dropped
+    pub fn wait_idle(&self) {
+        self.device.poll(wgpu::Maintain::Wait);
+    }
+
+    /// Reconfigures the surface for a window that now has this many physical pixels
+    pub fn resize(&mut self, width: u32, height: u32) {
+        if width == 0 || height == 0 {
+            return;
+        }
+        self.size = UVec2::new(width, height);
+        self.config.width = width;
+        self.config.height = height;
+        self.surface.configure(&self.device, &self.config);
+    }
+
     fn output(&self) -> Option<GraphicsOutput> {
         let Ok(output) = self.surface.get_current_texture() else {
             println!("No output available");
@@ -107,7 +233,7 @@ where
         Some(GraphicsOutput { output, view })
     }
 
-    pub fn render(&mut self) -> GraphicsResult<GraphicsPass<W>> {
+    pub fn render(&mut self) -> GraphicsResult<GraphicsPass<'_, '_, W>> {
         let mut encoder = self
             .device
             .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
@@ -116,6 +242,8 @@ where
             println!("No output available");
             return self.render();
         };
+        let timestamp_writes = self.timer.as_ref().and_then(GpuTimer::pass_writes);
+        let timed = timestamp_writes.is_some();
         let pass = encoder
             .begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: None,
@@ -127,6 +255,7 @@ where
                         store: wgpu::StoreOp::Store,
                     },
                 })],
+                timestamp_writes,
                 ..Default::default()
             })
             .forget_lifetime();
@@ -135,6 +264,7 @@ where
             encoder: Some(encoder),
             output: Some(output),
             pass,
+            timed,
         })
     }
 }
@@ -144,6 +274,8 @@ pub struct GraphicsPass<'g, 'p, W> {
     encoder: Option<wgpu::CommandEncoder>,
     output: Option<GraphicsOutput>,
     pass: wgpu::RenderPass<'p>,
+    // Whether the pass writes timestamps for the GPU timer
+    timed: bool,
 }
 
 impl<'p, W> Deref for GraphicsPass<'_, 'p, W> {
@@ -162,10 +294,17 @@ impl<W> DerefMut for GraphicsPass<'_, '_, W> {
 impl<W> GraphicsPass<'_, '_, W> {
     pub fn finish(mut self) {
         drop(self.pass);
-        let Some(encoder) = self.encoder.take() else {
+        let Some(mut encoder) = self.encoder.take() else {
             return;
         };
+        let timer = self.graphics.timer.as_ref().filter(|_| self.timed);
+        if let Some(timer) = timer {
+            timer.resolve(&mut encoder);
+        }
         self.graphics.queue.submit(Some(encoder.finish()));
+        if let Some(timer) = timer {
+            timer.read();
+        }
         if let Some(f) = self.output.take() {
             f.finish()
         }
@@ -1,9 +1,11 @@
-use image::GenericImageView;
-
 use crate::GraphicsResult;
 
 pub struct TextureBundle {
-    // pub(crate) texture: RenderTexture,
+    pub(crate) texture: RenderTexture,
+    // Shown outside the selection instead of `texture` when set
+    pub(crate) background: Option<RenderTexture>,
+    // Drawn over the image everywhere, starts out fully transparent
+    pub(crate) overlay: RenderTexture,
     pub(crate) bind_group: wgpu::BindGroup,
     pub(crate) bind_group_layout: wgpu::BindGroupLayout,
 }
@@ -31,32 +33,90 @@ impl TextureBundle {
                     ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
                     count: None,
                 },
-            ],
-        });
-        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: wgpu::BindingResource::TextureView(&texture.view),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
                 },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&texture.sampler),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
                 },
             ],
-            layout: &bind_group_layout,
-            label: None,
         });
+        let (width, height) = texture.dimensions();
+        let overlay = RenderTexture::blank(device, width, height, Some("overlay"));
+        let bind_group = make_bind_group(
+            device,
+            &bind_group_layout,
+            &texture,
+            &texture.view,
+            &overlay.view,
+        );
         Self {
-            // texture,
+            texture,
+            background: None,
+            overlay,
             bind_group,
             bind_group_layout,
         }
     }
+
+    pub fn set_background(&mut self, device: &wgpu::Device, background: RenderTexture) {
+        self.bind_group = make_bind_group(
+            device,
+            &self.bind_group_layout,
+            &self.texture,
+            &background.view,
+            &self.overlay.view,
+        );
+        self.background = Some(background);
+    }
+}
+
+fn make_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    texture: &RenderTexture,
+    background: &wgpu::TextureView,
+    overlay: &wgpu::TextureView,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&texture.view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(&texture.sampler),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: wgpu::BindingResource::TextureView(background),
+            },
+            wgpu::BindGroupEntry {
+                binding: 3,
+                resource: wgpu::BindingResource::TextureView(overlay),
+            },
+        ],
+        layout,
+        label: None,
+    })
 }
 
 pub struct RenderTexture {
-    #[allow(unused)]
     pub texture: wgpu::Texture,
     pub view: wgpu::TextureView,
     pub sampler: wgpu::Sampler,
@@ -79,12 +139,32 @@ impl RenderTexture {
         img: &image::DynamicImage,
         label: Option<&str>,
     ) -> GraphicsResult<Self> {
-        let rgba = img.to_rgba8();
-        let dimensions = img.dimensions();
+        // Captures are RGBA already, only convert anything else
+        let texture = match img.as_rgba8() {
+            Some(rgba) => Self::from_rgba(device, queue, rgba, label),
+            None => Self::from_rgba(device, queue, &img.to_rgba8(), label),
+        };
+        Ok(texture)
+    }
 
+    pub fn from_rgba(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        img: &image::RgbaImage,
+        label: Option<&str>,
+    ) -> Self {
+        let (width, height) = img.dimensions();
+        let texture = Self::blank(device, width, height, label);
+        texture.write(queue, img);
+        texture
+    }
+
+    /// Creates a transparent texture to be filled in with `write`. New textures are
+    /// zeroed by wgpu, so nothing is uploaded until then
+    pub fn blank(device: &wgpu::Device, width: u32, height: u32, label: Option<&str>) -> Self {
         let size = wgpu::Extent3d {
-            width: dimensions.0,
-            height: dimensions.1,
+            width,
+            height,
             depth_or_array_layers: 1,
         };
         let texture = device.create_texture(&wgpu::TextureDescriptor {
@@ -97,23 +177,59 @@ impl RenderTexture {
             usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
             view_formats: &[],
         });
+        Self::from_texture(device, texture)
+    }
 
+    /// Replaces the contents of the texture, `img` must have the same size
+    pub fn write(&self, queue: &wgpu::Queue, img: &image::RgbaImage) {
+        let (width, height) = img.dimensions();
         queue.write_texture(
             wgpu::ImageCopyTexture {
                 aspect: wgpu::TextureAspect::All,
-                texture: &texture,
+                texture: &self.texture,
                 mip_level: 0,
                 origin: wgpu::Origin3d::ZERO,
             },
-            &rgba,
+            img,
             wgpu::ImageDataLayout {
                 offset: 0,
-                bytes_per_row: Some(4 * dimensions.0),
-                rows_per_image: Some(dimensions.1),
+                bytes_per_row: Some(4 * width),
+                rows_per_image: Some(height),
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
             },
-            size,
         );
+    }
+
+    pub fn dimensions(&self) -> (u32, u32) {
+        let size = self.texture.size();
+        (size.width, size.height)
+    }
 
+    /// Creates an empty texture that can be drawn into and then sampled
+    pub fn render_target(
+        device: &wgpu::Device,
+        size: wgpu::Extent3d,
+        format: wgpu::TextureFormat,
+        label: Option<&str>,
+    ) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label,
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        Self::from_texture(device, texture)
+    }
+
+    fn from_texture(device: &wgpu::Device, texture: wgpu::Texture) -> Self {
         let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
         let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
             address_mode_u: wgpu::AddressMode::ClampToEdge,
@@ -125,10 +241,10 @@ impl RenderTexture {
             ..Default::default()
         });
 
-        Ok(Self {
+        Self {
             texture,
             view,
             sampler,
-        })
+        }
     }
 }
@@ -0,0 +1,121 @@
+use std::{
+    sync::{
+        atomic::{AtomicU64, AtomicU8, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+// Start and end of the render pass
+const QUERY_COUNT: u32 = 2;
+const RESULTS_SIZE: u64 = QUERY_COUNT as u64 * std::mem::size_of::<u64>() as u64;
+
+// Where the readback buffer is at, shared with the map callback
+const IDLE: u8 = 0;
+const MAPPING: u8 = 1;
+const MAPPED: u8 = 2;
+const FAILED: u8 = 3;
+const NO_RESULT: u64 = u64::MAX;
+
+/// Times render passes on the GPU with timestamp queries. Results are read back
+/// without waiting, so they lag a frame or two behind
+pub struct GpuTimer {
+    query_set: wgpu::QuerySet,
+    resolve: wgpu::Buffer,
+    readback: wgpu::Buffer,
+    // Nanoseconds per timestamp tick
+    period: f32,
+    state: Arc<AtomicU8>,
+    // Nanoseconds taken by the last measured pass
+    last: AtomicU64,
+}
+
+impl GpuTimer {
+    /// `None` if the device wasn't created with timestamp queries
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Option<Self> {
+        if !device.features().contains(wgpu::Features::TIMESTAMP_QUERY) {
+            return None;
+        }
+        let query_set = device.create_query_set(&wgpu::QuerySetDescriptor {
+            label: Some("gpu timer"),
+            ty: wgpu::QueryType::Timestamp,
+            count: QUERY_COUNT,
+        });
+        let resolve = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("gpu timer resolve"),
+            size: RESULTS_SIZE,
+            usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("gpu timer readback"),
+            size: RESULTS_SIZE,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        Some(Self {
+            query_set,
+            resolve,
+            readback,
+            period: queue.get_timestamp_period(),
+            state: Arc::new(AtomicU8::new(IDLE)),
+            last: AtomicU64::new(NO_RESULT),
+        })
+    }
+
+    /// Where the next render pass should write its timestamps, if the last
+    /// results have been read
+    pub fn pass_writes(&self) -> Option<wgpu::RenderPassTimestampWrites<'_>> {
+        (self.state.load(Ordering::Acquire) == IDLE).then_some(wgpu::RenderPassTimestampWrites {
+            query_set: &self.query_set,
+            beginning_of_pass_write_index: Some(0),
+            end_of_pass_write_index: Some(1),
+        })
+    }
+
+    /// Copies the timestamps written by the pass somewhere they can be read
+    pub fn resolve(&self, encoder: &mut wgpu::CommandEncoder) {
+        encoder.resolve_query_set(&self.query_set, 0..QUERY_COUNT, &self.resolve, 0);
+        encoder.copy_buffer_to_buffer(&self.resolve, 0, &self.readback, 0, RESULTS_SIZE);
+    }
+
+    /// Starts reading the results back, once the commands from `resolve` are submitted
+    pub fn read(&self) {
+        self.state.store(MAPPING, Ordering::Release);
+        let state = self.state.clone();
+        self.readback
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                let done = if result.is_ok() { MAPPED } else { FAILED };
+                state.store(done, Ordering::Release);
+            });
+    }
+
+    /// How long the last measured pass took, checking for new results first
+    pub fn elapsed(&self, device: &wgpu::Device) -> Option<Duration> {
+        device.poll(wgpu::Maintain::Poll);
+        match self.state.load(Ordering::Acquire) {
+            MAPPED => {
+                let ticks: Vec<u64> = self
+                    .readback
+                    .slice(..)
+                    .get_mapped_range()
+                    .chunks_exact(8)
+                    .map(|bytes| u64::from_le_bytes(bytes.try_into().unwrap_or_default()))
+                    .collect();
+                self.readback.unmap();
+                self.state.store(IDLE, Ordering::Release);
+                if let [start, end] = ticks[..] {
+                    let nanos = end.saturating_sub(start) as f64 * self.period as f64;
+                    self.last.store(nanos as u64, Ordering::Relaxed);
+                }
+            }
+            FAILED => self.state.store(IDLE, Ordering::Release),
+            _ => {}
+        }
+        match self.last.load(Ordering::Relaxed) {
+            NO_RESULT => None,
+            nanos => Some(Duration::from_nanos(nanos)),
+        }
+    }
+}
@@ -0,0 +1,105 @@
+/// Id of the window that has keyboard focus, as reported by the window manager
+#[cfg(target_os = "linux")]
+pub fn active_window_id() -> Option<u32> {
+    use xcb::{x, Xid};
+
+    let (conn, screen) = xcb::Connection::connect(None).ok()?;
+    let root = conn.get_setup().roots().nth(screen as usize)?.root();
+    let atom = conn
+        .wait_for_reply(conn.send_request(&x::InternAtom {
+            only_if_exists: true,
+            name: b"_NET_ACTIVE_WINDOW",
+        }))
+        .ok()?
+        .atom();
+    let reply = conn
+        .wait_for_reply(conn.send_request(&x::GetProperty {
+            delete: false,
+            window: root,
+            property: atom,
+            r#type: x::ATOM_WINDOW,
+            long_offset: 0,
+            long_length: 1,
+        }))
+        .ok()?;
+    reply.value::<x::Window>().first().map(|w| w.resource_id())
+}
+
+// Elsewhere xcap already lists windows front to back
+#[cfg(not(target_os = "linux"))]
+pub fn active_window_id() -> Option<u32> {
+    None
+}
+
+/// Which workspaces the window manager is showing and has put windows on, numbered from 0
+pub struct Workspaces {
+    pub current: Option<u32>,
+    // None for windows on every workspace, or when the window manager doesn't say
+    pub of_windows: Vec<Option<u32>>,
+}
+
+/// The current workspace and the one each of `windows` is on
+#[cfg(target_os = "linux")]
+pub fn workspaces(windows: &[u32]) -> Workspaces {
+    use xcb::{x, XidNew};
+
+    // Sticky windows are on "workspace" 0xFFFFFFFF
+    let cardinal = |conn: &xcb::Connection, window: x::Window, property: x::Atom| {
+        let reply = conn
+            .wait_for_reply(conn.send_request(&x::GetProperty {
+                delete: false,
+                window,
+                property,
+                r#type: x::ATOM_CARDINAL,
+                long_offset: 0,
+                long_length: 1,
+            }))
+            .ok()?;
+        reply
+            .value::<u32>()
+            .first()
+            .copied()
+            .filter(|&n| n != u32::MAX)
+    };
+    let atom = |conn: &xcb::Connection, name: &[u8]| {
+        conn.wait_for_reply(conn.send_request(&x::InternAtom {
+            only_if_exists: true,
+            name,
+        }))
+        .ok()
+        .map(|reply| reply.atom())
+    };
+    let unknown = || Workspaces {
+        current: None,
+        of_windows: vec![None; windows.len()],
+    };
+    let Ok((conn, screen)) = xcb::Connection::connect(None) else {
+        return unknown();
+    };
+    let (Some(root), Some(current), Some(desktop)) = (
+        conn.get_setup()
+            .roots()
+            .nth(screen as usize)
+            .map(|s| s.root()),
+        atom(&conn, b"_NET_CURRENT_DESKTOP"),
+        atom(&conn, b"_NET_WM_DESKTOP"),
+    ) else {
+        return unknown();
+    };
+    Workspaces {
+        current: cardinal(&conn, root, current),
+        of_windows: windows
+            .iter()
+            .map(|&id| cardinal(&conn, x::Window::new(id), desktop))
+            .collect(),
+    }
+}
+
+// Elsewhere xcap only lists the windows on the current desktop
+#[cfg(not(target_os = "linux"))]
+pub fn workspaces(windows: &[u32]) -> Workspaces {
+    Workspaces {
+        current: None,
+        of_windows: vec![None; windows.len()],
+    }
+}
@@ -0,0 +1,213 @@
+use std::{
+    io::{BufRead, BufReader, Write},
+    process::{Command, Stdio},
+};
+
+use anyhow::Context;
+use image::RgbaImage;
+
+use crate::{
+    args::Args,
+    error::{CleaveError, OrReport},
+    notify::Urgency,
+    util::{
+        self,
+        capture::{self, Capture, CaptureBackend},
+    },
+};
+
+// The agent answers each `capture` line with `ok LENGTH` and that many bytes of PNG, or
+// with `error MESSAGE`. `quit` or the end of its stdin stops it
+const CAPTURE: &str = "capture";
+const QUIT: &str = "quit";
+// Far more than a PNG of any screen, a bigger length is a garbled answer
+const MAX_PNG: usize = 1 << 30;
+
+/// Runs `cleave agent`: captures the screen of the machine it runs on whenever asked to
+/// over stdin, so another cleave can reach it through ssh with `--remote`
+pub fn run(args: &Args) -> Result<(), CleaveError> {
+    let backend = capture::backend(args);
+    answer(
+        args,
+        backend.as_ref(),
+        std::io::stdin().lock(),
+        std::io::stdout().lock(),
+    )
+}
+
+// Answers the requests read from `requests` until they end or one is `quit`
+fn answer(
+    args: &Args,
+    backend: &dyn CaptureBackend,
+    requests: impl BufRead,
+    mut stdout: impl Write,
+) -> Result<(), CleaveError> {
+    let notifier = args.notify.notifier(args.verbosity());
+    for request in requests.lines() {
+        let request = request
+            .context("Could not read the next request")
+            .or_report(notifier.as_ref(), CleaveError::Other)?;
+        let response = match request.trim() {
+            QUIT => break,
+            CAPTURE => backend.capture(args).and_then(|capture| {
+                let mut png = Vec::new();
+                util::write_png(&capture.image, args.png_compression, &mut png)?;
+                Ok(png)
+            }),
+            other => Err(anyhow::anyhow!("Unknown request {other:?}")),
+        };
+        let written = match response {
+            Ok(png) => {
+                notifier.notify(
+                    Urgency::Debug,
+                    &format!("Sent a capture of {}", util::human_size(png.len())),
+                );
+                writeln!(stdout, "ok {}", png.len()).and_then(|_| stdout.write_all(&png))
+            }
+            // Kept to one line, the error ends at the newline
+            Err(err) => writeln!(stdout, "error {}", format!("{err:#}").replace('\n', " ")),
+        };
+        written
+            .and_then(|_| stdout.flush())
+            .context("Could not answer the request")
+            .or_report(notifier.as_ref(), CleaveError::Delivery)?;
+    }
+    Ok(())
+}
+
+/// Captures through `cleave agent` on another machine, started by `--remote`
+pub struct RemoteBackend {
+    pub command: String,
+}
+
+impl CaptureBackend for RemoteBackend {
+    fn capture(&self, _: &Args) -> anyhow::Result<Capture> {
+        let command = format!("{} agent", self.command);
+        let mut child = shell(&command)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("Could not run {command:?}"))?;
+        let stdin = child.stdin.take().context("No stdin to write to")?;
+        let stdout = BufReader::new(child.stdout.take().context("No stdout to read")?);
+        let received = request_capture(stdin, stdout, &command);
+        child.wait()?;
+        // Positions on the other machine mean nothing here
+        Ok(Capture {
+            image: received?,
+            monitor: None,
+            window: None,
+        })
+    }
+
+    fn name(&self) -> &'static str {
+        "remote agent"
+    }
+}
+
+// Asks the agent at the other end of `stdin` and `stdout` for one capture
+fn request_capture(
+    mut stdin: impl Write,
+    mut stdout: impl BufRead,
+    command: &str,
+) -> anyhow::Result<RgbaImage> {
+    writeln!(stdin, "{CAPTURE}\n{QUIT}")?;
+    drop(stdin);
+
+    let mut header = String::new();
+    stdout.read_line(&mut header)?;
+    match header.trim_end().split_once(' ') {
+        Some(("ok", length)) => {
+            let length: usize = length.parse()?;
+            anyhow::ensure!(
+                length <= MAX_PNG,
+                "The agent announced a {} capture, more than the {} a screen can take",
+                util::human_size(length),
+                util::human_size(MAX_PNG)
+            );
+            let mut png = vec![0; length];
+            stdout.read_exact(&mut png)?;
+            Ok(image::load_from_memory(&png)?.to_rgba8())
+        }
+        Some(("error", message)) => Err(anyhow::anyhow!("The agent failed: {message}")),
+        _ if header.is_empty() => Err(anyhow::anyhow!(
+            "{command:?} ended without answering, is cleave installed there?"
+        )),
+        _ => Err(anyhow::anyhow!(
+            "Unexpected answer {header:?} from the agent"
+        )),
+    }
+}
+
+#[cfg(unix)]
+fn shell(command: &str) -> Command {
+    let mut shell = Command::new("sh");
+    shell.args(["-c", command]);
+    shell
+}
+
+#[cfg(windows)]
+fn shell(command: &str) -> Command {
+    let mut shell = Command::new("cmd");
+    shell.args(["/C", command]);
+    shell
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::BufReader;
+
+    use clap::Parser;
+    use image::{Rgba, RgbaImage};
+
+    use super::{answer, request_capture};
+    use crate::{
+        args::Args,
+        util::capture::{Capture, CaptureBackend},
+    };
+
+    // Always captures the same image
+    struct Fixed(RgbaImage);
+
+    impl CaptureBackend for Fixed {
+        fn capture(&self, _: &Args) -> anyhow::Result<Capture> {
+            Ok(Capture {
+                image: self.0.clone(),
+                monitor: None,
+                window: None,
+            })
+        }
+
+        fn name(&self) -> &'static str {
+            "fixed"
+        }
+    }
+
+    #[test]
+    fn captures_round_trip_through_the_agent() {
+        let mut img = RgbaImage::from_pixel(5, 3, Rgba([10, 20, 30, 255]));
+        img.put_pixel(4, 2, Rgba([200, 100, 0, 128]));
+        let (agent_stdin, to_agent) = std::io::pipe().unwrap();
+        let (from_agent, agent_stdout) = std::io::pipe().unwrap();
+        let agent = {
+            let backend = Fixed(img.clone());
+            std::thread::spawn(move || {
+                let args = Args::parse_from(["cleave"]);
+                answer(&args, &backend, BufReader::new(agent_stdin), agent_stdout)
+            })
+        };
+
+        let received = request_capture(to_agent, BufReader::new(from_agent), "test").unwrap();
+        assert!(agent.join().unwrap().is_ok());
+        assert_eq!(received, img);
+    }
+
+    #[test]
+    fn oversized_answers_are_refused_before_reading_them() {
+        let answer = format!("ok {}\n", usize::MAX);
+        let err = request_capture(std::io::sink(), answer.as_bytes(), "test").unwrap_err();
+        assert!(err.to_string().contains("more than"), "{err}");
+        assert!(request_capture(std::io::sink(), "error no display\n".as_bytes(), "test").is_err());
+        assert!(request_capture(std::io::sink(), "".as_bytes(), "test").is_err());
+    }
+}
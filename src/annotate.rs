@@ -0,0 +1,185 @@
+#[cfg(feature = "gui")]
+use glam::Vec2;
+#[cfg(feature = "gui")]
+use image::{Rgba, RgbaImage};
+
+#[cfg(feature = "gui")]
+const STROKE_COLOR: Rgba<u8> = Rgba([230, 30, 30, 255]);
+#[cfg(feature = "gui")]
+const STROKE_WIDTH: f32 = 3.0;
+// Longest side of an arrow head, shorter arrows get smaller heads
+#[cfg(feature = "gui")]
+const ARROW_HEAD_LENGTH: f32 = 16.0;
+
+#[derive(Clone, Copy, Debug)]
+pub enum Tool {
+    Rectangle,
+    Arrow,
+    Freehand,
+}
+
+#[cfg(feature = "gui")]
+#[derive(Clone, Debug)]
+enum Shape {
+    Rectangle(Vec2, Vec2),
+    Arrow(Vec2, Vec2),
+    Freehand(Vec<Vec2>),
+}
+
+#[cfg(feature = "gui")]
+impl Shape {
+    fn new(tool: Tool, start: Vec2) -> Self {
+        match tool {
+            Tool::Rectangle => Shape::Rectangle(start, start),
+            Tool::Arrow => Shape::Arrow(start, start),
+            Tool::Freehand => Shape::Freehand(vec![start]),
+        }
+    }
+
+    fn extend(&mut self, point: Vec2) {
+        match self {
+            Shape::Rectangle(_, end) | Shape::Arrow(_, end) => *end = point,
+            Shape::Freehand(points) => points.push(point),
+        }
+    }
+
+    // Straight strokes that make up the shape
+    fn segments(&self) -> Vec<(Vec2, Vec2)> {
+        match self {
+            Shape::Rectangle(start, end) => {
+                let (a, b) = (*start, Vec2::new(end.x, start.y));
+                let (c, d) = (*end, Vec2::new(start.x, end.y));
+                vec![(a, b), (b, c), (c, d), (d, a)]
+            }
+            Shape::Arrow(start, end) => {
+                let shaft = *end - *start;
+                let head = shaft.normalize_or_zero() * ARROW_HEAD_LENGTH.min(shaft.length() / 3.0);
+                // Barbs point back along the shaft at 30 degrees either side
+                let barb = 30f32.to_radians();
+                let left = Vec2::from_angle(barb).rotate(-head);
+                let right = Vec2::from_angle(-barb).rotate(-head);
+                vec![(*start, *end), (*end, *end + left), (*end, *end + right)]
+            }
+            Shape::Freehand(points) => match points.as_slice() {
+                [point] => vec![(*point, *point)],
+                points => points.windows(2).map(|w| (w[0], w[1])).collect(),
+            },
+        }
+    }
+
+    fn draw(&self, layer: &mut RgbaImage) {
+        for (start, end) in self.segments() {
+            draw_segment(layer, start, end);
+        }
+    }
+}
+
+#[cfg(feature = "gui")]
+/// Shapes drawn over the capture, kept in image coordinates
+pub struct Annotations {
+    pub tool: Tool,
+    shapes: Vec<Shape>,
+    active: Option<Shape>,
+    // Every finished shape, drawn once so saving doesn't have to redraw them
+    layer: RgbaImage,
+    changed: bool,
+}
+
+#[cfg(feature = "gui")]
+impl Annotations {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            tool: Tool::Rectangle,
+            shapes: Vec::new(),
+            active: None,
+            layer: RgbaImage::new(width, height),
+            changed: false,
+        }
+    }
+
+    pub fn start(&mut self, point: Vec2) {
+        self.active = Some(Shape::new(self.tool, point));
+        self.changed = true;
+    }
+
+    pub fn extend(&mut self, point: Vec2) {
+        if let Some(shape) = self.active.as_mut() {
+            shape.extend(point);
+            self.changed = true;
+        }
+    }
+
+    pub fn finish(&mut self) {
+        if let Some(shape) = self.active.take() {
+            shape.draw(&mut self.layer);
+            self.shapes.push(shape);
+        }
+    }
+
+    pub fn cancel(&mut self) {
+        self.changed |= self.active.take().is_some();
+    }
+
+    pub fn undo(&mut self) {
+        if self.shapes.pop().is_none() {
+            return;
+        }
+        self.layer = RgbaImage::new(self.layer.width(), self.layer.height());
+        for shape in &self.shapes {
+            shape.draw(&mut self.layer);
+        }
+        self.changed = true;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.shapes.is_empty()
+    }
+
+    /// Finished shapes only, the one being drawn is left out
+    pub fn layer(&self) -> &RgbaImage {
+        &self.layer
+    }
+
+    /// Everything drawn so far, if it changed since the last call
+    pub fn take_preview(&mut self) -> Option<RgbaImage> {
+        if !std::mem::take(&mut self.changed) {
+            return None;
+        }
+        let mut preview = self.layer.clone();
+        if let Some(shape) = &self.active {
+            shape.draw(&mut preview);
+        }
+        Some(preview)
+    }
+}
+
+#[cfg(feature = "gui")]
+// Antialiased line with round caps, only ever raising the coverage already in `layer`
+fn draw_segment(layer: &mut RgbaImage, start: Vec2, end: Vec2) {
+    let reach = STROKE_WIDTH / 2.0 + 1.0;
+    let min = (start.min(end) - reach).max(Vec2::ZERO);
+    let max = (start.max(end) + reach).min(Vec2::new(layer.width() as f32, layer.height() as f32));
+    for y in min.y as u32..max.y as u32 {
+        for x in min.x as u32..max.x as u32 {
+            let center = Vec2::new(x as f32 + 0.5, y as f32 + 0.5);
+            let distance = segment_distance(center, start, end);
+            let coverage = (STROKE_WIDTH / 2.0 + 0.5 - distance).clamp(0.0, 1.0);
+            let alpha = (coverage * 255.0).round() as u8;
+            let pixel = layer.get_pixel_mut(x, y);
+            if alpha > pixel[3] {
+                *pixel = Rgba([STROKE_COLOR[0], STROKE_COLOR[1], STROKE_COLOR[2], alpha]);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "gui")]
+fn segment_distance(point: Vec2, start: Vec2, end: Vec2) -> f32 {
+    let line = end - start;
+    let t = if line == Vec2::ZERO {
+        0.0
+    } else {
+        ((point - start).dot(line) / line.length_squared()).clamp(0.0, 1.0)
+    };
+    point.distance(start + line * t)
+}
@@ -0,0 +1,617 @@
+use std::time::{Duration, Instant};
+
+use image::RgbaImage;
+use winit::{
+    application::ApplicationHandler,
+    event::{ElementState, KeyEvent, MouseButton, MouseScrollDelta, WindowEvent},
+    event_loop::{ActiveEventLoop, ControlFlow},
+    keyboard::{Key, ModifiersState, NamedKey},
+};
+
+#[cfg(feature = "record")]
+use crate::util::{self, record::Recording};
+use crate::{
+    args::{Args, Command, MonitorTarget},
+    context::AppContext,
+    error::{CleaveError, OrReport},
+    keyboard::{Action, Direction, Keybindings},
+    notify::Urgency,
+    output::{self, Delivered},
+    util::inhibit::{self, Inhibitor},
+};
+
+// How long the compositor gets to take a hidden window off the screen before it's captured
+const HIDE_DELAY: Duration = Duration::from_millis(200);
+// How often a recording without its indicator is checked on
+#[cfg(feature = "record")]
+const RECORDING_POLL: Duration = Duration::from_millis(100);
+
+// `--delay` counting down, nothing is captured before it ends
+struct Countdown {
+    // Shut once the delay is up, the capture waits until it's off the screen
+    window: Option<AppContext>,
+    ends: Instant,
+    // Keeps the screen from locking in the meantime
+    _awake: Option<Inhibitor>,
+}
+
+struct App {
+    args: Args,
+    bindings: Keybindings,
+    context: Option<AppContext>,
+    // One overlay per monitor while `--monitor pick` waits for a choice
+    pickers: Vec<AppContext>,
+    countdown: Option<Countdown>,
+    // The recording `V` started and its indicator, when it could be shown
+    #[cfg(feature = "record")]
+    recording: Option<(Recording, Option<AppContext>)>,
+    modifiers: ModifiersState,
+    // For `--timeout`
+    last_input: Instant,
+    // The move key being held down and since when, for `--acceleration`
+    held_move: Option<(Direction, Instant)>,
+    // Why the overlay closed without delivering a capture
+    error: Option<CleaveError>,
+    // Checked on once the event loop is done, with `--verify-clipboard`
+    copied: Option<RgbaImage>,
+    // When the screen is captured once the `--live` overlay is off it
+    live_capture: Option<Instant>,
+}
+
+impl App {
+    fn execute_key_command(&mut self, event_loop: &ActiveEventLoop, action: Action) {
+        let Some(context) = &mut self.context else {
+            return;
+        };
+        match action {
+            Action::Confirm => {
+                if context.prompt_tags() {
+                    return;
+                }
+                context.hide_window();
+                if context.is_live() {
+                    self.live_capture = Some(Instant::now() + HIDE_DELAY);
+                    self.poll_live_capture(event_loop);
+                    return;
+                }
+                self.deliver(event_loop);
+            }
+            Action::ConfirmFullscreen => {
+                context.select_all();
+                self.execute_key_command(event_loop, Action::Confirm);
+            }
+            Action::SelectAll => context.select_all(),
+            Action::SelectCenter if context.has_selection() => {
+                self.execute_key_command(event_loop, Action::Confirm);
+            }
+            Action::SelectCenter => context.select_center(),
+            #[cfg(feature = "record")]
+            Action::Record => self.start_recording(event_loop),
+            #[cfg(not(feature = "record"))]
+            Action::Record => self.args.notify.notifier(self.args.verbosity()).notify(
+                Urgency::Error,
+                "Could not record: this cleave was built without the record feature",
+            ),
+            Action::Cancel => {
+                self.error = Some(CleaveError::Cancelled);
+                event_loop.exit();
+                context.hide_window();
+            }
+            Action::Move(dir) => {
+                let since = match self.held_move {
+                    Some((held, since)) if held == dir => since,
+                    _ => self.held_move.insert((dir, Instant::now())).1,
+                };
+                context.handle_move(dir, self.modifiers.alt_key(), since.elapsed());
+            }
+            Action::SetMode(mode) => context.set_mode(mode),
+            Action::StartSelection => context.start_keyboard_selection(),
+            Action::AdjustCornerRadius(delta) => context.adjust_corner_radius(delta),
+            Action::LockAspect(locked) => context.set_aspect_lock(locked),
+            Action::ToggleAnnotation => context.toggle_annotation(),
+            Action::SetAnnotationTool(tool) => context.set_annotation_tool(tool),
+            Action::UndoAnnotation => context.undo_annotation(),
+            Action::UndoSelection => context.undo_selection(),
+            Action::RedoSelection => context.redo_selection(),
+            Action::RestoreSelection => context.restore_last_selection(),
+            Action::ToggleHud => context.toggle_hud(),
+            Action::EnterCoordinates => context.start_coordinate_input(),
+        }
+    }
+
+    // Delivers the confirmed selection and ends the run
+    fn deliver(&mut self, event_loop: &ActiveEventLoop) {
+        let Some(context) = &mut self.context else {
+            return;
+        };
+        match context.save_selection() {
+            Delivered::Clipboard(copied) => {
+                self.copied = self.args.verify_clipboard.then_some(copied);
+            }
+            Delivered::NothingSelected => self.error = Some(CleaveError::NoSelection),
+            Delivered::Failed => self.error = Some(CleaveError::Delivery),
+            Delivered::Stdout | Delivered::Saved | Delivered::Unchanged => {}
+        }
+        event_loop.exit();
+    }
+
+    // Captures the screen once the `--live` overlay is off it, then delivers the selection
+    fn poll_live_capture(&mut self, event_loop: &ActiveEventLoop) {
+        let (Some(due), Some(context)) = (self.live_capture, &mut self.context) else {
+            return;
+        };
+        if Instant::now() < due {
+            event_loop.set_control_flow(ControlFlow::WaitUntil(due));
+            return;
+        }
+        self.live_capture = None;
+        if let Err(err) = context.recapture() {
+            let notifier = self.args.notify.notifier(self.args.verbosity());
+            notifier.notify(Urgency::Error, &format!("Could not capture: {err:#}"));
+            self.error = Some(CleaveError::Capture);
+            event_loop.exit();
+            return;
+        }
+        self.deliver(event_loop);
+    }
+
+    fn cancel(&mut self, event_loop: &ActiveEventLoop) {
+        self.error = Some(CleaveError::Cancelled);
+        event_loop.exit();
+    }
+
+    // Cancels once `--timeout` passes without input, waking up for it even when no frames
+    // are drawn. `--delay` and recordings aren't waiting on input, so they can't time out
+    fn poll_timeout(&mut self, event_loop: &ActiveEventLoop) {
+        let Some(secs) = self.args.timeout else {
+            return;
+        };
+        if self.countdown.is_some() || self.recording() || self.live_capture.is_some() {
+            return;
+        }
+        let deadline = self.last_input + Duration::from_secs(secs);
+        if Instant::now() >= deadline {
+            self.cancel(event_loop);
+        } else {
+            event_loop.set_control_flow(ControlFlow::WaitUntil(deadline));
+        }
+    }
+
+    // Starts the capture, or ends the run if it can't be
+    fn start_or_report(&mut self, event_loop: &ActiveEventLoop) {
+        if let Err(err) = self.start(event_loop) {
+            let notifier = self.args.notify.notifier(self.args.verbosity());
+            notifier.notify_with_hint(
+                Urgency::Error,
+                &format!("Could not start capture: {err:#}"),
+                startup_hint(&self.args),
+            );
+            self.error = Some(CleaveError::Capture);
+            event_loop.exit();
+        }
+    }
+
+    fn start(&mut self, event_loop: &ActiveEventLoop) -> anyhow::Result<()> {
+        let picking =
+            matches!(self.args.monitor, MonitorTarget::Pick) && self.args.window.is_none();
+        if self.args.command.is_some() || !picking {
+            self.context = Some(AppContext::new(event_loop, self.args.clone())?);
+            return Ok(());
+        }
+        let monitors = xcap::Monitor::all()?;
+        if let [monitor] = monitors.as_slice() {
+            self.context = Some(AppContext::on_monitor(
+                event_loop,
+                self.args.clone(),
+                monitor,
+            )?);
+            return Ok(());
+        }
+        for (number, monitor) in (1..).zip(&monitors) {
+            let mut picker = AppContext::on_monitor(event_loop, self.args.clone(), monitor)?;
+            picker.set_picker_label(Some(number));
+            self.pickers.push(picker);
+        }
+        Ok(())
+    }
+
+    // Keeps the chosen monitor's overlay for the selection and closes the rest
+    fn pick(&mut self, index: usize) {
+        if index >= self.pickers.len() {
+            return;
+        }
+        let mut context = self.pickers.swap_remove(index);
+        self.pickers.clear();
+        context.set_picker_label(None);
+        self.context = Some(context);
+    }
+
+    #[cfg(feature = "record")]
+    fn start_recording(&mut self, event_loop: &ActiveEventLoop) {
+        let Some(context) = &self.context else {
+            return;
+        };
+        let notifier = self.args.notify.notifier(self.args.verbosity());
+        let recording = match context.start_recording() {
+            Ok(recording) => recording,
+            // The overlay stays open to fix what was wrong, like selecting something
+            Err(err) => {
+                notifier.notify(Urgency::Error, &format!("Could not record: {err:#}"));
+                return;
+            }
+        };
+        context.hide_window();
+        let position = context.indicator_position();
+        let indicator = match AppContext::recording_indicator(event_loop, &self.args, position) {
+            Ok(indicator) => Some(indicator),
+            Err(err) => {
+                notifier.notify(
+                    Urgency::Warning,
+                    &format!(
+                        "Could not show the recording indicator, recording for the full --record-seconds: {err:#}"
+                    ),
+                );
+                None
+            }
+        };
+        self.recording = Some((recording, indicator));
+    }
+
+    #[cfg(feature = "record")]
+    fn indicator_event(&mut self, event: WindowEvent) {
+        let Some((recording, Some(indicator))) = &mut self.recording else {
+            return;
+        };
+        match event {
+            WindowEvent::RedrawRequested => {
+                let left = recording.ends.saturating_duration_since(Instant::now());
+                indicator.set_picker_label(Some(left.as_secs_f32().ceil().max(1.0) as u32));
+                indicator.draw();
+            }
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        state: ElementState::Pressed,
+                        ..
+                    },
+                ..
+            }
+            | WindowEvent::MouseInput {
+                state: ElementState::Pressed,
+                ..
+            }
+            | WindowEvent::CloseRequested => recording.stop(),
+            _ => {}
+        }
+    }
+
+    // Ends the run once the recording is saved
+    #[cfg(feature = "record")]
+    fn poll_recording(&mut self, event_loop: &ActiveEventLoop) {
+        let Some((recording, _)) = &self.recording else {
+            return;
+        };
+        if !recording.is_finished() {
+            event_loop.set_control_flow(ControlFlow::WaitUntil(Instant::now() + RECORDING_POLL));
+            return;
+        }
+        let Some((recording, indicator)) = self.recording.take() else {
+            return;
+        };
+        if let Some(indicator) = indicator {
+            indicator.hide_window();
+            indicator.shutdown();
+        }
+        let notifier = self.args.notify.notifier(self.args.verbosity());
+        match recording.finish() {
+            Ok(recorded) => notifier.notify(
+                Urgency::Info,
+                &format!(
+                    "Recorded {} frames to {} ({})",
+                    recorded.frames,
+                    recorded.output.display(),
+                    util::human_size(recorded.bytes)
+                ),
+            ),
+            Err(err) => notifier.notify(Urgency::Error, &format!("Could not record: {err:#}")),
+        }
+        event_loop.exit();
+    }
+
+    // Whether a recording is running, nothing else happens until it's done
+    fn recording(&self) -> bool {
+        #[cfg(feature = "record")]
+        return self.recording.is_some();
+        #[cfg(not(feature = "record"))]
+        false
+    }
+
+    fn countdown_event(&mut self, event_loop: &ActiveEventLoop, event: WindowEvent) {
+        let Some(Countdown {
+            window: Some(window),
+            ends,
+            ..
+        }) = &mut self.countdown
+        else {
+            return;
+        };
+        match event {
+            // Ending it is left to `poll_countdown`, which also runs without a window
+            WindowEvent::RedrawRequested => {
+                let left = ends.saturating_duration_since(Instant::now());
+                window.set_picker_label(Some(left.as_secs_f32().ceil() as u32));
+                window.draw();
+            }
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        state: ElementState::Pressed,
+                        logical_key: Key::Named(NamedKey::Escape),
+                        ..
+                    },
+                ..
+            }
+            | WindowEvent::CloseRequested => self.cancel(event_loop),
+            _ => {}
+        }
+    }
+
+    // Takes the countdown off the screen once `--delay` is up, then starts the capture
+    fn poll_countdown(&mut self, event_loop: &ActiveEventLoop) {
+        let Some(countdown) = &mut self.countdown else {
+            return;
+        };
+        if Instant::now() < countdown.ends {
+            event_loop.set_control_flow(ControlFlow::WaitUntil(countdown.ends));
+            return;
+        }
+        if let Some(window) = countdown.window.take() {
+            window.hide_window();
+            window.shutdown();
+            // Give the compositor time to take the countdown off the screen
+            countdown.ends = Instant::now() + HIDE_DELAY;
+            event_loop.set_control_flow(ControlFlow::WaitUntil(countdown.ends));
+            return;
+        }
+        self.countdown = None;
+        event_loop.set_control_flow(ControlFlow::Wait);
+        // The wait isn't idleness for `--timeout`
+        self.last_input = Instant::now();
+        self.start_or_report(event_loop);
+    }
+
+    fn picker_event(&mut self, event_loop: &ActiveEventLoop, index: usize, event: WindowEvent) {
+        match event {
+            WindowEvent::RedrawRequested => self.pickers[index].draw(),
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        state: ElementState::Pressed,
+                        logical_key,
+                        ..
+                    },
+                ..
+            } => match logical_key {
+                Key::Named(NamedKey::Escape) => self.cancel(event_loop),
+                Key::Character(c) => {
+                    if let Ok(number @ 1..) = c.parse::<usize>() {
+                        self.pick(number - 1);
+                    }
+                }
+                _ => {}
+            },
+            WindowEvent::MouseInput {
+                state: ElementState::Pressed,
+                button: MouseButton::Left,
+                ..
+            } => self.pick(index),
+            WindowEvent::CloseRequested => self.cancel(event_loop),
+            _ => {}
+        }
+    }
+}
+
+impl ApplicationHandler for App {
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        // Images given to `cleave edit` are already there, so there's nothing to wait for
+        if self.args.delay == 0 || self.args.command.is_some() {
+            self.start_or_report(event_loop);
+            return;
+        }
+        let notifier = self.args.notify.notifier(self.args.verbosity());
+        // The capture still waits, just without showing it
+        let window = AppContext::countdown(event_loop, &self.args)
+            .inspect_err(|err| {
+                notifier.notify(
+                    Urgency::Warning,
+                    &format!("Could not show the countdown: {err:#}"),
+                )
+            })
+            .ok();
+        self.countdown = Some(Countdown {
+            window,
+            ends: Instant::now() + Duration::from_secs(self.args.delay),
+            _awake: inhibit::during_delay(notifier.as_ref()),
+        });
+        self.poll_countdown(event_loop);
+    }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        self.poll_countdown(event_loop);
+        self.poll_timeout(event_loop);
+        self.poll_live_capture(event_loop);
+        #[cfg(feature = "record")]
+        self.poll_recording(event_loop);
+    }
+
+    fn exiting(&mut self, _: &ActiveEventLoop) {
+        // Whatever was recorded until now is still saved
+        #[cfg(feature = "record")]
+        if let Some((recording, _)) = self.recording.take() {
+            let _ = recording.finish();
+        }
+        if let Some(window) = self.countdown.take().and_then(|c| c.window) {
+            window.shutdown();
+        }
+        if let Some(context) = self.context.take() {
+            context.shutdown();
+        }
+        for picker in self.pickers.drain(..) {
+            picker.shutdown();
+        }
+    }
+
+    fn window_event(
+        &mut self,
+        event_loop: &ActiveEventLoop,
+        id: winit::window::WindowId,
+        event: winit::event::WindowEvent,
+    ) {
+        if matches!(
+            event,
+            WindowEvent::KeyboardInput { .. }
+                | WindowEvent::MouseInput { .. }
+                | WindowEvent::MouseWheel { .. }
+                | WindowEvent::CursorMoved { .. }
+        ) {
+            self.last_input = Instant::now();
+        }
+
+        #[cfg(feature = "record")]
+        if let Some((_, indicator)) = &self.recording {
+            if indicator.as_ref().is_some_and(|i| i.window_id() == id) {
+                self.indicator_event(event);
+            }
+            return;
+        }
+        if let Some(countdown) = &self.countdown {
+            if countdown
+                .window
+                .as_ref()
+                .is_some_and(|w| w.window_id() == id)
+            {
+                self.countdown_event(event_loop, event);
+            }
+            return;
+        }
+        if let Some(index) = self.pickers.iter().position(|p| p.window_id() == id) {
+            self.picker_event(event_loop, index, event);
+            return;
+        }
+        let Some(context) = &mut self.context else {
+            return;
+        };
+        if id != context.window_id() {
+            return;
+        }
+
+        match event {
+            WindowEvent::RedrawRequested => {
+                context.draw();
+            }
+            WindowEvent::Resized(size) => context.resize(size),
+            WindowEvent::CursorMoved { position, .. } => {
+                context.update_mouse_position(position.x, position.y);
+            }
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        state,
+                        logical_key: key,
+                        repeat,
+                        ..
+                    },
+                ..
+            } => {
+                // Only the key's own repeats keep accelerating a move
+                if !repeat {
+                    self.held_move = None;
+                }
+                if context.is_typing() {
+                    if state.is_pressed() && context.type_key(&key) {
+                        self.execute_key_command(event_loop, Action::Confirm);
+                    }
+                    return;
+                }
+                if let Some(action) = self.bindings.action(state, &key, self.modifiers) {
+                    self.execute_key_command(event_loop, action);
+                }
+            }
+            WindowEvent::ModifiersChanged(modifiers) => self.modifiers = modifiers.state(),
+            WindowEvent::MouseInput { state, button, .. } => match (state, button) {
+                (ElementState::Pressed, MouseButton::Left) => context.start_drag(),
+                (ElementState::Released, MouseButton::Left) => {
+                    context.end_drag(self.modifiers.shift_key())
+                }
+                (ElementState::Pressed, MouseButton::Middle) => context.start_pan(),
+                (ElementState::Released, MouseButton::Middle) => context.end_pan(),
+                (_, MouseButton::Right) => context.cancel_drag(),
+                _ => {}
+            },
+            WindowEvent::MouseWheel { delta, .. } => {
+                let steps = match delta {
+                    MouseScrollDelta::LineDelta(_, y) => y,
+                    MouseScrollDelta::PixelDelta(pos) => pos.y as f32 / 40.0,
+                };
+                context.zoom(steps);
+            }
+            WindowEvent::CloseRequested => self.cancel(event_loop),
+            _ => {}
+        }
+    }
+}
+
+// Most likely cause of a failed start, given what was asked for
+fn startup_hint(args: &Args) -> &'static str {
+    match (&args.command, &args.monitor) {
+        (Some(Command::Edit { .. }), _) => {
+            "check that the input is a readable PNG, JPEG or other common image format"
+        }
+        _ if args.remote.is_some() => {
+            "check that the --remote command can run cleave agent from a shell"
+        }
+        _ if args.window.is_some() => "cleave --window-list shows the windows it can capture",
+        (_, MonitorTarget::Window) => "try --monitor primary if no window has focus",
+        (_, MonitorTarget::Named(_)) => "cleave --monitor-list shows the monitors it can capture",
+        (_, MonitorTarget::Primary | MonitorTarget::Pick) => {
+            "on Linux, capturing needs libxcb, libxrandr and dbus, see the readme"
+        }
+    }
+}
+
+/// Opens the overlay and runs it until a selection is confirmed or it's cancelled
+pub fn run(args: Args) -> Result<(), CleaveError> {
+    let notifier = args.notify.notifier(args.verbosity());
+    let mut app = App {
+        bindings: Keybindings::new(args.keymap, args.bindings.clone()),
+        args,
+        context: None,
+        pickers: Vec::new(),
+        countdown: None,
+        #[cfg(feature = "record")]
+        recording: None,
+        modifiers: ModifiersState::empty(),
+        last_input: Instant::now(),
+        held_move: None,
+        error: None,
+        copied: None,
+        live_capture: None,
+    };
+    let event_loop = winit::event_loop::EventLoop::new()
+        .map_err(anyhow::Error::from)
+        .or_report(notifier.as_ref(), CleaveError::Other)?;
+    event_loop
+        .run_app(&mut app)
+        .map_err(anyhow::Error::from)
+        .or_report(notifier.as_ref(), CleaveError::Other)?;
+    // The window and its clipboard handle are gone by now, like they will be after exit
+    if let Some(copied) = &app.copied {
+        if !output::verify_clipboard(&app.args, copied) {
+            app.error = Some(CleaveError::Delivery);
+        }
+    }
+    match app.error {
+        Some(err) => Err(err),
+        None => Ok(()),
+    }
+}
@@ -0,0 +1,870 @@
+use std::{net::SocketAddr, path::PathBuf, str::FromStr};
+
+use anyhow::Context;
+use clap::{ArgGroup, Parser, Subcommand, ValueEnum};
+use image::Rgba;
+
+use crate::{
+    keyboard::{Binding, Keymap},
+    notify::{NotifyBackend, Urgency, Verbosity},
+    palette, project, repo,
+    util::capture::MonitorInfo,
+};
+
+#[derive(Clone, Debug, Default)]
+pub enum MonitorTarget {
+    /// The primary monitor
+    #[default]
+    Primary,
+    /// The monitor showing the focused window
+    Window,
+    /// Number every monitor and ask which one to capture
+    Pick,
+    /// A monitor's ID, its number in --monitor-list, or part of its name
+    Named(String),
+}
+
+impl FromStr for MonitorTarget {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.trim() {
+            "primary" => Self::Primary,
+            "window" => Self::Window,
+            "pick" => Self::Pick,
+            "" => anyhow::bail!("Expected primary, window, pick or a monitor from --monitor-list"),
+            query => Self::Named(query.to_string()),
+        })
+    }
+}
+
+// Discriminants match `dim_style` in the shader
+#[derive(Clone, Copy, Debug, Default, ValueEnum)]
+pub enum DimStyle {
+    /// Leave the area outside the selection untouched
+    #[default]
+    None = 0,
+    /// Darken the area outside the selection
+    Dim = 1,
+    /// Blur the area outside the selection
+    Blur = 2,
+}
+
+// Discriminants match `border_style` in the shader
+/// How the outline of the selection is drawn
+#[derive(Clone, Copy, Debug, Default, ValueEnum)]
+pub enum BorderStyle {
+    /// Marching ants
+    #[default]
+    Dashed = 0,
+    /// A solid line
+    Solid = 1,
+}
+
+/// Mouse cursor over the capture while selecting
+#[derive(Clone, Copy, Debug, Default, ValueEnum)]
+pub enum CursorStyle {
+    #[default]
+    Crosshair,
+    /// A thick plus sign
+    Cell,
+    /// The usual arrow
+    Arrow,
+}
+
+/// How a copied capture is offered to other apps
+#[derive(Clone, Copy, Debug, Default, ValueEnum)]
+pub enum ClipboardFormat {
+    /// Whatever the platform does with images: PNG and a bitmap on Windows, PNG on Linux, TIFF on macOS
+    #[default]
+    Auto,
+    /// cleave's own PNG with alpha, through wl-copy or xclip (Linux only)
+    Png,
+}
+
+/// How `--exclude-window` and `--exclude-region` hide what they cover
+#[derive(Clone, Copy, Debug, Default, ValueEnum)]
+pub enum ExcludeStyle {
+    /// Solid black
+    #[default]
+    Black,
+    /// Blurred beyond reading
+    Blur,
+}
+
+/// DEFLATE effort for PNG output
+#[derive(Clone, Copy, Debug, Default, ValueEnum)]
+pub enum PngCompression {
+    #[default]
+    Fast,
+    Default,
+    /// Smallest files, noticeably slower on large captures
+    Best,
+}
+
+/// How colors are picked when `--colors` reduces the palette
+#[derive(Clone, Copy, Debug, Default, ValueEnum)]
+pub enum Dither {
+    /// Use the nearest palette color for every pixel
+    #[default]
+    None,
+    /// Floyd-Steinberg error diffusion, smoother gradients at the cost of noise
+    Floyd,
+}
+
+// Parsed from `--aspect` in every build, only the overlay reads it
+#[cfg_attr(not(feature = "gui"), allow(dead_code))]
+#[derive(Clone, Copy, Debug)]
+pub struct AspectRatio {
+    width: f32,
+    height: f32,
+}
+
+#[cfg(feature = "gui")]
+impl AspectRatio {
+    pub fn ratio(self) -> f32 {
+        self.width / self.height
+    }
+}
+
+impl FromStr for AspectRatio {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (width, height) = s
+            .split_once(':')
+            .with_context(|| format!("Expected WIDTH:HEIGHT, got {s:?}"))?;
+        let (width, height): (f32, f32) = (width.trim().parse()?, height.trim().parse()?);
+        if !(width > 0.0 && height > 0.0) {
+            anyhow::bail!("Aspect ratio sides must be positive");
+        }
+        Ok(Self { width, height })
+    }
+}
+
+/// What the X and Y of `--region` refer to
+#[derive(Clone, Copy, Debug, Default, ValueEnum)]
+pub enum RegionOrigin {
+    #[default]
+    TopLeft,
+    Center,
+}
+
+/// Outline drawn around the capture by `--border`
+#[derive(Clone, Copy, Debug)]
+pub struct Border {
+    pub width: u32,
+    pub color: Rgba<u8>,
+}
+
+impl FromStr for Border {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (width, color) = s.split_once(':').unwrap_or((s, "#000000"));
+        let width = width.trim().trim_end_matches("px");
+        let width: u32 = width
+            .parse()
+            .with_context(|| format!("Expected WIDTH[:COLOR], e.g. 4px:#ff0000, got {s:?}"))?;
+        if width == 0 {
+            anyhow::bail!("Border width must be at least one pixel");
+        }
+        Ok(Self {
+            width,
+            color: palette::parse_hex(color)?,
+        })
+    }
+}
+
+/// Part of the captured monitor or image, in its pixels
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Region {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Region {
+    /// The point halfway across and down, which may fall between pixels
+    pub fn center(self) -> (f64, f64) {
+        (
+            self.x as f64 + self.width as f64 / 2.0,
+            self.y as f64 + self.height as f64 / 2.0,
+        )
+    }
+
+    /// The part of the region inside a `width` by `height` capture, `None` if it's
+    /// entirely outside
+    pub fn fit_within(self, width: u32, height: u32) -> Option<Self> {
+        let right = self.x.saturating_add(self.width).min(width);
+        let bottom = self.y.saturating_add(self.height).min(height);
+        (right > self.x && bottom > self.y).then(|| Self {
+            width: right - self.x,
+            height: bottom - self.y,
+            ..self
+        })
+    }
+
+    /// The smallest region covering both
+    pub fn union(self, other: Self) -> Self {
+        let (x, y) = (self.x.min(other.x), self.y.min(other.y));
+        let right = (self.x + self.width).max(other.x + other.width);
+        let bottom = (self.y + self.height).max(other.y + other.height);
+        Self {
+            x,
+            y,
+            width: right - x,
+            height: bottom - y,
+        }
+    }
+
+    /// Whether the two regions share any pixels
+    #[cfg(feature = "record")]
+    pub fn overlaps(self, other: Self) -> bool {
+        self.x < other.x + other.width
+            && other.x < self.x + self.width
+            && self.y < other.y + other.height
+            && other.y < self.y + self.height
+    }
+
+    /// Grows the region by `pad` pixels on every side, without leaving a `width` by
+    /// `height` capture
+    pub fn padded(self, pad: u32, width: u32, height: u32) -> Self {
+        let (x, y) = (self.x.saturating_sub(pad), self.y.saturating_sub(pad));
+        let right = (self.x + self.width).saturating_add(pad).min(width);
+        let bottom = (self.y + self.height).saturating_add(pad).min(height);
+        Self {
+            x,
+            y,
+            width: right - x,
+            height: bottom - y,
+        }
+    }
+
+    /// Rounds odd sizes down to even ones for `--even-dimensions`. A single pixel grows to
+    /// two instead, moving back from the right or bottom of a `width` by `height` capture
+    /// if it has to. Only a capture a single pixel across keeps an odd size
+    pub fn even(self, width: u32, height: u32) -> Self {
+        let (x, width) = even_span(self.x, self.width, width);
+        let (y, height) = even_span(self.y, self.height, height);
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// Moves a region given around `origin` so that `x` and `y` are its top left corner
+    pub fn anchored(self, origin: RegionOrigin) -> anyhow::Result<Self> {
+        match origin {
+            RegionOrigin::TopLeft => Ok(self),
+            // Odd sizes put the extra pixel below and to the right of the center
+            RegionOrigin::Center => Ok(Self {
+                x: self.x.checked_sub(self.width / 2).with_context(|| {
+                    format!("Region centered at x {} starts left of the screen", self.x)
+                })?,
+                y: self.y.checked_sub(self.height / 2).with_context(|| {
+                    format!("Region centered at y {} starts above the screen", self.y)
+                })?,
+                ..self
+            }),
+        }
+    }
+
+    /// The point `origin` names, what `anchored` turns back into this region
+    #[cfg(feature = "gui")]
+    pub fn origin_point(self, origin: RegionOrigin) -> (u32, u32) {
+        match origin {
+            RegionOrigin::TopLeft => (self.x, self.y),
+            RegionOrigin::Center => (self.x + self.width / 2, self.y + self.height / 2),
+        }
+    }
+}
+
+impl FromStr for Region {
+    type Err = anyhow::Error;
+
+    // X11 geometry, as printed by slop and xwininfo, or X,Y,WIDTH,HEIGHT
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let expected = || format!("Expected WIDTHxHEIGHT+X+Y or X,Y,WIDTH,HEIGHT, got {s:?}");
+        let (x, y, width, height) = match s.split(',').collect::<Vec<_>>().as_slice() {
+            [x, y, width, height] => (*x, *y, *width, *height),
+            _ => {
+                let (size, position) = s.split_once('+').with_context(expected)?;
+                let (width, height) = size.split_once('x').with_context(expected)?;
+                let (x, y) = position.split_once('+').with_context(expected)?;
+                (x, y, width, height)
+            }
+        };
+        let region = Self {
+            x: x.trim().parse()?,
+            y: y.trim().parse()?,
+            width: width.trim().parse()?,
+            height: height.trim().parse()?,
+        };
+        if region.width == 0 || region.height == 0 {
+            anyhow::bail!("Region must be at least one pixel wide and tall");
+        }
+        Ok(region)
+    }
+}
+
+// Start and length along one axis of `Region::even`, within `0..limit`
+fn even_span(start: u32, length: u32, limit: u32) -> (u32, u32) {
+    match length {
+        length if length % 2 == 0 => (start, length),
+        1 if limit < 2 => (start, 1),
+        1 => (start.min(limit - 2), 2),
+        length => (start, length - 1),
+    }
+}
+
+impl std::fmt::Display for Region {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}x{}+{}+{}", self.width, self.height, self.x, self.y)
+    }
+}
+
+/// Part of every capture to hide, on one monitor or all of them
+#[derive(Clone, Debug)]
+pub struct ExcludedRegion {
+    // A monitor's name or ID, None for every capture
+    pub monitor: Option<String>,
+    pub region: Region,
+}
+
+impl ExcludedRegion {
+    pub fn applies_to(&self, monitor: Option<&MonitorInfo>) -> bool {
+        match (&self.monitor, monitor) {
+            (None, _) => true,
+            (Some(query), Some(monitor)) => {
+                monitor.name.eq_ignore_ascii_case(query) || query.parse() == Ok(monitor.id)
+            }
+            (Some(_), None) => false,
+        }
+    }
+}
+
+impl FromStr for ExcludedRegion {
+    type Err = anyhow::Error;
+
+    // Regions never contain a colon, monitor names rarely do
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.rsplit_once(':') {
+            Some((monitor, region)) => Ok(Self {
+                monitor: Some(monitor.trim().to_string()),
+                region: region.parse()?,
+            }),
+            None => Ok(Self {
+                monitor: None,
+                region: s.parse()?,
+            }),
+        }
+    }
+}
+
+/// How many pieces to cut the selection into, across and down
+#[derive(Clone, Copy, Debug)]
+pub struct TileGrid {
+    pub columns: u32,
+    pub rows: u32,
+}
+
+impl FromStr for TileGrid {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (columns, rows) = s
+            .to_lowercase()
+            .split_once('x')
+            .map(|(c, r)| (c.trim().to_string(), r.trim().to_string()))
+            .with_context(|| format!("Expected COLUMNSxROWS, got {s:?}"))?;
+        let (columns, rows): (u32, u32) = (columns.parse()?, rows.parse()?);
+        if columns == 0 || rows == 0 {
+            anyhow::bail!("Tile grid needs at least one column and one row");
+        }
+        Ok(Self { columns, rows })
+    }
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum Command {
+    /// Try capturing, the GPU, PNG encoding, the clipboard and notifications, and report
+    /// which of them work
+    Check,
+    /// Edit the config file in a small window
+    Settings,
+    /// Crop an existing image instead of the screen and write the result to stdout as PNG
+    Edit {
+        /// Image to open, or - to read it from stdin
+        input: PathBuf,
+    },
+    /// Capture this machine's screen whenever asked over stdin, for another cleave's
+    /// `--remote`
+    Agent,
+    /// Capture what a cleave://capture?region=...&sink=clipboard URL asks for, run by the
+    /// desktop once `cleave install-url-handler` registered it
+    Url { url: String },
+    /// Register cleave as the handler for cleave:// URLs with a desktop entry (Linux and BSD)
+    InstallUrlHandler,
+    /// Check that a PNG written with `--stamp-provenance` hasn't changed since
+    Verify { file: PathBuf },
+    /// Answer `POST /capture` requests over HTTP with a PNG of this machine's screen, for
+    /// test rigs and dashboards on the network
+    Serve {
+        /// Address and port to listen on
+        #[arg(long, default_value = "127.0.0.1:7878")]
+        listen: SocketAddr,
+        /// File holding the token requests have to send as `Authorization: Bearer TOKEN`
+        #[arg(long, value_name = "PATH")]
+        token_file: PathBuf,
+    },
+    /// Look through the captures tagged with `--prompt-tags`
+    History {
+        #[command(subcommand)]
+        command: HistoryCommand,
+    },
+    /// Show a notification about a written file with buttons to open it or copy its
+    /// path. Started in the background by `--notify desktop`
+    #[command(hide = true)]
+    FileActions {
+        #[arg(long, value_enum)]
+        urgency: Urgency,
+        file: PathBuf,
+        message: String,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum HistoryCommand {
+    /// Print the time, tags and files of every capture whose tags or file names contain TERM
+    Search { term: String },
+}
+
+#[derive(Parser, Debug, Clone)]
+#[command(version, about)]
+// What `--no-gui` can capture without a selection from the overlay
+#[command(group(
+    ArgGroup::new("headless_target")
+        .args(["region", "fullscreen", "find_text"])
+        .multiple(true)
+))]
+pub struct Args {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Capture this region without showing the overlay, e.g. 640x480+100+50 or 100,50,640,480
+    #[arg(long, value_name = "WIDTHxHEIGHT+X+Y")]
+    pub region: Option<Region>,
+
+    /// Whether the X and Y of --region, of coordinates typed in the overlay and of the
+    /// drag's size label are its top left corner or its center
+    #[arg(long, value_enum, default_value_t)]
+    pub region_origin: RegionOrigin,
+
+    /// Never open a window or touch the GPU, for headless and scripted captures.
+    /// Needs --region, --fullscreen or --find-text to know what to capture
+    #[arg(long, requires = "headless_target")]
+    pub no_gui: bool,
+
+    /// Capture where TEXT is on the screen, found with tesseract, instead of a fixed
+    /// region. Use --pad to take some of its surroundings too
+    #[arg(long, value_name = "TEXT", conflicts_with_all = ["region", "fullscreen"])]
+    pub find_text: Option<String>,
+
+    /// Capture the whole monitor straight away, without the overlay or a --region
+    #[arg(long, conflicts_with = "region")]
+    pub fullscreen: bool,
+
+    /// Only deliver the capture if it differs from the one whose hash is kept in this
+    /// file, then update it. Exits with 3 when nothing changed. Captures without the overlay
+    #[arg(long, value_name = "BASELINE")]
+    pub if_changed: Option<PathBuf>,
+
+    /// Keep output files and remembered state apart from other projects.
+    /// Relative output paths are put in a directory of this name
+    #[arg(long, value_name = "NAME", value_parser = project::parse_name)]
+    pub project: Option<String>,
+
+    /// Which monitor to capture: primary, window (the one showing the focused window),
+    /// pick (ask), or a monitor's ID, number or part of its name from --monitor-list
+    #[arg(long, value_name = "MONITOR", default_value = "primary")]
+    pub monitor: MonitorTarget,
+
+    /// Print every monitor with its number, ID, name, size, position and scale factor, and exit
+    #[arg(long)]
+    pub monitor_list: bool,
+
+    /// Capture another machine's screen through `cleave agent`, run by this command, e.g. "ssh host cleave"
+    #[arg(long, value_name = "COMMAND")]
+    pub remote: Option<String>,
+
+    /// Capture one window instead of a monitor: its ID, number or part of its title or app
+    /// name from --window-list. Takes precedence over --monitor
+    #[arg(long, value_name = "WINDOW")]
+    pub window: Option<String>,
+
+    /// Print every window with its number, title, app, ID, size, position and workspace, and exit
+    #[arg(long)]
+    pub window_list: bool,
+
+    /// Warn when the capture is nearly identical to the previous one
+    #[arg(long)]
+    pub warn_duplicate: bool,
+
+    /// Once the selection is confirmed, type tags for the capture before it's delivered.
+    /// They're written into saved PNGs and kept for `cleave history search`
+    #[arg(long, conflicts_with_all = ["no_gui", "headless_target", "if_changed"])]
+    pub prompt_tags: bool,
+
+    /// Show the live screen under the overlay instead of a frozen capture, and capture it
+    /// once the selection is confirmed. Needs a compositor that shows through transparent
+    /// windows, the loupe and zoom are off and blur dims instead
+    #[arg(
+        long,
+        conflicts_with_all = ["no_gui", "headless_target", "if_changed", "ocr_select", "window", "remote"]
+    )]
+    pub live: bool,
+
+    /// Don't record anything about this capture on disk
+    #[arg(long, alias = "no-history")]
+    pub private: bool,
+
+    /// List the files cleave saves in the desktop's recent documents, unless `--private`
+    #[arg(long)]
+    pub add_to_recent: bool,
+
+    /// Round the captured width and height down to even numbers, a single pixel up to two
+    #[arg(long)]
+    pub even_dimensions: bool,
+
+    /// Grow the selection by this many pixels on every side before cropping, as far as
+    /// the screen goes
+    #[arg(long, value_name = "PIXELS", default_value_t = 0)]
+    pub pad: u32,
+
+    /// Snap the selection to a grid with cells of this many pixels
+    #[arg(long, value_name = "PIXELS")]
+    pub grid: Option<u32>,
+
+    /// Pixels each arrow key press moves or resizes the selection by
+    #[arg(long, value_name = "PIXELS", default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+    pub step: u32,
+
+    /// Pixels each arrow key press moves or resizes the selection by while Alt is held
+    #[arg(long, value_name = "PIXELS", default_value_t = 10, value_parser = clap::value_parser!(u32).range(1..))]
+    pub big_step: u32,
+
+    /// How quickly holding an arrow key speeds it up, in extra steps per second held, 0 keeps the step constant
+    #[arg(long, value_name = "RATE", default_value_t = 2.0, value_parser = parse_acceleration)]
+    pub acceleration: f32,
+
+    /// Pull the selection's edges onto the screen's edges and center lines and the
+    /// edges of windows when they come within this many pixels
+    #[arg(long, value_name = "PIXELS")]
+    pub snap: Option<u32>,
+
+    /// Highlight lines of text under the cursor so a click selects the line and
+    /// Shift+click adds it to the selection. Text is recognized with tesseract
+    #[arg(long)]
+    pub ocr_select: bool,
+
+    /// Languages tesseract recognizes for --ocr-select and --find-text, such as eng or
+    /// deu+eng. all uses every installed language at once, which is slower and less
+    /// accurate than picking the right ones
+    #[arg(
+        long,
+        alias = "ocr-lang",
+        value_name = "LANGUAGES",
+        default_value = "all"
+    )]
+    pub ocr_language: String,
+
+    /// Download the models for --ocr-language that tesseract doesn't have installed into
+    /// the cache directory. They're checked against the SHA256SUMS file there before use
+    #[arg(long)]
+    pub ocr_download: bool,
+
+    /// Lock the selection to an aspect ratio, e.g. 16:9
+    #[arg(long, value_name = "WIDTH:HEIGHT")]
+    pub aspect: Option<AspectRatio>,
+
+    /// Keep the loupe on the corner moved with the arrow keys and announce the
+    /// selection size on stderr while adjusting it
+    #[arg(long)]
+    pub zoom_follow: bool,
+
+    /// Round the corners of the capture, leaving them transparent
+    #[arg(
+        long,
+        visible_alias = "radius",
+        value_name = "PIXELS",
+        default_value_t = 0.0,
+        value_parser = parse_corner_radius
+    )]
+    pub corner_radius: f32,
+
+    /// Outline the capture, following its rounded corners, e.g. 4px:#ff0000 (black if no
+    /// color is given)
+    #[arg(long, value_name = "WIDTH:COLOR")]
+    pub border: Option<Border>,
+
+    /// Put the capture on a transparent canvas with a soft drop shadow
+    #[arg(long)]
+    pub shadow: bool,
+
+    /// Set of key bindings used in the overlay
+    #[arg(long, value_enum, default_value_t)]
+    pub keymap: Keymap,
+
+    /// Bind a key to an action on top of the keymap, e.g. `x=cancel`, may be repeated
+    #[arg(long = "bind", value_name = "KEY=ACTION")]
+    pub bindings: Vec<Binding>,
+
+    /// How to draw the area outside the selection
+    #[arg(long, value_enum, default_value_t)]
+    pub dim_style: DimStyle,
+
+    /// How much `--dim-style dim` darkens the area outside the selection, from 0 to 1
+    #[arg(long, value_name = "OPACITY", default_value_t = 0.5, value_parser = parse_opacity)]
+    pub dim_opacity: f32,
+
+    /// Color of the selection's outline, with alpha for how much it covers the capture
+    #[arg(long, value_name = "COLOR", default_value = "#00ff0080", value_parser = palette::parse_hex)]
+    pub border_color: Rgba<u8>,
+
+    /// Thickness of the selection's outline in screen pixels
+    #[arg(long, value_name = "PIXELS", default_value_t = 2.0, value_parser = parse_border_thickness)]
+    pub border_thickness: f32,
+
+    /// Whether the selection's outline is dashed or solid
+    #[arg(long, value_enum, default_value_t)]
+    pub border_style: BorderStyle,
+
+    /// Draw rule-of-thirds lines inside the selection
+    #[arg(long)]
+    pub thirds: bool,
+
+    /// Mouse cursor while selecting; annotating and panning get their own
+    #[arg(long, value_enum, default_value_t)]
+    pub cursor: CursorStyle,
+
+    /// Physical pixels per logical pixel, for when the platform reports the wrong one.
+    /// Sizes the loupe, labels and HUD, and is the scale factor reported with `--json`
+    #[arg(long, value_name = "FACTOR", value_parser = parse_dpi_scale)]
+    pub dpi_scale: Option<f32>,
+
+    /// Blur radius for `--dim-style blur`; larger is softer but slower to prepare
+    #[arg(long, value_name = "PIXELS", default_value_t = 8)]
+    pub blur_radius: u32,
+
+    /// Where V saves a recording of the selection as GIF, or - for stdout
+    #[arg(long, value_name = "PATH", default_value = "cleave.gif")]
+    pub record_output: PathBuf,
+
+    /// How long V records for unless stopped sooner
+    #[arg(long, value_name = "SECONDS", default_value_t = 5, value_parser = clap::value_parser!(u64).range(1..))]
+    pub record_seconds: u64,
+
+    /// Frames per second captured while recording
+    #[arg(long, value_name = "FPS", default_value_t = 10, value_parser = clap::value_parser!(u32).range(1..=50))]
+    pub fps: u32,
+
+    /// How hard to compress PNG output
+    #[arg(long, value_enum, default_value_t)]
+    pub png_compression: PngCompression,
+
+    /// Quality of lossy output, from 0 to 100. Only PNG can be written so far, which is
+    /// lossless, so this has no effect yet
+    #[arg(long, value_name = "0-100", value_parser = clap::value_parser!(u8).range(0..=100))]
+    pub quality: Option<u8>,
+
+    /// Reduce the selection to at most this many colors, written as an indexed PNG
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u16).range(2..=256))]
+    pub colors: Option<u16>,
+
+    /// How to pick colors with `--colors`
+    #[arg(long, value_enum, default_value_t)]
+    pub dither: Dither,
+
+    /// Print the N dominant colors of the selection as hex values
+    #[arg(long, value_name = "N")]
+    pub palette: Option<usize>,
+
+    /// Also save the palette as a row of color squares to this PNG file
+    #[arg(long, value_name = "PATH", requires = "palette")]
+    pub palette_swatch: Option<PathBuf>,
+
+    /// Also save the selection as a PNG in this directory of the current git repository,
+    /// printing a markdown snippet with its path from the repository's root
+    #[arg(
+        long,
+        value_name = "DIR",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "docs/images",
+        value_parser = repo::parse_assets_dir
+    )]
+    pub repo_assets: Option<PathBuf>,
+
+    /// Experimental: also save the selection as an SVG of flat colored rectangles
+    #[arg(long, value_name = "PATH")]
+    pub vectorize: Option<PathBuf>,
+
+    /// Also save the selection for the web as PATH@2x and PATH@1x, the second scaled down
+    /// by the monitor's scale factor (2 for `cleave edit`)
+    #[arg(long, value_name = "PATH")]
+    pub retina_pair: Option<PathBuf>,
+
+    /// Hash the pixels and details of the capture into each PNG written, and print the
+    /// hashes, so `cleave verify` can show the file is unmodified
+    #[arg(long)]
+    pub stamp_provenance: bool,
+
+    /// Also save the selection split into a grid of tiles, e.g. 3x2
+    #[arg(long, value_name = "COLUMNSxROWS")]
+    pub tiles: Option<TileGrid>,
+
+    /// Start of the tile file names, each ends in -r<ROW>-c<COLUMN>.png
+    #[arg(long, value_name = "PATH", default_value = "cleave")]
+    pub tile_prefix: PathBuf,
+
+    /// After exiting the overlay, check that the capture is still on the clipboard and
+    /// copy it again if a clipboard manager lost it
+    #[arg(long)]
+    pub verify_clipboard: bool,
+
+    /// Put the path of the file the capture is saved to on the clipboard as text, in
+    /// place of the image. It's saved by --repo-assets, or in --fallback-dir when the
+    /// image can't be copied
+    #[arg(long, conflicts_with_all = ["stdout", "verify_clipboard"])]
+    pub copy_path: bool,
+
+    /// Print a JSON description of the capture once it has been copied or written.
+    /// Goes to stderr when the image itself goes to stdout
+    #[arg(long)]
+    pub json: bool,
+
+    /// Cancel the capture after this many seconds without keyboard or mouse input, counted
+    /// from the end of `--delay`
+    #[arg(long, value_name = "SECONDS")]
+    pub timeout: Option<u64>,
+
+    /// Wait this many seconds before capturing, counting down in a small window
+    #[arg(long, value_name = "SECONDS", default_value_t = 0)]
+    pub delay: u64,
+
+    /// Write the selection to stdout as PNG instead of copying it to the clipboard
+    #[arg(long)]
+    pub stdout: bool,
+
+    /// Hide windows whose title or app name contains this text before showing the overlay, may be repeated
+    #[arg(long, value_name = "TEXT")]
+    pub exclude_window: Vec<String>,
+
+    /// Blank out this part of every capture, such as a ticker or a webcam overlay. Prefix it
+    /// with a monitor's name or ID to only hide it there, e.g. DP-1:320x240+1600+840. May be repeated
+    #[arg(long, value_name = "[MONITOR:]WIDTHxHEIGHT+X+Y")]
+    pub exclude_region: Vec<ExcludedRegion>,
+
+    /// How excluded windows and regions are hidden
+    #[arg(long, value_enum, default_value_t)]
+    pub exclude_style: ExcludeStyle,
+
+    /// Largest width or height to fall back to when the clipboard rejects a capture, the
+    /// full-size one is saved to `--fallback-dir`
+    #[arg(long, value_name = "PIXELS", default_value_t = 4096)]
+    pub clipboard_max_dimension: u32,
+
+    /// How the capture is put on the clipboard, try png if pasting gives a blank or flattened image
+    #[arg(long, value_enum, default_value_t)]
+    pub clipboard_format: ClipboardFormat,
+
+    /// Where the capture is saved as PNG when it can't be copied to the clipboard, such as
+    /// over SSH, or only a downscaled copy could be. Defaults to the pictures directory, or
+    /// the current one without it. Relative paths and the default go in the --project
+    /// directory
+    #[arg(long, value_name = "DIR")]
+    pub fallback_dir: Option<PathBuf>,
+
+    /// Where to send capture feedback
+    #[arg(long, value_enum, default_value_t)]
+    pub notify: NotifyBackend,
+
+    /// Only report warnings and errors
+    #[arg(long, short)]
+    pub quiet: bool,
+
+    /// Also report what each step did and how long it took
+    #[arg(long, short, conflicts_with = "quiet")]
+    pub verbose: bool,
+
+    /// Print how long each step of opening the overlay took, once it's shown
+    #[arg(long)]
+    pub timings: bool,
+
+    /// Config file with defaults for these options [default: ~/.config/cleave/config.toml]
+    #[arg(long, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+
+    /// Print the effective options in config file format and exit
+    #[arg(long)]
+    pub dump_config: bool,
+}
+
+impl Args {
+    pub fn verbosity(&self) -> Verbosity {
+        match (self.quiet, self.verbose) {
+            (true, _) => Verbosity::Quiet,
+            (_, true) => Verbosity::Verbose,
+            _ => Verbosity::Normal,
+        }
+    }
+}
+
+fn parse_opacity(s: &str) -> anyhow::Result<f32> {
+    let opacity: f32 = s.trim().parse()?;
+    anyhow::ensure!(
+        (0.0..=1.0).contains(&opacity),
+        "Opacity must be between 0 and 1, got {s:?}"
+    );
+    Ok(opacity)
+}
+
+fn parse_border_thickness(s: &str) -> anyhow::Result<f32> {
+    let thickness: f32 = s.trim().trim_end_matches("px").parse()?;
+    anyhow::ensure!(
+        thickness.is_finite() && (0.5..=32.0).contains(&thickness),
+        "Border thickness must be between 0.5 and 32 pixels, got {s:?}"
+    );
+    Ok(thickness)
+}
+
+fn parse_corner_radius(s: &str) -> anyhow::Result<f32> {
+    let radius: f32 = s.trim().trim_end_matches("px").parse()?;
+    anyhow::ensure!(
+        radius.is_finite() && radius >= 0.0,
+        "Corner radius must be a number of pixels, 0 or more, got {s:?}"
+    );
+    Ok(radius)
+}
+
+fn parse_acceleration(s: &str) -> anyhow::Result<f32> {
+    let rate: f32 = s.trim().parse()?;
+    anyhow::ensure!(
+        rate.is_finite() && rate >= 0.0,
+        "Acceleration can't be negative, got {s:?}"
+    );
+    Ok(rate)
+}
+
+fn parse_dpi_scale(s: &str) -> anyhow::Result<f32> {
+    let scale: f32 = s.trim().parse()?;
+    anyhow::ensure!(
+        scale.is_finite() && (0.25..=8.0).contains(&scale),
+        "Scale factor must be between 0.25 and 8, got {s:?}"
+    );
+    Ok(scale)
+}
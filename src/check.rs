@@ -0,0 +1,87 @@
+use image::{Rgba, RgbaImage};
+
+#[cfg(feature = "notifications")]
+use crate::notify::{DesktopNotifier, Urgency};
+use crate::{
+    args::{Args, PngCompression},
+    util::{self, capture, clipboard},
+};
+
+// Passing checks describe what they found
+type Check = fn(&Args) -> anyhow::Result<String>;
+
+/// Runs every check and prints one line per component, for pasting into bug reports.
+/// Returns whether all of them passed
+pub fn run(args: &Args) -> bool {
+    let checks: [(&str, Check); 5] = [
+        ("capture", check_capture),
+        ("gpu", check_gpu),
+        ("png", check_png),
+        ("clipboard", check_clipboard),
+        ("notify", check_notify),
+    ];
+    println!("cleave {}", env!("CARGO_PKG_VERSION"));
+    let mut passed = true;
+    for (name, check) in checks {
+        match check(args) {
+            Ok(detail) => println!("  ok    {name:<10} {detail}"),
+            Err(err) => {
+                println!("  FAIL  {name:<10} {err:#}");
+                passed = false;
+            }
+        }
+    }
+    passed
+}
+
+fn check_capture(args: &Args) -> anyhow::Result<String> {
+    let backend = capture::backend(args);
+    let capture = backend.capture(args)?;
+    let (width, height) = capture.image.dimensions();
+    let monitor = match &capture.monitor {
+        Some(monitor) => format!(" of {} at {}x scale", monitor.name, monitor.scale_factor),
+        None => String::new(),
+    };
+    Ok(format!("{width}x{height}{monitor} via {}", backend.name()))
+}
+
+#[cfg(feature = "gui")]
+fn check_gpu(_: &Args) -> anyhow::Result<String> {
+    let info = pollster::block_on(cleave_graphics::prelude::probe_device())?;
+    Ok(format!("{} ({:?})", info.name, info.backend))
+}
+
+// Headless captures never touch the GPU
+#[cfg(not(feature = "gui"))]
+fn check_gpu(_: &Args) -> anyhow::Result<String> {
+    Ok("not needed, built without the gui feature".to_string())
+}
+
+fn check_png(_: &Args) -> anyhow::Result<String> {
+    let img = RgbaImage::from_fn(8, 8, |x, y| Rgba([x as u8 * 32, y as u8 * 32, 128, 255]));
+    let mut png = Vec::new();
+    util::write_png(&img, PngCompression::default(), &mut png)?;
+    let decoded = image::load_from_memory(&png)?.to_rgba8();
+    anyhow::ensure!(decoded == img, "Decoded image differs from the original");
+    Ok(format!(
+        "encoded and decoded {}",
+        util::human_size(png.len())
+    ))
+}
+
+fn check_clipboard(_: &Args) -> anyhow::Result<String> {
+    clipboard::roundtrip()?;
+    Ok("copied and read back an image".to_string())
+}
+
+#[cfg(feature = "notifications")]
+fn check_notify(_: &Args) -> anyhow::Result<String> {
+    DesktopNotifier.show(Urgency::Info, "cleave check: notifications work")?;
+    Ok("sent a test notification".to_string())
+}
+
+// Messages only go to stderr, which can't fail
+#[cfg(not(feature = "notifications"))]
+fn check_notify(_: &Args) -> anyhow::Result<String> {
+    Ok("not needed, built without the notifications feature".to_string())
+}
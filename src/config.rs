@@ -0,0 +1,281 @@
+use std::{ffi::OsString, path::PathBuf};
+
+use anyhow::Context;
+use clap::{
+    parser::ValueSource, Arg, ArgAction, ArgMatches, Command, CommandFactory, FromArgMatches, Id,
+};
+use toml_edit::{DocumentMut, Item, Value};
+
+use crate::{args::Args, util};
+
+// Options that only make sense on the command line
+const CLI_ONLY: [&str; 6] = [
+    "config",
+    "dump-config",
+    "help",
+    "monitor-list",
+    "version",
+    "window-list",
+];
+
+fn default_config_path() -> Option<PathBuf> {
+    Some(dirs::config_dir()?.join("cleave").join("config.toml"))
+}
+
+/// The file `load` reads defaults from, `--config` or the default location
+#[cfg(feature = "gui")]
+pub fn config_path(args: &Args) -> Option<PathBuf> {
+    args.config.clone().or_else(default_config_path)
+}
+
+/// Every option that can be set in the config file
+pub fn options() -> Vec<Arg> {
+    Args::command()
+        .get_arguments()
+        .filter(|arg| arg.get_long().is_some_and(|long| !CLI_ONLY.contains(&long)))
+        .cloned()
+        .collect()
+}
+
+/// Checks that `text` would load, the same way `load` reads the config file
+#[cfg(feature = "gui")]
+pub fn validate(text: &str) -> anyhow::Result<()> {
+    let args = std::iter::once(OsString::from("cleave"))
+        .chain(config_args(text)?.into_iter().flat_map(|(_, args)| args));
+    // Only the first line, the rest is clap pointing at --help
+    let first_line = |err: clap::Error| {
+        let message = err.to_string();
+        let line = message.lines().next().unwrap_or_default();
+        anyhow::anyhow!("{}", line.trim_start_matches("error: "))
+    };
+    let matches = Args::command()
+        .args_override_self(true)
+        .try_get_matches_from(args)
+        .map_err(first_line)?;
+    Args::from_arg_matches(&matches).map_err(first_line)?;
+    Ok(())
+}
+
+/// Parses the command line on top of the defaults in the config file.
+///
+/// Config keys are the long option names, so `corner-radius = 6` in the file acts
+/// like `--corner-radius 6` on the command line. Options given on the command line
+/// replace the file's, and so does anything they conflict with: `--stdout` drops
+/// `copy-path = true`. `--FLAG=false` turns off a flag the file turns on.
+///
+/// The matches are returned as well, for `dump_config`.
+pub fn load() -> anyhow::Result<(Args, ArgMatches)> {
+    let command = Args::command();
+    let (cli, cleared) = cleared_flags(&command, std::env::args_os());
+    // Only to see what was given, errors are reported once the file is merged in
+    let given = command
+        .clone()
+        .ignore_errors(true)
+        .try_get_matches_from(&cli)?;
+    let explicit = given.get_one::<PathBuf>("config").cloned();
+
+    let file_args = match explicit.clone().or_else(default_config_path) {
+        Some(path) if explicit.is_some() || path.exists() => {
+            let text = std::fs::read_to_string(&path)
+                .with_context(|| format!("Could not read config file {}", path.display()))?;
+            config_args(&text).with_context(|| format!("Invalid config file {}", path.display()))?
+        }
+        _ => Vec::new(),
+    };
+
+    let on_command_line: Vec<&Arg> = command
+        .get_arguments()
+        .filter(|arg| given.value_source(arg.get_id().as_str()) == Some(ValueSource::CommandLine))
+        .collect();
+    let overridden = |arg: &Arg| {
+        cleared.contains(arg.get_id())
+            || on_command_line.iter().any(|given| {
+                given.get_id() == arg.get_id()
+                    || conflicts(&command, given, arg)
+                    || conflicts(&command, arg, given)
+            })
+    };
+    let (program, rest) = cli.split_first().context("Missing program name")?;
+    let merged = std::iter::once(program.clone())
+        .chain(
+            file_args
+                .into_iter()
+                .filter(|(arg, _)| !overridden(arg))
+                .flat_map(|(_, args)| args),
+        )
+        .chain(rest.iter().cloned());
+    let matches = command
+        .clone()
+        .args_override_self(true)
+        .try_get_matches_from(merged)?;
+    let args = Args::from_arg_matches(&matches)?;
+    Ok((args, matches))
+}
+
+fn conflicts(command: &Command, arg: &Arg, other: &Arg) -> bool {
+    command
+        .get_arg_conflicts_with(arg)
+        .iter()
+        .any(|conflict| conflict.get_id() == other.get_id())
+}
+
+// Takes `--FLAG=false` and `--FLAG=true` out of the command line, which clap doesn't
+// accept for flags. `true` is the same as the bare flag, `false` leaves it off even
+// when the config file turns it on
+fn cleared_flags(
+    command: &Command,
+    cli: impl IntoIterator<Item = OsString>,
+) -> (Vec<OsString>, Vec<Id>) {
+    let mut kept = Vec::new();
+    let mut cleared = Vec::new();
+    let mut cli = cli.into_iter();
+    for token in cli.by_ref() {
+        if token == "--" {
+            kept.push(token);
+            break;
+        }
+        let flag = token
+            .to_str()
+            .and_then(|token| token.strip_prefix("--")?.split_once('='))
+            .and_then(|(long, value)| {
+                let arg = command.get_arguments().find(|arg| {
+                    arg.get_long() == Some(long) && matches!(arg.get_action(), ArgAction::SetTrue)
+                })?;
+                Some((arg, value.parse::<bool>().ok()?))
+            });
+        match flag {
+            Some((arg, true)) => {
+                kept.push(format!("--{}", arg.get_long().unwrap_or_default()).into())
+            }
+            Some((arg, false)) => cleared.push(arg.get_id().clone()),
+            None => kept.push(token),
+        }
+    }
+    kept.extend(cli);
+    (kept, cleared)
+}
+
+// Turns every `key = value` into the equivalent command line arguments, along with the
+// option they set
+fn config_args(text: &str) -> anyhow::Result<Vec<(Arg, Vec<OsString>)>> {
+    let doc: DocumentMut = text.parse()?;
+    let command = Args::command();
+    let mut args = Vec::new();
+    for (key, item) in doc.iter() {
+        let long = key.replace('_', "-");
+        let arg = command
+            .get_arguments()
+            .find(|a| a.get_long() == Some(long.as_str()) && !CLI_ONLY.contains(&long.as_str()))
+            .with_context(|| format!("Unknown option `{key}`"))?;
+        let Item::Value(value) = item else {
+            anyhow::bail!("`{key}` must be a value, not a table");
+        };
+
+        if matches!(arg.get_action(), ArgAction::SetTrue) {
+            let Value::Boolean(set) = value else {
+                anyhow::bail!("`{key}` must be true or false");
+            };
+            if *set.value() {
+                args.push((arg.clone(), vec![format!("--{long}").into()]));
+            }
+            continue;
+        }
+
+        let values = match value {
+            Value::Array(array) => array.iter().collect(),
+            value => vec![value],
+        };
+        let mut arg_values = Vec::new();
+        for value in values {
+            let value = match value {
+                Value::String(s) => s.value().clone(),
+                Value::Integer(i) => i.value().to_string(),
+                Value::Float(f) => f.value().to_string(),
+                Value::Boolean(b) => b.value().to_string(),
+                _ => anyhow::bail!("`{key}` must be a string, number or boolean"),
+            };
+            arg_values.push(format!("--{long}={value}").into());
+        }
+        args.push((arg.clone(), arg_values));
+    }
+    Ok(args)
+}
+
+/// Every option with its effective value, in the format `load` reads
+pub fn dump_config(matches: &ArgMatches) -> String {
+    let mut out = String::new();
+    for arg in options() {
+        let Some(long) = arg.get_long() else {
+            continue;
+        };
+        let id = arg.get_id().as_str();
+        let value = if matches!(arg.get_action(), ArgAction::SetTrue) {
+            matches.get_flag(id).to_string()
+        } else {
+            let values: Vec<String> = matches
+                .get_raw(id)
+                .into_iter()
+                .flatten()
+                .map(|v| toml_value(&v.to_string_lossy()))
+                .collect();
+            match (arg.get_action(), values.as_slice()) {
+                (ArgAction::Append, values) => format!("[{}]", values.join(", ")),
+                (_, [value]) => value.clone(),
+                // Options without a default that weren't given
+                _ => {
+                    out.push_str(&format!("# {long} =\n"));
+                    continue;
+                }
+            }
+        };
+        out.push_str(&format!("{long} = {value}\n"));
+    }
+    out
+}
+
+// Numbers stay bare, everything else becomes a basic string
+fn toml_value(raw: &str) -> String {
+    if raw.parse::<i64>().is_ok() || raw.parse::<f64>().is_ok_and(f64::is_finite) {
+        return raw.to_string();
+    }
+    util::quote(raw)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::OsString;
+
+    use clap::CommandFactory;
+
+    use super::cleared_flags;
+    use crate::args::Args;
+
+    fn cli(args: &[&str]) -> Vec<OsString> {
+        args.iter().map(OsString::from).collect()
+    }
+
+    #[test]
+    fn false_flags_are_cleared_and_true_ones_kept() {
+        let (kept, cleared) = cleared_flags(
+            &Args::command(),
+            cli(&[
+                "cleave",
+                "--shadow=false",
+                "--stdout=true",
+                "--corner-radius=4",
+            ]),
+        );
+        assert_eq!(kept, cli(&["cleave", "--stdout", "--corner-radius=4"]));
+        assert_eq!(cleared, ["shadow"]);
+    }
+
+    #[test]
+    fn arguments_after_the_separator_are_left_alone() {
+        let (kept, cleared) = cleared_flags(
+            &Args::command(),
+            cli(&["cleave", "edit", "--", "--shadow=false"]),
+        );
+        assert_eq!(kept, cli(&["cleave", "edit", "--", "--shadow=false"]));
+        assert!(cleared.is_empty());
+    }
+}
@@ -1,28 +1,45 @@
+use std::{collections::VecDeque, time::Duration};
+
+#[cfg(feature = "record")]
 use anyhow::Context;
-use arboard::ImageData;
-use glam::{DVec2, Vec2};
-use image::{GenericImageView, ImageBuffer, Rgba};
+
+use glam::{DVec2, Vec2, Vec4};
+use image::{GenericImageView, ImageBuffer, Rgba, RgbaImage};
 // use pixels::{Pixels, SurfaceTexture};
 use winit::{
-    dpi::PhysicalSize,
-    window::{Icon, Window, WindowAttributes},
+    dpi::{PhysicalPosition, PhysicalSize},
+    keyboard::{Key, NamedKey},
+    window::{CursorIcon, Fullscreen, Icon, Window, WindowAttributes, WindowLevel},
 };
 
 // use crate::{graphics_bundle::GraphicsBundle, graphics_impl::Graphics};
 use cleave_graphics::prelude::*;
 
-pub enum MoveMode {
-    Move,          // Move the selection
-    InverseResize, // Make the selection smaller
-    Resize,        // Make the selection larger
-}
+#[cfg(feature = "record")]
+use crate::util::record::Recording;
+use crate::{
+    annotate::{Annotations, Tool},
+    args::{Args, Command, CursorStyle, DimStyle, MonitorTarget, Region},
+    history::{self, History},
+    keyboard::{Direction, MoveMode},
+    notify::{Notifier, NotifyBackend, Urgency},
+    ocr::{Languages, TextLines},
+    output::{Delivered, Output},
+    snap::Guides,
+    timings::Timings,
+    util::{
+        self,
+        capture::{self, Capture, MonitorInfo, WindowInfo},
+    },
+};
 
-pub enum Direction {
-    Up,
-    Down,
-    Left,
-    Right,
-}
+const MAX_ZOOM: f32 = 16.0;
+// Least time between selection size announcements with `--zoom-follow`
+const ANNOUNCE_INTERVAL: Duration = Duration::from_secs(2);
+const COUNTDOWN_SIZE: (u32, u32) = (480, 280); // Fits three digits of the picker's label
+const ZOOM_STEP: f32 = 1.25; // Zoom factor per scroll wheel line
+const HUD_FRAMES: usize = 32; // Frame times kept for the HUD graph
+const MAX_INPUT: usize = 32; // Characters that fit in the uniforms
 
 #[repr(C)]
 #[derive(bytemuck::Pod, bytemuck::Zeroable, Copy, Clone, Default, Debug)]
@@ -33,13 +50,40 @@ pub struct SelectionUniforms {
     selection_start: Vec2,
     selection_end: Vec2,
     time: f32,
-    is_dragging: u32, // 0 = None, 1 = Dragging, 2 = Selected, 3 = Both
+    is_dragging: u32,        // 0 = None, 1 = Dragging, 2 = Selected, 3 = Both
+    selection_adjusted: u32, // 1 if the saved crop will differ from the selection
+    grid_size: f32,          // 0 = No grid
+    view_offset: Vec2,       // Image coords of the top left of the screen
+    zoom: f32,
+    dim_style: u32, // 0 = None, 1 = Dim, 2 = Blur
+    corner_radius: f32,
+    show_loupe: u32,
+    mouse: Vec2,        // Image coords of the cursor, for the loupe
+    monitor_label: u32, // 0 = None, otherwise the number shown by the monitor picker
+    show_hud: u32,
+    hud_stats: [u32; 4], // Capture, upload, frame and GPU time in tenths of a ms, u32::MAX = Unknown
+    frame_times: [Vec4; 8], // Last `HUD_FRAMES` frame times in ms, oldest first
+    input_len: u32,      // 0 = No coordinate input, otherwise 1 + the characters typed
+    ui_scale: f32,       // Screen pixels per logical pixel of the loupe, labels and HUD
+    live: u32,           // 1 = The screen shows through the window with `--live`
+    _padding: u32,
+    input_glyphs: [u32; 8], // Typed characters as glyph numbers, 4 to a u32 from the low byte
+    drag_label_len: u32,    // 0 = No drag, otherwise the characters in `drag_label`
+    _label_padding: [u32; 3],
+    drag_label: [u32; 8], // "WxH @ (X,Y)" of the drag, packed like `input_glyphs`
+    padded_bounds: Vec4, // Selection grown by `--pad` as min x, min y, max x, max y, all 0 = No padding
+    text_bounds: Vec4, // Line of text under the cursor with `--ocr-select`, laid out like `padded_bounds`
+    border_color: Vec4, // Selection outline as RGBA from 0 to 1, alpha is how much it covers the capture
+    dim_opacity: f32,
+    border_thickness: f32, // Screen pixels
+    border_style: u32,     // 0 = Dashed, 1 = Solid
+    show_thirds: u32,
 }
 
 impl std::fmt::Display for SelectionUniforms {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "size: {:?}, is_dragging: {}, drag_start: {:?}, drag_end: {:?}, selection_start: {:?}, selection_end: {:?}, time: {}", 
-          self.screen_size, self.is_dragging, self.drag_start, self.drag_end, self.selection_start, self.selection_end, self.time)
+        write!(f, "size: {:?}, is_dragging: {}, drag_start: {:?}, drag_end: {:?}, selection_start: {:?}, selection_end: {:?}, time: {}, selection_adjusted: {}, grid_size: {}, view_offset: {:?}, zoom: {}, dim_style: {}, corner_radius: {}, show_loupe: {}, mouse: {:?}, monitor_label: {}, show_hud: {}, hud_stats: {:?}, input_len: {}, ui_scale: {}, drag_label_len: {}", 
+          self.screen_size, self.is_dragging, self.drag_start, self.drag_end, self.selection_start, self.selection_end, self.time, self.selection_adjusted, self.grid_size, self.view_offset, self.zoom, self.dim_style, self.corner_radius, self.show_loupe, self.mouse, self.monitor_label, self.show_hud, self.hud_stats, self.input_len, self.ui_scale, self.drag_label_len)
     }
 }
 
@@ -49,15 +93,53 @@ pub struct Drag {
     end: Option<Vec2>,
 }
 
-#[derive(Clone, Copy, Debug)]
+impl Drag {
+    // Whole pixels covered so far, the same way a finished selection is cropped
+    fn region(&self) -> Region {
+        let end = self.end.unwrap_or(self.start);
+        let (min, max) = (self.start.min(end).ceil(), self.start.max(end).floor());
+        let size = (max - min).max(Vec2::ZERO);
+        Region {
+            x: min.x as u32,
+            y: min.y as u32,
+            width: size.x as u32,
+            height: size.y as u32,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Selection {
     start: Vec2,
     end: Vec2,
 }
 
+impl Selection {
+    // The smallest selection covering both, from its top left to its bottom right
+    fn union(self, other: Self) -> Self {
+        Self {
+            start: self.start.min(self.end).min(other.start.min(other.end)),
+            end: self.start.max(self.end).max(other.start.max(other.end)),
+        }
+    }
+}
+
+impl From<Region> for Selection {
+    fn from(region: Region) -> Self {
+        let start = Vec2::new(region.x as f32, region.y as f32);
+        Self {
+            start,
+            end: start + Vec2::new(region.width as f32, region.height as f32),
+        }
+    }
+}
+
 pub struct UserSelection {
     drag: Option<Drag>,
     selection: Option<Selection>,
+    history: History<Option<Selection>>,
+    // Set while the move keys are adjusting the selection, so a run of presses undoes at once
+    nudging: bool,
 }
 
 impl UserSelection {
@@ -65,189 +147,795 @@ impl UserSelection {
         Self {
             drag: None,
             selection: None,
+            history: History::new(),
+            nudging: false,
         }
     }
 
-    fn sel_coords(&self) -> Option<((u32, u32), (u32, u32))> {
-        let selection = self.selection.as_ref()?;
-        let (start_x, start_y) = (selection.start.x, selection.start.y);
-        let (end_x, end_y) = (selection.end.x, selection.end.y);
-
-        let (min_x, max_x) = (start_x.min(end_x).ceil(), start_x.max(end_x).floor());
-        let (min_y, max_y) = (start_y.min(end_y).ceil(), start_y.max(end_y).floor());
-        Some(((min_x as u32, min_y as u32), (max_x as u32, max_y as u32)))
+    // Replaces the selection, remembering the old one if it changed
+    fn set(&mut self, selection: Option<Selection>) {
+        if selection != self.selection {
+            self.history.record(self.selection);
+        }
+        self.selection = selection;
+        self.nudging = false;
     }
 
-    fn sel_dimensions(&self) -> Option<(f32, f32)> {
+    // The whole pixels the selection covers, none when it doesn't cover any
+    fn sel_coords(&self) -> Option<((u32, u32), (u32, u32))> {
         let selection = self.selection.as_ref()?;
-        let width = (selection.end.x - selection.start.x).abs();
-        let height = (selection.end.y - selection.start.y).abs();
-        Some((width, height))
+        let Region {
+            x,
+            y,
+            width,
+            height,
+        } = whole_pixels(selection.start, selection.end)?;
+        Some(((x, y), (x + width, y + height)))
     }
-
-    // fn get_
 }
 
 pub struct AppContext {
     size: PhysicalSize<u32>,
+    // The window can be bigger or smaller than the capture when the compositor scales it
+    window_size: PhysicalSize<u32>,
+    // Cursor position in the window, scaled to capture pixels
     mouse_position: DVec2,
+    // Last mouse position while panning with the middle button
+    pan_anchor: Option<DVec2>,
+    view_offset: Vec2,
+    zoom: f32,
+    corner_radius: f32,
+    aspect_lock: bool,
     selection: UserSelection,
+    // Mouse drags draw annotations instead of selecting while set
+    annotating: bool,
+    annotations: Annotations,
     // current_drag: Option<Drag>,
     // selection: Option<Selection>,
     image: ImageBuffer<Rgba<u8>, Vec<u8>>,
+    // The screen shows through the overlay with `--live`, `image` is only captured again
+    // once it's confirmed
+    live: bool,
     // pixels: Pixels<'static>,
     total_time: f32,
     last_frame: std::time::Instant,
-    graphics: Graphics<Window>,
+    // Declared before `graphics` so its GPU resources drop before the surface and window
     bundle: GraphicsBundle<SelectionUniforms>,
+    graphics: Graphics<Window>,
     mode: MoveMode,
+    // Whether the loupe is on the corner moved with the arrow keys, until the mouse moves
+    following_keys: bool,
+    // Selection last announced with `--zoom-follow`, and when
+    announced: Option<Region>,
+    last_announcement: std::time::Instant,
+    // Performance overlay toggled with F12
+    show_hud: bool,
+    capture_time: Duration,
+    upload_time: Duration,
+    frame_times: VecDeque<f32>,
+    // Typed `x,y,w,h` while entering the selection's coordinates
+    coordinate_input: Option<String>,
+    // Tags being typed for `--prompt-tags`, while the confirmed capture waits for them
+    tag_input: Option<String>,
+    // What was typed for `--prompt-tags`, the capture can be delivered once it's set
+    tags: Option<String>,
+    // Last cursor given to the window, so it's only set on changes
+    cursor: Option<CursorIcon>,
+    // Edges the selection snaps to with `--snap`
+    guides: Option<Guides>,
+    // Lines of text a click selects with `--ocr-select`
+    text_lines: Option<TextLines>,
+    notifier: Box<dyn Notifier>,
+    // For the time taken in the summary
+    started: std::time::Instant,
+    // The captured monitor, to find it again for recording
+    monitor: Option<MonitorInfo>,
+    // The window captured with `--window`, for `--json`
+    captured_window: Option<WindowInfo>,
+    // Startup steps with `--timings`, until the first frame is shown
+    timings: Option<Timings>,
+    args: Args,
 }
 
 impl AppContext {
     pub fn start_drag(&mut self) {
-        if let Some(drag) = self.selection.drag.as_mut() {
-            if drag.start != Vec2::ZERO {
-                return;
-            }
-        };
+        if self.annotating {
+            let position = self.image_position(self.mouse_position.as_vec2());
+            self.annotations
+                .start(position.clamp(Vec2::ZERO, self.bounds()));
+            return;
+        }
+        // Already dragging, the press that started it counts
+        if self.selection.drag.is_some() {
+            return;
+        }
+        let start = self.snapped_mouse_position();
         self.selection.drag = Some(Drag {
-            start: self.mouse_position.as_vec2(),
-            end: Some(self.mouse_position.as_vec2()),
+            start,
+            end: Some(start),
         });
     }
 
-    pub fn end_drag(&mut self) {
-        self.selection.selection = None;
-        if let Some(drag) = self.selection.drag.take() {
+    /// Finishes the drag as the new selection. With `--ocr-select` a click on a line of
+    /// text selects the line instead, or adds it to the selection when `adding`
+    pub fn end_drag(&mut self, adding: bool) {
+        if self.annotating {
+            self.annotations.finish();
+            return;
+        }
+        let selection = self.selection.drag.take().map(|drag| {
             let end_pos = drag.end.unwrap_or(drag.start); // Use end if set, otherwise use start
-            self.selection.selection = Some(Selection {
+            Selection {
                 start: drag.start,
                 end: end_pos,
-            });
+            }
+        });
+        let clicked_line = selection
+            .filter(|s| s.start.distance(s.end) < 1.0)
+            .and_then(|_| self.hovered_line())
+            .map(Selection::from);
+        let selection = match (clicked_line, self.selection.selection) {
+            (Some(line), Some(current)) if adding => Some(current.union(line)),
+            (Some(line), _) => Some(line),
+            (None, _) => selection,
+        };
+        self.selection.set(selection);
+    }
+
+    // The line of text a click would select with `--ocr-select`
+    fn hovered_line(&self) -> Option<Region> {
+        if self.annotating || self.selection.drag.is_some() {
+            return None;
         }
+        let position = self.image_position(self.mouse_position.as_vec2());
+        self.text_lines.as_ref()?.line_at(position)
+    }
+
+    // Starts an empty selection at the cursor, to be grown with the move keys
+    pub fn start_keyboard_selection(&mut self) {
+        let start = self.snapped_mouse_position();
+        self.selection.drag = None;
+        self.selection.set(Some(Selection { start, end: start }));
+    }
+
+    pub fn select_all(&mut self) {
+        self.selection.drag = None;
+        self.selection.set(Some(Selection {
+            start: Vec2::ZERO,
+            end: self.bounds(),
+        }));
+    }
+
+    /// Selects the middle third of what's on screen, a starting point for the move keys
+    pub fn select_center(&mut self) {
+        let (grid, bounds) = (self.args.grid, self.bounds());
+        let visible = bounds / self.zoom;
+        let start = snap_point(self.view_offset + visible / 3.0, grid, bounds).round();
+        let end = snap_point(self.view_offset + visible * 2.0 / 3.0, grid, bounds).round();
+        self.selection.drag = None;
+        self.selection.set(Some(Selection { start, end }));
+        self.following_keys = self.args.zoom_follow;
+    }
+
+    /// Whether confirming now would deliver something
+    pub fn has_selection(&self) -> bool {
+        self.crop_coords()
+            .is_some_and(|((min_x, min_y), (max_x, max_y))| min_x < max_x && min_y < max_y)
     }
 
     pub fn cancel_drag(&mut self) {
+        if self.annotating {
+            self.annotations.cancel();
+            return;
+        }
         self.selection.drag = None;
-        self.selection.selection = None;
+        self.selection.set(None);
     }
 
-    fn get_selection_data(&self) -> Option<Vec<u8>> {
-        let ((min_x, min_y), (max_x, max_y)) = self.selection.sel_coords()?;
-        let img = self
-            .image
-            .view(min_x, min_y, max_x.abs_diff(min_x), max_y.abs_diff(min_y));
-        let image_data = img.to_image().to_vec();
-        Some(image_data)
+    pub fn undo_selection(&mut self) {
+        let selection = &mut self.selection;
+        if let Some(previous) = selection.history.undo(selection.selection) {
+            selection.selection = previous;
+            selection.nudging = false;
+        }
     }
 
-    pub fn save_selection_to_clipboard(&self) {
-        let (width, height) = self.selection.sel_dimensions().unwrap();
+    pub fn redo_selection(&mut self) {
+        let selection = &mut self.selection;
+        if let Some(next) = selection.history.redo(selection.selection) {
+            selection.selection = next;
+            selection.nudging = false;
+        }
+    }
 
-        let width = width.floor() as usize;
-        let height = height.floor() as usize;
+    /// Opens the input for typing the selection as `x,y,w,h`
+    pub fn start_coordinate_input(&mut self) {
+        self.coordinate_input = Some(String::new());
+    }
 
-        let image_data = self.get_selection_data().unwrap();
+    /// Opens the input for `--prompt-tags`, unless they were typed already. Returns
+    /// whether the capture has to wait for them
+    pub fn prompt_tags(&mut self) -> bool {
+        if !self.args.prompt_tags || self.tags.is_some() || !self.has_selection() {
+            return false;
+        }
+        self.tag_input = Some(String::new());
+        self.notifier.notify(
+            Urgency::Info,
+            "Type tags for the capture separated by commas, then press Enter",
+        );
+        true
+    }
 
-        let mut clipboard = arboard::Clipboard::new().unwrap();
-        if width * height != image_data.len() / 4 {
-            eprintln!(
-                "Invalid selection size {:?} (w h p)",
-                (width, height, image_data.len() / 4)
+    /// Whether keys go to the coordinate or tag input rather than the bindings
+    pub fn is_typing(&self) -> bool {
+        self.coordinate_input.is_some() || self.tag_input.is_some()
+    }
+
+    /// Edits the coordinate input, Enter selects the typed region and Escape closes it.
+    /// Returns whether Enter finished typing tags, so the capture can be delivered
+    pub fn type_key(&mut self, key: &Key) -> bool {
+        if self.tag_input.is_some() {
+            return self.type_tag_key(key);
+        }
+        let Some(input) = self.coordinate_input.as_mut() else {
+            return false;
+        };
+        match key {
+            Key::Named(NamedKey::Escape) => self.coordinate_input = None,
+            Key::Named(NamedKey::Backspace) => {
+                input.pop();
+            }
+            Key::Named(NamedKey::Enter) => self.apply_coordinate_input(),
+            Key::Character(c) => {
+                let typed = c.chars().filter(|c| c.is_ascii_digit() || *c == ',');
+                for c in typed.take(MAX_INPUT - input.len()) {
+                    input.push(c);
+                }
+            }
+            _ => {}
+        }
+        false
+    }
+
+    // Like the coordinate input, but Escape goes back to the selection without the tags
+    fn type_tag_key(&mut self, key: &Key) -> bool {
+        let Some(input) = self.tag_input.as_mut() else {
+            return false;
+        };
+        match key {
+            Key::Named(NamedKey::Escape) => self.tag_input = None,
+            Key::Named(NamedKey::Backspace) => {
+                input.pop();
+            }
+            Key::Named(NamedKey::Enter) => {
+                self.tags = self.tag_input.take();
+                return true;
+            }
+            Key::Named(NamedKey::Space) if input.len() < MAX_INPUT => input.push(' '),
+            // Only what the overlay's font can show
+            Key::Character(c) => {
+                let typed = c
+                    .chars()
+                    .filter(|c| c.is_ascii_alphanumeric() || matches!(c, ',' | '-' | '_'));
+                for c in typed.take(MAX_INPUT - input.len()) {
+                    input.push(c);
+                }
+            }
+            _ => {}
+        }
+        false
+    }
+
+    fn apply_coordinate_input(&mut self) {
+        let Some(input) = &self.coordinate_input else {
+            return;
+        };
+        // Typed the same way as `--region`, around its center with `--region-origin center`
+        let region = input
+            .parse::<Region>()
+            .and_then(|region| region.anchored(self.args.region_origin));
+        let region = match region {
+            Ok(region) => region,
+            Err(err) => {
+                self.notifier.notify(Urgency::Warning, &err.to_string());
+                return;
+            }
+        };
+        let bounds = self.bounds();
+        let start = Vec2::new(region.x as f32, region.y as f32);
+        let size = Vec2::new(region.width as f32, region.height as f32);
+        if start.x >= bounds.x || start.y >= bounds.y {
+            self.notifier.notify(
+                Urgency::Warning,
+                &format!("{},{} is outside the capture", region.x, region.y),
             );
             return;
         }
-        let image_data = ImageData {
+        self.coordinate_input = None;
+        self.selection.drag = None;
+        self.selection.set(Some(Selection {
+            start,
+            end: (start + size).min(bounds),
+        }));
+    }
+
+    /// Brings back the selection saved by the previous capture, clamped to this image
+    pub fn restore_last_selection(&mut self) {
+        let Some((start, end)) = history::load_last_selection(self.args.project.as_deref()) else {
+            self.notifier
+                .notify(Urgency::Warning, "No previous selection to restore");
+            return;
+        };
+        let bounds = self.bounds();
+        let fitted = Selection {
+            start: start.clamp(Vec2::ZERO, bounds),
+            end: end.clamp(Vec2::ZERO, bounds),
+        };
+        // The monitor may have changed size since, or this could be another one
+        if (fitted.end - fitted.start).abs().min_element() < 1.0 {
+            self.notifier.notify(
+                Urgency::Warning,
+                "Previous selection is outside this capture",
+            );
+            return;
+        }
+        if fitted != (Selection { start, end }) {
+            self.notifier.notify(
+                Urgency::Warning,
+                "Previous selection didn't fit this capture and was trimmed",
+            );
+        }
+        self.selection.drag = None;
+        self.selection.set(Some(fitted));
+    }
+
+    // The selection grown by `--pad`, within the capture
+    fn padded_coords(&self) -> Option<((u32, u32), (u32, u32))> {
+        let ((min_x, min_y), (max_x, max_y)) = self.selection.sel_coords()?;
+        let selection = Region {
+            x: min_x,
+            y: min_y,
+            width: max_x.abs_diff(min_x),
+            height: max_y.abs_diff(min_y),
+        };
+        let (width, height) = self.image.dimensions();
+        let Region {
+            x,
+            y,
             width,
             height,
-            bytes: std::borrow::Cow::Owned(image_data),
+        } = selection.padded(self.args.pad, width, height);
+        Some(((x, y), (x + width, y + height)))
+    }
+
+    // Final crop rectangle, after the adjustments requested on the command line
+    fn crop_coords(&self) -> Option<((u32, u32), (u32, u32))> {
+        let coords @ ((min_x, min_y), (max_x, max_y)) = self.padded_coords()?;
+        if !self.args.even_dimensions {
+            return Some(coords);
+        }
+        let (width, height) = self.image.dimensions();
+        let Region {
+            x,
+            y,
+            width,
+            height,
+        } = Region {
+            x: min_x,
+            y: min_y,
+            width: max_x.abs_diff(min_x),
+            height: max_y.abs_diff(min_y),
+        }
+        .even(width, height);
+        Some(((x, y), (x + width, y + height)))
+    }
+
+    fn selection_region(&self) -> Option<Region> {
+        let ((min_x, min_y), (max_x, max_y)) = self.crop_coords()?;
+        Some(Region {
+            x: min_x,
+            y: min_y,
+            width: max_x.abs_diff(min_x),
+            height: max_y.abs_diff(min_y),
+        })
+    }
+
+    fn get_selection_image(&self) -> Option<RgbaImage> {
+        let ((min_x, min_y), (max_x, max_y)) = self.crop_coords()?;
+        let img = self
+            .image
+            .view(min_x, min_y, max_x.abs_diff(min_x), max_y.abs_diff(min_y));
+        let mut img = img.to_image();
+        if !self.annotations.is_empty() {
+            let width = max_x.abs_diff(min_x);
+            let height = max_y.abs_diff(min_y);
+            let layer = self.annotations.layer().view(min_x, min_y, width, height);
+            image::imageops::overlay(&mut img, &layer.to_image(), 0, 0);
+        }
+        util::round_corners(&mut img, self.corner_radius);
+        Some(img)
+    }
+
+    pub fn save_selection(&self) -> Delivered {
+        let (Some(selection_image), Some(region)) =
+            (self.get_selection_image(), self.selection_region())
+        else {
+            self.notifier
+                .notify(Urgency::Warning, "Nothing selected, nothing was saved");
+            return Delivered::NothingSelected;
         };
-        let _ = clipboard.set_image(image_data);
+        if !self.args.private {
+            if let Some(selection) = self.selection.selection {
+                if let Err(err) = history::save_last_selection(
+                    self.args.project.as_deref(),
+                    selection.start,
+                    selection.end,
+                ) {
+                    self.notifier.notify(
+                        Urgency::Warning,
+                        &format!("Could not remember the selection: {err}"),
+                    );
+                }
+            }
+        }
+        let monitor = self.monitor.as_ref();
+        Output::new(&self.args, self.notifier.as_ref(), self.started, monitor)
+            .with_window(self.captured_window.as_ref())
+            .with_tags(self.tags.as_deref())
+            .deliver(selection_image, region)
+    }
+
+    /// Starts recording the selected area of the screen to a GIF on another thread. The
+    /// overlay has to be hidden straight after
+    #[cfg(feature = "record")]
+    pub fn start_recording(&self) -> anyhow::Result<Recording> {
+        let position = self
+            .monitor
+            .as_ref()
+            .context("Only screen captures can be recorded")?
+            .position;
+        let region = self.selection_region().context("Nothing selected")?;
+        anyhow::ensure!(region.width > 0 && region.height > 0, "Nothing selected");
+        Ok(Recording::start(position, self.args.clone(), region))
     }
 
-    pub fn new(event_loop: &winit::event_loop::ActiveEventLoop) -> anyhow::Result<Self> {
-        let monitor = xcap::Monitor::all()?
+    /// Where the recording indicator goes: the first corner of the monitor it doesn't
+    /// cover any of the selection from, so it stays out of the recording
+    #[cfg(feature = "record")]
+    pub fn indicator_position(&self) -> Option<PhysicalPosition<i32>> {
+        let monitor = self.monitor.as_ref()?.position;
+        let (width, height) = COUNTDOWN_SIZE;
+        let right = self.size.width.saturating_sub(width);
+        let bottom = self.size.height.saturating_sub(height);
+        let selection = self.selection_region();
+        let corner = [(0, 0), (right, 0), (0, bottom), (right, bottom)]
             .into_iter()
-            .find(|m| m.is_primary())
-            .with_context(|| "Could not get primary monitor")?;
-        let img = monitor.capture_image()?;
-        let size = PhysicalSize::new(monitor.width(), monitor.height());
+            .find(|&(x, y)| {
+                let indicator = Region {
+                    x,
+                    y,
+                    width,
+                    height,
+                };
+                selection.is_none_or(|selection| !selection.overlaps(indicator))
+            })
+            .unwrap_or((0, 0));
+        Some(PhysicalPosition::new(
+            monitor.x + corner.0 as i32,
+            monitor.y + corner.1 as i32,
+        ))
+    }
+
+    pub fn new(
+        event_loop: &winit::event_loop::ActiveEventLoop,
+        args: Args,
+    ) -> anyhow::Result<Self> {
+        match &args.command {
+            // Edited images get a regular window of their own size
+            Some(Command::Edit { input }) => {
+                let mut timings = Timings::new();
+                let img = util::load_image(input)?;
+                timings.lap("capture");
+                Self::with_image(event_loop, args, img, None, None, timings, None)
+            }
+            _ => {
+                let mut timings = Timings::new();
+                // The device doesn't need the capture, so it's set up while the screen is read
+                let gpu = std::thread::spawn(|| {
+                    let started = std::time::Instant::now();
+                    (pollster::block_on(Gpu::new()), started.elapsed())
+                });
+                let capture = capture::backend(&args).capture(&args)?;
+                timings.lap("capture");
+                // Without a device here, the window gets a chance to find one itself
+                let gpu = match gpu.join() {
+                    Ok((gpu, took)) => {
+                        timings.record("GPU device (while capturing)", took);
+                        timings.lap("waiting for GPU device");
+                        gpu.ok()
+                    }
+                    Err(_) => None,
+                };
+                Self::from_capture(event_loop, args, capture, timings, gpu)
+            }
+        }
+    }
+
+    /// A small window showing the seconds left of `--delay` with the picker's label. It
+    /// has nothing to select, and has to be gone before the capture
+    pub fn countdown(
+        event_loop: &winit::event_loop::ActiveEventLoop,
+        args: &Args,
+    ) -> anyhow::Result<Self> {
+        Self::badge(event_loop, args, Rgba([48, 48, 48, 255]), None)
+    }
+
+    /// The countdown's window in red, showing the seconds a recording has left at
+    /// `position`. Keys and clicks on it stop the recording
+    #[cfg(feature = "record")]
+    pub fn recording_indicator(
+        event_loop: &winit::event_loop::ActiveEventLoop,
+        args: &Args,
+        position: Option<PhysicalPosition<i32>>,
+    ) -> anyhow::Result<Self> {
+        Self::badge(event_loop, args, Rgba([160, 32, 32, 255]), position)
+    }
+
+    fn badge(
+        event_loop: &winit::event_loop::ActiveEventLoop,
+        args: &Args,
+        color: Rgba<u8>,
+        position: Option<PhysicalPosition<i32>>,
+    ) -> anyhow::Result<Self> {
+        let args = Args {
+            ocr_select: false,
+            dim_style: DimStyle::None,
+            timings: false,
+            ..args.clone()
+        };
+        let (width, height) = COUNTDOWN_SIZE;
+        let img = RgbaImage::from_pixel(width, height, color);
+        let context =
+            Self::with_image(event_loop, args, img, None, position, Timings::new(), None)?;
+        context.graphics.window.set_decorations(false);
+        context
+            .graphics
+            .window
+            .set_window_level(WindowLevel::AlwaysOnTop);
+        Ok(context)
+    }
+
+    /// Captures `monitor` and covers it with the overlay
+    pub fn on_monitor(
+        event_loop: &winit::event_loop::ActiveEventLoop,
+        args: Args,
+        monitor: &xcap::Monitor,
+    ) -> anyhow::Result<Self> {
+        let mut timings = Timings::new();
+        let mut capture = capture::capture_monitor(monitor, &args)?;
+        capture.override_scale(args.dpi_scale);
+        timings.lap("capture");
+        Self::from_capture(event_loop, args, capture, timings, None)
+    }
+
+    fn from_capture(
+        event_loop: &winit::event_loop::ActiveEventLoop,
+        args: Args,
+        capture: Capture,
+        timings: Timings,
+        gpu: Option<Gpu>,
+    ) -> anyhow::Result<Self> {
+        // Cover the captured monitor rather than wherever the window manager puts new windows.
+        // xcap and winit can disagree on positions with mixed scale factors, names rarely differ
+        let fullscreen_on = capture.monitor.as_ref().and_then(|monitor| {
+            let monitors: Vec<_> = event_loop.available_monitors().collect();
+            let by_position = monitors.iter().find(|m| m.position() == monitor.position);
+            by_position
+                .or_else(|| {
+                    monitors
+                        .iter()
+                        .find(|m| m.name().is_some_and(|name| name == monitor.name))
+                })
+                .cloned()
+        });
+        // Window managers that ignore the fullscreen monitor open the window where it was placed
+        let position = fullscreen_on
+            .as_ref()
+            .map(|m| m.position())
+            .or(capture.monitor.as_ref().map(|m| m.position));
+        let mut context = Self::with_image(
+            event_loop,
+            args,
+            capture.image,
+            Some(Fullscreen::Borderless(fullscreen_on)),
+            position,
+            timings,
+            gpu,
+        )?;
+        if context.guides.is_some() {
+            context.guides = Some(Guides::new(context.bounds(), capture.monitor.as_ref()));
+        }
+        context.monitor = capture.monitor;
+        context.captured_window = capture.window;
+        Ok(context)
+    }
+
+    fn with_image(
+        event_loop: &winit::event_loop::ActiveEventLoop,
+        args: Args,
+        img: RgbaImage,
+        fullscreen: Option<Fullscreen>,
+        position: Option<PhysicalPosition<i32>>,
+        mut timings: Timings,
+        gpu: Option<Gpu>,
+    ) -> anyhow::Result<Self> {
+        let size = PhysicalSize::new(img.width(), img.height());
+        // Edited images and badges aren't on the screen to begin with
+        let live = args.live && fullscreen.is_some();
 
         let icon_bytes = include_bytes!("../icon.png");
         let rgba = image::load_from_memory(icon_bytes)?.to_rgba8();
         let (width, height) = rgba.dimensions();
         let rgba = rgba.into_raw();
 
+        let mut attributes = WindowAttributes::default();
+        if let Some(position) = position {
+            attributes = attributes.with_position(position);
+        }
         let window = event_loop.create_window(
-            WindowAttributes::default()
+            attributes
                 .with_inner_size(size)
                 .with_title("Cleave")
                 .with_resizable(false)
-                .with_decorations(false)
-                .with_fullscreen(Some(winit::window::Fullscreen::Borderless(None)))
+                .with_decorations(fullscreen.is_none())
+                .with_fullscreen(fullscreen)
                 .with_visible(false)
+                .with_transparent(live)
                 .with_window_icon(Some(Icon::from_rgba(rgba, width, height)?)),
         )?;
+        timings.lap("window");
 
-        let graphics = Graphics::new(window, size.width, size.height);
-        let graphics = pollster::block_on(graphics)?;
+        let graphics = Graphics::with_gpu(window, size.width, size.height, gpu);
+        let mut graphics = pollster::block_on(graphics)?;
+        let transparent = live && graphics.set_transparent();
+        timings.lap("surface");
 
-        let bundle = GraphicsBundle::new(
-            img.clone().into(),
+        let mut bundle = GraphicsBundle::new(
+            &img,
             &graphics.device,
             &graphics.queue,
             wgpu::PrimitiveTopology::TriangleStrip,
             graphics.config.format,
         );
+        timings.lap("upload");
+        if matches!(args.dim_style, DimStyle::Blur) && !transparent {
+            bundle =
+                bundle.with_blurred_background(&graphics.device, &graphics.queue, args.blur_radius);
+            timings.lap("blur");
+        }
+        let (capture_time, upload_time) = (
+            timings.get("capture"),
+            timings.get("upload") + timings.get("blur"),
+        );
 
+        let window_size = graphics.window.inner_size();
         graphics.window.set_visible(true);
         let _ = graphics
             .window
             .set_cursor_grab(winit::window::CursorGrabMode::Confined);
 
+        let text_lines = args
+            .ocr_select
+            .then(|| TextLines::recognize(img.clone(), Languages::from(&args)));
+
         // let surface_texture = SurfaceTexture::new(size.width, size.height, window.clone());
         // let pixels = Pixels::new(size.width, size.height, surface_texture)?;
 
-        Ok(Self {
+        let context = Self {
             size,
             image: img,
+            live: transparent,
             bundle,
             total_time: 0.0,
             last_frame: std::time::Instant::now(),
             selection: UserSelection::new(),
+            annotating: false,
+            annotations: Annotations::new(size.width, size.height),
             // window,
             graphics,
+            window_size,
             mouse_position: DVec2::new(0.0, 0.0),
+            pan_anchor: None,
+            view_offset: Vec2::ZERO,
+            zoom: 1.0,
+            corner_radius: args.corner_radius,
+            aspect_lock: false,
             mode: MoveMode::Resize,
-        })
+            following_keys: false,
+            announced: None,
+            last_announcement: std::time::Instant::now(),
+            show_hud: false,
+            capture_time,
+            upload_time,
+            frame_times: VecDeque::with_capacity(HUD_FRAMES),
+            coordinate_input: None,
+            tag_input: None,
+            tags: None,
+            cursor: None,
+            guides: args
+                .snap
+                .map(|_| Guides::new(Vec2::new(size.width as f32, size.height as f32), None)),
+            text_lines,
+            notifier: args.notify.notifier(args.verbosity()),
+            started: std::time::Instant::now(),
+            monitor: None,
+            captured_window: None,
+            timings: args.timings.then_some(timings),
+            args,
+        };
+        if live && !transparent {
+            context.notifier.notify(
+                Urgency::Warning,
+                "This desktop can't show through the overlay, so --live shows a frozen capture",
+            );
+        }
+        context.notifier.notify(
+            Urgency::Debug,
+            &format!(
+                "Read the {}x{} image in {:.1}ms and uploaded it to the GPU in {:.1}ms",
+                size.width,
+                size.height,
+                capture_time.as_secs_f32() * 1000.0,
+                upload_time.as_secs_f32() * 1000.0
+            ),
+        );
+        Ok(context)
     }
 
-    pub fn handle_move(&mut self, dir: Direction) -> Option<()> {
-        let (dx, dy) = match dir {
-            Direction::Up => (0.0, -1.0),
-            Direction::Down => (0.0, 1.0),
-            Direction::Left => (-1.0, 0.0),
-            Direction::Right => (1.0, 0.0),
+    /// Moves or resizes the selection by a step in `dir`, growing with how long the key
+    /// has been `held` for `--acceleration`
+    pub fn handle_move(&mut self, dir: Direction, big: bool, held: Duration) -> Option<()> {
+        let step = if big {
+            self.args.big_step
+        } else {
+            self.args.step
+        };
+        let speedup = 1.0 + self.args.acceleration * held.as_secs_f32();
+        let pixels = (step as f32 * speedup).round() as u32;
+        let step = match self.args.grid {
+            Some(grid) if grid > 1 => pixels.next_multiple_of(grid),
+            _ => pixels,
+        } as f32;
+        let delta = match dir {
+            Direction::Up => Vec2::new(0.0, -step),
+            Direction::Down => Vec2::new(0.0, step),
+            Direction::Left => Vec2::new(-step, 0.0),
+            Direction::Right => Vec2::new(step, 0.0),
         };
 
+        let (grid, bounds) = (self.args.grid, self.bounds());
+        let nudge = |point: Vec2| snap_point(point + delta, grid, bounds);
+        let current = self.selection.selection?;
+        if !self.selection.nudging {
+            self.selection.history.record(Some(current));
+            self.selection.nudging = true;
+        }
         let selection = self.selection.selection.as_mut()?;
 
         match self.mode {
             MoveMode::Move => {
-                selection.start.x = (selection.start.x + dx).clamp(0.0, self.size.width as f32);
-                selection.start.y = (selection.start.y + dy).clamp(0.0, self.size.height as f32);
-                selection.end.x = (selection.end.x + dx).clamp(0.0, self.size.width as f32);
-                selection.end.y = (selection.end.y + dy).clamp(0.0, self.size.height as f32);
+                selection.start = nudge(selection.start);
+                selection.end = nudge(selection.end);
             }
             MoveMode::Resize => {
-                selection.end.x = (selection.end.x + dx).clamp(0.0, self.size.width as f32);
-                selection.end.y = (selection.end.y + dy).clamp(0.0, self.size.height as f32);
+                selection.end = nudge(selection.end);
             }
             MoveMode::InverseResize => {
-                selection.start.x = (selection.start.x + dx).clamp(0.0, self.size.width as f32);
-                selection.start.y = (selection.start.y + dy).clamp(0.0, self.size.height as f32);
+                selection.start = nudge(selection.start);
             }
         }
+        self.following_keys = self.args.zoom_follow;
 
         Some(())
     }
@@ -256,42 +944,224 @@ impl AppContext {
         let time = self.last_frame.elapsed().as_secs_f32();
         self.total_time += time;
         self.last_frame = std::time::Instant::now();
+        if self.frame_times.len() == HUD_FRAMES {
+            self.frame_times.pop_front();
+        }
+        self.frame_times.push_back(time * 1000.0);
 
+        if self.args.zoom_follow {
+            self.announce_selection();
+        }
+        if let Some(err) = self.text_lines.as_mut().and_then(TextLines::poll) {
+            self.notifier.notify_with_hint(
+                Urgency::Warning,
+                &format!("Could not recognize text: {err:#}"),
+                "--ocr-select needs tesseract and its data for the --ocr-language languages",
+            );
+        }
+        self.update_cursor();
         self.update_uniforms();
         self.bundle.update_buffer(&self.graphics.queue);
+        if let Some(overlay) = self.annotations.take_preview() {
+            self.bundle.set_overlay(&self.graphics.queue, &overlay);
+        }
 
         let mut pass = match self.graphics.render() {
             Ok(pass) => pass,
             Err(err) => {
-                eprintln!("Error rendering frame: {:?}", err);
+                self.notifier
+                    .notify(Urgency::Warning, &format!("Could not render frame: {err}"));
                 return;
             }
         };
         self.bundle.draw(&mut pass);
         pass.finish();
+        if let Some(mut timings) = self.timings.take() {
+            timings.lap("first frame");
+            self.notifier.notify(Urgency::Info, &timings.summary());
+        }
         self.graphics.request_redraw();
     }
 
+    // What a click or drag would do right now, shown through the cursor
+    fn cursor_icon(&self) -> CursorIcon {
+        if self.bundle.uniforms.monitor_label != 0 {
+            return CursorIcon::Pointer;
+        }
+        if self.pan_anchor.is_some() {
+            return CursorIcon::Grabbing;
+        }
+        if self.annotating {
+            return CursorIcon::Cell;
+        }
+        if self.hovered_line().is_some() {
+            return CursorIcon::Text;
+        }
+        match self.args.cursor {
+            CursorStyle::Crosshair => CursorIcon::Crosshair,
+            CursorStyle::Cell => CursorIcon::Cell,
+            CursorStyle::Arrow => CursorIcon::Default,
+        }
+    }
+
+    fn update_cursor(&mut self) {
+        let icon = self.cursor_icon();
+        if self.cursor != Some(icon) {
+            self.graphics.window.set_cursor(icon);
+            self.cursor = Some(icon);
+        }
+    }
+
+    /// Shows or hides the frame time graph and timings, measuring the GPU while it's up
+    pub fn toggle_hud(&mut self) {
+        self.show_hud = !self.show_hud;
+        self.graphics.measure_gpu(self.show_hud);
+    }
+
+    fn update_hud(&mut self) {
+        let tenths =
+            |time: Option<Duration>| time.map_or(u32::MAX, |t| (t.as_micros() / 100) as u32);
+        let frame = self
+            .frame_times
+            .back()
+            .map(|ms| Duration::from_secs_f32(ms / 1000.0));
+        self.bundle.uniforms.hud_stats = [
+            tenths(Some(self.capture_time)),
+            tenths(Some(self.upload_time)),
+            tenths(frame),
+            tenths(self.graphics.gpu_time()),
+        ];
+        let mut frame_times = [0.0; HUD_FRAMES];
+        let skipped = HUD_FRAMES - self.frame_times.len();
+        for (slot, ms) in frame_times[skipped..].iter_mut().zip(&self.frame_times) {
+            *slot = *ms;
+        }
+        for (chunk, times) in frame_times
+            .chunks_exact(4)
+            .zip(&mut self.bundle.uniforms.frame_times)
+        {
+            *times = Vec4::from_slice(chunk);
+        }
+    }
+
+    // Shows whichever input is open, tags in the capitals the font has
+    fn update_input(&mut self) {
+        let uniforms = &mut self.bundle.uniforms;
+        let input = match (&self.coordinate_input, &self.tag_input) {
+            (Some(input), _) => input.clone(),
+            (_, Some(tags)) => tags.to_ascii_uppercase(),
+            (None, None) => {
+                uniforms.input_len = 0;
+                return;
+            }
+        };
+        uniforms.input_len = input.len() as u32 + 1;
+        uniforms.input_glyphs = pack_glyphs(&input);
+    }
+
+    // Says the selection's size when it has changed, at most every `ANNOUNCE_INTERVAL`
+    fn announce_selection(&mut self) {
+        // A popup every couple of seconds would bury the desktop's other notifications
+        if matches!(self.args.notify, NotifyBackend::Desktop)
+            || self.last_announcement.elapsed() < ANNOUNCE_INTERVAL
+        {
+            return;
+        }
+        let region = self.selection_region();
+        if region == self.announced {
+            return;
+        }
+        self.announced = region;
+        self.last_announcement = std::time::Instant::now();
+        if let Some(region) = region {
+            self.notifier.notify(
+                Urgency::Info,
+                &format!(
+                    "Selection {}x{} at {},{}",
+                    region.width, region.height, region.x, region.y
+                ),
+            );
+        }
+    }
+
+    // The corner the arrow keys last moved, while the loupe is following them
+    fn keyboard_focus(&self) -> Option<Vec2> {
+        let selection = self.selection.selection.filter(|_| self.following_keys)?;
+        Some(match self.mode {
+            MoveMode::Move | MoveMode::Resize => selection.end,
+            MoveMode::InverseResize => selection.start,
+        })
+    }
+
     fn update_uniforms(&mut self) {
         self.bundle.uniforms.time = self.total_time;
         self.bundle.uniforms.screen_size.x = self.size.width as f32;
         self.bundle.uniforms.screen_size.y = self.size.height as f32;
+        self.bundle.uniforms.grid_size = self.args.grid.unwrap_or(0) as f32;
+        self.bundle.uniforms.view_offset = self.view_offset;
+        self.bundle.uniforms.zoom = self.zoom;
+        self.bundle.uniforms.dim_style = self.args.dim_style as u32;
+        self.bundle.uniforms.corner_radius = self.corner_radius;
+        self.bundle.uniforms.dim_opacity = self.args.dim_opacity;
+        self.bundle.uniforms.border_color =
+            Vec4::from_array(self.args.border_color.0.map(f32::from)) / 255.0;
+        self.bundle.uniforms.border_thickness = self.args.border_thickness;
+        self.bundle.uniforms.border_style = self.args.border_style as u32;
+        self.bundle.uniforms.show_thirds = self.args.thirds as u32;
+        self.bundle.uniforms.ui_scale = self.ui_scale();
+        self.bundle.uniforms.live = self.live as u32;
+        self.bundle.uniforms.show_hud = self.show_hud as u32;
+        self.update_input();
+        if self.show_hud {
+            self.update_hud();
+        }
 
         let drag = self.selection.drag;
         let selection = self.selection.selection;
+        let focus = self.keyboard_focus();
+        // The loupe magnifies the capture, which isn't what's on the screen with `--live`
+        self.bundle.uniforms.show_loupe =
+            ((drag.is_some() || focus.is_some()) && !self.live) as u32;
+        self.bundle.uniforms.mouse = focus
+            .unwrap_or_else(|| self.image_position(self.mouse_position.as_vec2()))
+            .clamp(Vec2::ZERO, self.bounds() - 1.0);
         self.bundle.uniforms.is_dragging = match (drag, selection) {
-            (Some(d), Some(s)) if d.start != Vec2::ZERO || s.start != Vec2::ZERO => 3,
-            (Some(d), None) if d.start != Vec2::ZERO => 1,
-            (None, Some(s)) if s.start != Vec2::ZERO => 2,
-            _ => 0,
+            (Some(_), Some(_)) => 3,
+            (Some(_), None) => 1,
+            (None, Some(_)) => 2,
+            (None, None) => 0,
         };
 
         if let Some(drag) = drag {
             self.bundle.uniforms.drag_start = drag.start;
             self.bundle.uniforms.drag_end = drag.end.unwrap_or_default();
+            let region = drag.region();
+            let (x, y) = region.origin_point(self.args.region_origin);
+            let label = format!("{}x{} @ ({x},{y})", region.width, region.height);
+            self.bundle.uniforms.drag_label_len = label.len() as u32;
+            self.bundle.uniforms.drag_label = pack_glyphs(&label);
         } else {
             self.bundle.uniforms.drag_start = Vec2::ZERO;
             self.bundle.uniforms.drag_end = Vec2::ZERO;
+            self.bundle.uniforms.drag_label_len = 0;
+        };
+
+        self.bundle.uniforms.selection_adjusted =
+            (self.crop_coords() != self.padded_coords()) as u32;
+        self.bundle.uniforms.padded_bounds = match self.padded_coords() {
+            Some(((min_x, min_y), (max_x, max_y))) if self.args.pad > 0 => {
+                Vec4::new(min_x as f32, min_y as f32, max_x as f32, max_y as f32)
+            }
+            _ => Vec4::ZERO,
+        };
+        self.bundle.uniforms.text_bounds = match self.hovered_line() {
+            Some(line) => Vec4::new(
+                line.x as f32,
+                line.y as f32,
+                (line.x + line.width) as f32,
+                (line.y + line.height) as f32,
+            ),
+            None => Vec4::ZERO,
         };
 
         if let Some(selection) = selection {
@@ -307,22 +1177,396 @@ impl AppContext {
         self.graphics.id()
     }
 
-    pub fn destroy(&self) {
-        self.graphics.window.set_minimized(true);
-    }
-
     pub fn hide_window(&self) {
         self.graphics.set_visible(false);
     }
 
+    /// Whether the screen shows through with `--live`, so it has to be captured again
+    /// once the overlay is gone
+    pub fn is_live(&self) -> bool {
+        self.live
+    }
+
+    /// Captures the screen under the overlay again for `--live`, after it's hidden
+    pub fn recapture(&mut self) -> anyhow::Result<()> {
+        // The monitor picked with `--monitor pick` rather than the one asked for
+        let args = match &self.monitor {
+            Some(monitor) => Args {
+                monitor: MonitorTarget::Named(monitor.id.to_string()),
+                ..self.args.clone()
+            },
+            None => self.args.clone(),
+        };
+        let capture = capture::backend(&args).capture(&args)?;
+        anyhow::ensure!(
+            capture.image.dimensions() == self.image.dimensions(),
+            "The screen changed size from {}x{} to {}x{} while selecting",
+            self.image.width(),
+            self.image.height(),
+            capture.image.width(),
+            capture.image.height()
+        );
+        self.image = capture.image;
+        Ok(())
+    }
+
+    /// Takes the overlay down: hides the window, then frees the bundle, the surface
+    /// and finally the window. Dropping does the same, this just makes it explicit
+    pub fn shutdown(self) {
+        drop(self);
+    }
+
+    pub fn adjust_corner_radius(&mut self, delta: f32) {
+        self.corner_radius = (self.corner_radius + delta).max(0.0);
+    }
+
     pub fn set_mode(&mut self, mode: MoveMode) {
         self.mode = mode
     }
 
     pub fn update_mouse_position(&mut self, x: f64, y: f64) {
-        self.mouse_position = DVec2::new(x, y);
+        self.mouse_position = window_to_capture(DVec2::new(x, y), self.window_size, self.size);
+        self.following_keys = false;
+        if let Some(anchor) = self.pan_anchor.replace(self.mouse_position) {
+            self.view_offset -= (self.mouse_position - anchor).as_vec2() / self.zoom;
+            self.clamp_view();
+        }
+        if self.annotating {
+            let position = self.image_position(self.mouse_position.as_vec2());
+            self.annotations
+                .extend(position.clamp(Vec2::ZERO, self.bounds()));
+            return;
+        }
+        let Some(start) = self.selection.drag.map(|d| d.start) else {
+            return;
+        };
+        let end = self.constrain_aspect(start, self.snapped_mouse_position());
         if let Some(drag) = self.selection.drag.as_mut() {
-            drag.end = Some(self.mouse_position.as_vec2());
+            drag.end = Some(end);
+        }
+    }
+
+    // Annotations only make sense on top of a selection, so one has to exist first
+    pub fn toggle_annotation(&mut self) {
+        if !self.annotating && self.selection.selection.is_none() {
+            self.notifier
+                .notify(Urgency::Warning, "Select a region before annotating");
+            return;
+        }
+        self.annotating = !self.annotating;
+        self.selection.drag = None;
+        self.annotations.cancel();
+    }
+
+    pub fn set_annotation_tool(&mut self, tool: Tool) {
+        self.annotations.tool = tool;
+    }
+
+    pub fn undo_annotation(&mut self) {
+        self.annotations.undo();
+    }
+
+    /// Shows a large number over the capture while the user picks a monitor.
+    /// The cursor is only confined to the window once the picking is done,
+    /// so it can still reach the other monitors
+    pub fn set_picker_label(&mut self, label: Option<u32>) {
+        self.bundle.uniforms.monitor_label = label.unwrap_or(0);
+        let grab = match label {
+            Some(_) => winit::window::CursorGrabMode::None,
+            None => winit::window::CursorGrabMode::Confined,
+        };
+        let _ = self.graphics.window.set_cursor_grab(grab);
+        if label.is_none() {
+            self.graphics.window.focus_window();
         }
     }
+
+    pub fn set_aspect_lock(&mut self, locked: bool) {
+        self.aspect_lock = locked;
+    }
+
+    // `--aspect` always applies, holding the lock key without it gives a square
+    fn aspect_ratio(&self) -> Option<f32> {
+        let held = self.aspect_lock.then_some(1.0);
+        self.args.aspect.map(|a| a.ratio()).or(held)
+    }
+
+    // Moves `end` so the rectangle from `start` has the locked aspect ratio and stays on screen
+    fn constrain_aspect(&self, start: Vec2, end: Vec2) -> Vec2 {
+        let Some(ratio) = self.aspect_ratio() else {
+            return end;
+        };
+        let delta = end - start;
+        let (mut width, mut height) = (delta.x.abs(), delta.y.abs());
+        if width == 0.0 && height == 0.0 {
+            return end;
+        }
+        if width > height * ratio {
+            height = width / ratio;
+        } else {
+            width = height * ratio;
+        }
+
+        let bounds = self.bounds();
+        let room_x = if delta.x < 0.0 {
+            start.x
+        } else {
+            bounds.x - start.x
+        };
+        let room_y = if delta.y < 0.0 {
+            start.y
+        } else {
+            bounds.y - start.y
+        };
+        let scale = (room_x / width).min(room_y / height).min(1.0);
+        // Whole pixels, so the crop matches the rectangle drawn by the shader
+        let width = (width * scale).round();
+        let height = (width / ratio).round();
+
+        let end = start + Vec2::new(width.copysign(delta.x), height.copysign(delta.y));
+        end.clamp(Vec2::ZERO, bounds)
+    }
+
+    pub fn resize(&mut self, size: PhysicalSize<u32>) {
+        if size.width == 0 || size.height == 0 {
+            return;
+        }
+        self.window_size = size;
+        self.graphics.resize(size.width, size.height);
+    }
+
+    // Physical pixels per logical pixel, `--dpi-scale` or whatever the platform says
+    fn ui_scale(&self) -> f32 {
+        self.args
+            .dpi_scale
+            .unwrap_or_else(|| self.graphics.window.scale_factor() as f32)
+    }
+
+    fn bounds(&self) -> Vec2 {
+        Vec2::new(self.size.width as f32, self.size.height as f32)
+    }
+
+    // Position in the captured image under the given point on screen
+    fn image_position(&self, screen: Vec2) -> Vec2 {
+        self.view_offset + screen / self.zoom
+    }
+
+    fn snapped_mouse_position(&self) -> Vec2 {
+        let position = self.image_position(self.mouse_position.as_vec2());
+        let position = snap_point(position, self.args.grid, self.bounds());
+        match (&self.guides, self.args.snap) {
+            // The tolerance is in screen pixels, whatever the zoom
+            (Some(guides), Some(tolerance)) => guides
+                .snap(position, tolerance as f32 / self.zoom)
+                .clamp(Vec2::ZERO, self.bounds()),
+            _ => position,
+        }
+    }
+
+    // Zooms the preview in (positive) or out (negative), keeping the point under the cursor in place
+    pub fn zoom(&mut self, steps: f32) {
+        // Only the capture can be zoomed into, not the screen behind `--live`
+        if self.live {
+            return;
+        }
+        let cursor = self.mouse_position.as_vec2();
+        let anchor = self.image_position(cursor);
+        self.zoom = (self.zoom * ZOOM_STEP.powf(steps)).clamp(1.0, MAX_ZOOM);
+        self.view_offset = anchor - cursor / self.zoom;
+        self.clamp_view();
+    }
+
+    pub fn start_pan(&mut self) {
+        if self.zoom > 1.0 {
+            self.pan_anchor = Some(self.mouse_position);
+        }
+    }
+
+    pub fn end_pan(&mut self) {
+        self.pan_anchor = None;
+    }
+
+    fn clamp_view(&mut self) {
+        let max_offset = self.bounds() - self.bounds() / self.zoom;
+        self.view_offset = self.view_offset.clamp(Vec2::ZERO, max_offset);
+    }
+}
+
+// Runs on every exit path, unwinding from a panic included
+impl Drop for AppContext {
+    fn drop(&mut self) {
+        // Hidden first so the fullscreen window doesn't linger while the rest is freed
+        self.graphics.set_visible(false);
+        self.graphics.wait_idle();
+    }
+}
+
+// Packs `text` for the shader, 4 glyph numbers to a u32 from the low byte. Characters
+// without a glyph come out blank and anything past 32 is dropped
+fn pack_glyphs(text: &str) -> [u32; 8] {
+    let mut packed = [0; 8];
+    for (i, c) in text.chars().take(32).enumerate() {
+        let glyph = match c {
+            '0'..='9' => c as u32 - '0' as u32,
+            ',' => 10,
+            '.' => 11,
+            '-' => 16,
+            ':' => 17,
+            '_' => 18,
+            'x' => 19,
+            '@' => 20,
+            '(' => 21,
+            ')' => 22,
+            'A'..='Z' => 23 + (c as u32 - 'A' as u32),
+            _ => 99,
+        };
+        packed[i / 4] |= glyph << (i % 4 * 8);
+    }
+    packed
+}
+
+// Rounds `point` to the nearest grid intersection (if any) and keeps it on screen
+fn snap_point(point: Vec2, grid: Option<u32>, bounds: Vec2) -> Vec2 {
+    let point = match grid {
+        Some(size) if size > 1 => (point / size as f32).round() * size as f32,
+        _ => point,
+    };
+    point.clamp(Vec2::ZERO, bounds)
+}
+
+// Where a cursor position in a `window` sized window lands on a `capture` sized capture.
+// They're the same size unless the compositor scaled the window, e.g. when the portal
+// hands back a logical sized screenshot on a HiDPI display. Fractions are kept, the
+// selection rounds them to whole pixels once it's cropped
+fn window_to_capture(
+    cursor: DVec2,
+    window: PhysicalSize<u32>,
+    capture: PhysicalSize<u32>,
+) -> DVec2 {
+    let scale = DVec2::new(
+        capture.width as f64 / window.width.max(1) as f64,
+        capture.height as f64 / window.height.max(1) as f64,
+    );
+    cursor * scale
+}
+
+// The whole pixels between two corners in capture pixels. Edges between pixels move in to
+// the next whole one, so a click between two pixels covers none and gives no region
+fn whole_pixels(start: Vec2, end: Vec2) -> Option<Region> {
+    let (min, max) = (start.min(end).ceil(), start.max(end).floor());
+    if max.x <= min.x || max.y <= min.y {
+        return None;
+    }
+    Some(Region {
+        x: min.x as u32,
+        y: min.y as u32,
+        width: (max.x - min.x) as u32,
+        height: (max.y - min.y) as u32,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CAPTURE: PhysicalSize<u32> = PhysicalSize::new(1920, 1080);
+
+    // The window a compositor scaling by `scale` gives a capture of CAPTURE's size
+    fn scaled_window(scale: f64) -> PhysicalSize<u32> {
+        PhysicalSize::new(
+            (CAPTURE.width as f64 / scale) as u32,
+            (CAPTURE.height as f64 / scale) as u32,
+        )
+    }
+
+    #[test]
+    fn unscaled_window_maps_one_to_one() {
+        let cursor = DVec2::new(640.5, 360.0);
+        assert_eq!(window_to_capture(cursor, CAPTURE, CAPTURE), cursor);
+    }
+
+    #[test]
+    fn scaled_windows_map_to_capture_pixels() {
+        for (scale, cursor, expected) in [
+            (1.25, DVec2::new(100.0, 80.0), DVec2::new(125.0, 100.0)),
+            (1.5, DVec2::new(100.0, 80.0), DVec2::new(150.0, 120.0)),
+            (2.0, DVec2::new(100.0, 80.0), DVec2::new(200.0, 160.0)),
+        ] {
+            let window = scaled_window(scale);
+            assert_eq!(
+                window_to_capture(cursor, window, CAPTURE),
+                expected,
+                "{scale}"
+            );
+            // The window's far corner is the capture's
+            let corner = DVec2::new(window.width as f64, window.height as f64);
+            assert_eq!(
+                window_to_capture(corner, window, CAPTURE),
+                DVec2::new(1920.0, 1080.0),
+                "{scale}"
+            );
+        }
+    }
+
+    #[test]
+    fn wrong_platform_scale_factors_crop_the_same_pixels() {
+        // The compositor really scales by 1.5, so the window is 1280x720 logical pixels. A
+        // platform reporting another factor sizes the window and cursor by that instead,
+        // which is why `--dpi-scale` only changes the UI scale and not this mapping
+        let logical = DVec2::new(1280.0, 720.0);
+        let (start, end) = (DVec2::new(100.0, 80.0), DVec2::new(300.0, 200.0));
+        for reported in [1.0, 1.25, 1.5, 2.0] {
+            let window =
+                PhysicalSize::new((logical.x * reported) as u32, (logical.y * reported) as u32);
+            let start = window_to_capture(start * reported, window, CAPTURE);
+            let end = window_to_capture(end * reported, window, CAPTURE);
+            assert_eq!(
+                whole_pixels(start.as_vec2(), end.as_vec2()),
+                Some(Region {
+                    x: 150,
+                    y: 120,
+                    width: 300,
+                    height: 180,
+                }),
+                "{reported}"
+            );
+        }
+    }
+
+    #[test]
+    fn fractional_positions_are_not_rounded() {
+        // 8.24 logical pixels at 125% is 10.3 capture pixels, between two pixels
+        let position = window_to_capture(DVec2::new(8.24, 3.0), scaled_window(1.25), CAPTURE);
+        assert!((position.x - 10.3).abs() < 1e-9, "{position}");
+        assert_eq!(position.y, 3.75);
+        // 1.5 splits odd positions in half
+        let position = window_to_capture(DVec2::new(7.0, 1.0), scaled_window(1.5), CAPTURE);
+        assert_eq!(position, DVec2::new(10.5, 1.5));
+    }
+
+    #[test]
+    fn whole_pixels_rounds_edges_inwards() {
+        let region = whole_pixels(Vec2::new(20.7, 15.0), Vec2::new(10.3, 5.0));
+        assert_eq!(
+            region,
+            Some(Region {
+                x: 11,
+                y: 5,
+                width: 9,
+                height: 10
+            })
+        );
+    }
+
+    #[test]
+    fn clicks_between_pixels_select_nothing() {
+        // A click without a drag at 125%, which lands at 10.3 and used to underflow
+        let click = window_to_capture(DVec2::new(8.24, 4.0), scaled_window(1.25), CAPTURE);
+        let click = click.as_vec2();
+        assert_eq!(whole_pixels(click, click), None);
+        // Between two pixels, whichever way round
+        let (left, right) = (Vec2::new(10.3, 0.0), Vec2::new(10.9, 50.0));
+        assert_eq!(whole_pixels(left, right), None);
+        assert_eq!(whole_pixels(right, left), None);
+    }
 }
@@ -0,0 +1,50 @@
+use crate::notify::{Notifier, Urgency};
+
+/// Why cleave stopped without delivering a capture, each with its own exit status
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CleaveError {
+    /// Anything not covered below, such as `cleave check` finding a problem
+    Other,
+    /// The overlay was closed without confirming a selection
+    #[cfg(feature = "gui")]
+    Cancelled,
+    /// `--if-changed` found the capture matching its baseline
+    Unchanged,
+    /// A selection was confirmed without anything selected
+    NoSelection,
+    /// The screen, or the image given to `cleave edit`, couldn't be read
+    Capture,
+    /// The capture didn't make it onto the clipboard, into `--fallback-dir` or out through stdout
+    Delivery,
+    /// The command line or config file is invalid, or asks for something impossible
+    BadArgs,
+}
+
+impl CleaveError {
+    pub fn exit_code(self) -> u8 {
+        match self {
+            CleaveError::Other => 1,
+            #[cfg(feature = "gui")]
+            CleaveError::Cancelled => 2,
+            CleaveError::Unchanged => 3,
+            CleaveError::NoSelection => 4,
+            CleaveError::Capture => 5,
+            CleaveError::Delivery => 6,
+            CleaveError::BadArgs => 7,
+        }
+    }
+}
+
+/// Reporting an error before turning it into the exit status it calls for
+pub trait OrReport<T> {
+    fn or_report(self, notifier: &dyn Notifier, kind: CleaveError) -> Result<T, CleaveError>;
+}
+
+impl<T> OrReport<T> for anyhow::Result<T> {
+    fn or_report(self, notifier: &dyn Notifier, kind: CleaveError) -> Result<T, CleaveError> {
+        self.map_err(|err| {
+            notifier.notify(Urgency::Error, &format!("{err:#}"));
+            kind
+        })
+    }
+}
@@ -0,0 +1,138 @@
+use std::time::{Duration, Instant};
+
+use anyhow::Context;
+use image::GenericImageView;
+
+use crate::{
+    args::{Args, Command, Region},
+    error::{CleaveError, OrReport},
+    notify::Urgency,
+    ocr::{self, Languages},
+    output::{Delivered, Output},
+    phash,
+    util::{
+        self,
+        capture::{self, Capture},
+        inhibit,
+    },
+};
+
+/// Captures `--region`, or everything with `--fullscreen`, and sends it on without
+/// creating a window or touching the GPU. Errors are reported before they're returned
+pub fn run(args: &Args) -> Result<Delivered, CleaveError> {
+    let started = Instant::now();
+    let notifier = args.notify.notifier(args.verbosity());
+    let notifier = notifier.as_ref();
+    let region = match args.region {
+        Some(region) => Some(
+            region
+                .anchored(args.region_origin)
+                .or_report(notifier, CleaveError::BadArgs)?,
+        ),
+        None if args.fullscreen || args.find_text.is_some() => None,
+        None => {
+            return Err(anyhow::anyhow!(
+                "--if-changed needs a --region, --fullscreen or --find-text to capture"
+            ))
+            .or_report(notifier, CleaveError::BadArgs)
+        }
+    };
+    if args.delay > 0 && args.command.is_none() {
+        notifier.notify(Urgency::Info, &format!("Capturing in {}s", args.delay));
+        let _awake = inhibit::during_delay(notifier);
+        std::thread::sleep(Duration::from_secs(args.delay));
+    }
+    let (capture, source) = match &args.command {
+        Some(Command::Edit { input }) => util::load_image(input).map(|image| {
+            let capture = Capture {
+                image,
+                monitor: None,
+                window: None,
+            };
+            (capture, input.display().to_string())
+        }),
+        _ => {
+            let backend = capture::backend(args);
+            let capture = backend.capture(args);
+            capture.map(|capture| (capture, backend.name().to_string()))
+        }
+    }
+    .or_report(notifier, CleaveError::Capture)?;
+    let image = capture.image;
+
+    let (width, height) = image.dimensions();
+    notifier.notify(
+        Urgency::Debug,
+        &format!(
+            "Captured {width}x{height} from {source} in {:.1}ms",
+            started.elapsed().as_secs_f32() * 1000.0
+        ),
+    );
+    let requested = match (&args.find_text, region) {
+        (Some(text), _) => {
+            let found = ocr::find_text(&image, text, &Languages::from(args))
+                .context("Could not recognize the text on the screen")
+                .or_report(notifier, CleaveError::Capture)?
+                .with_context(|| format!("{text:?} isn't on the screen"))
+                .or_report(notifier, CleaveError::NoSelection)?;
+            notifier.notify(Urgency::Debug, &format!("Found {text:?} at {found}"));
+            found
+        }
+        (None, Some(region)) => region,
+        (None, None) => Region {
+            x: 0,
+            y: 0,
+            width,
+            height,
+        },
+    };
+    // Regions saved for another monitor may hang off this one, so keep what's on it
+    let fitted = requested
+        .fit_within(width, height)
+        .with_context(|| {
+            format!("Region {requested} is entirely outside the {width}x{height} capture")
+        })
+        .or_report(notifier, CleaveError::BadArgs)?;
+    if fitted != requested {
+        notifier.notify(
+            Urgency::Warning,
+            &format!(
+                "Region {requested} doesn't fit in the {width}x{height} capture, captured {fitted} instead"
+            ),
+        );
+    }
+    // `--pad` and `--even-dimensions` change it on purpose, only `fitted` says whether it fit
+    let mut region = fitted.padded(args.pad, width, height);
+    if args.even_dimensions {
+        region = region.even(width, height);
+    }
+
+    let mut selection = image
+        .view(region.x, region.y, region.width, region.height)
+        .to_image();
+    util::round_corners(&mut selection, args.corner_radius);
+
+    let baseline = args.if_changed.as_deref();
+    if let Some(baseline) = baseline {
+        if !phash::differs_from_baseline(&selection, baseline) {
+            notifier.notify(Urgency::Info, "Capture is unchanged since the baseline");
+            return Ok(Delivered::Unchanged);
+        }
+    }
+    // Hashed before delivery, which may decorate or reduce the colors
+    let hashed = baseline.map(|_| selection.clone());
+    let mut output = Output::new(args, notifier, started, capture.monitor.as_ref())
+        .with_window(capture.window.as_ref());
+    if fitted != requested {
+        output = output.with_requested_region(requested);
+    }
+    let delivered = output.deliver(selection, region);
+    if let (Some(baseline), Some(hashed)) = (baseline, hashed) {
+        if !matches!(delivered, Delivered::Failed) {
+            phash::save_baseline(&hashed, baseline)
+                .with_context(|| format!("Could not save baseline {}", baseline.display()))
+                .or_report(notifier, CleaveError::Other)?;
+        }
+    }
+    Ok(delivered)
+}
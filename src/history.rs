@@ -0,0 +1,172 @@
+use std::{
+    io::Write,
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Context;
+#[cfg(feature = "gui")]
+use glam::Vec2;
+
+use crate::project;
+
+/// Undo and redo stacks of earlier states
+#[cfg(feature = "gui")]
+pub struct History<T> {
+    past: Vec<T>,
+    future: Vec<T>,
+}
+
+#[cfg(feature = "gui")]
+impl<T> History<T> {
+    pub fn new() -> Self {
+        Self {
+            past: Vec::new(),
+            future: Vec::new(),
+        }
+    }
+
+    /// Remembers `current` before it is changed, which forgets anything undone
+    pub fn record(&mut self, current: T) {
+        self.past.push(current);
+        self.future.clear();
+    }
+
+    pub fn undo(&mut self, current: T) -> Option<T> {
+        let previous = self.past.pop()?;
+        self.future.push(current);
+        Some(previous)
+    }
+
+    pub fn redo(&mut self, current: T) -> Option<T> {
+        let next = self.future.pop()?;
+        self.past.push(current);
+        Some(next)
+    }
+}
+
+#[cfg(feature = "gui")]
+fn last_selection_path(project: Option<&str>) -> Option<PathBuf> {
+    Some(project::state_dir(project)?.join("last_selection"))
+}
+
+/// Corners of the selection saved by the previous capture, in image coordinates
+#[cfg(feature = "gui")]
+pub fn load_last_selection(project: Option<&str>) -> Option<(Vec2, Vec2)> {
+    let text = std::fs::read_to_string(last_selection_path(project)?).ok()?;
+    let values: Vec<f32> = text
+        .split_whitespace()
+        .map(str::parse)
+        .collect::<Result<_, _>>()
+        .ok()?;
+    match values.as_slice() {
+        [x0, y0, x1, y1] => Some((Vec2::new(*x0, *y0), Vec2::new(*x1, *y1))),
+        _ => None,
+    }
+}
+
+#[cfg(feature = "gui")]
+pub fn save_last_selection(project: Option<&str>, start: Vec2, end: Vec2) -> anyhow::Result<()> {
+    let path = last_selection_path(project).context("Could not find cache directory")?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(
+        &path,
+        format!("{} {} {} {}", start.x, start.y, end.x, end.y),
+    )?;
+    Ok(())
+}
+
+// Captures tagged with `--prompt-tags`, a line each of the time, tags and saved files
+// separated by tabs
+fn tag_index_path(project: Option<&str>) -> Option<PathBuf> {
+    Some(project::state_dir(project)?.join("tags"))
+}
+
+/// A capture from the tag index
+pub struct Tagged {
+    pub time: u64,
+    pub tags: String,
+    pub files: Vec<PathBuf>,
+}
+
+impl Tagged {
+    fn parse(line: &str) -> Option<Self> {
+        let mut fields = line.split('\t');
+        Some(Self {
+            time: fields.next()?.parse().ok()?,
+            tags: fields.next()?.to_string(),
+            files: fields.map(PathBuf::from).collect(),
+        })
+    }
+
+    // Case doesn't matter, and the file names count as tags
+    fn matches(&self, term: &str) -> bool {
+        let term = term.to_lowercase();
+        self.tags.to_lowercase().contains(&term)
+            || self
+                .files
+                .iter()
+                .any(|file| file.to_string_lossy().to_lowercase().contains(&term))
+    }
+}
+
+/// Adds a capture to the index `cleave history search` reads
+pub fn record_tags(project: Option<&str>, tags: &str, files: &[PathBuf]) -> anyhow::Result<()> {
+    let path = tag_index_path(project).context("Could not find cache directory")?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs());
+    let clean = |text: &str| text.replace(['\t', '\n'], " ");
+    let mut line = format!("{time}\t{}", clean(tags));
+    for file in files {
+        // Found again from anywhere, not just where cleave ran
+        let file = std::fs::canonicalize(file).unwrap_or_else(|_| file.clone());
+        line.push('\t');
+        line.push_str(&clean(&file.to_string_lossy()));
+    }
+    let mut index = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)?;
+    writeln!(index, "{line}")?;
+    Ok(())
+}
+
+/// Tagged captures whose tags or files contain `term`, oldest first
+pub fn search_tags(project: Option<&str>, term: &str) -> anyhow::Result<Vec<Tagged>> {
+    let path = tag_index_path(project).context("Could not find cache directory")?;
+    let index = match std::fs::read_to_string(&path) {
+        Ok(index) => index,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err).with_context(|| format!("Could not read {}", path.display())),
+    };
+    Ok(index
+        .lines()
+        .filter_map(Tagged::parse)
+        .filter(|tagged| tagged.matches(term))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Tagged;
+
+    #[test]
+    fn tags_and_files_are_searched_without_case() {
+        let tagged =
+            Tagged::parse("1700000000\tLogin bug, staging\t/home/me/cleave-1.png").unwrap();
+        assert_eq!(tagged.files.len(), 1);
+        assert!(tagged.matches("login"));
+        assert!(tagged.matches("STAGING"));
+        assert!(tagged.matches("cleave-1"));
+        assert!(!tagged.matches("production"));
+        // Captures only copied to the clipboard have no files
+        assert!(Tagged::parse("1700000000\tdraft").unwrap().files.is_empty());
+        assert!(Tagged::parse("not a time\tdraft").is_none());
+    }
+}
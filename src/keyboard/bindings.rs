@@ -0,0 +1,163 @@
+use std::str::FromStr;
+
+use anyhow::Context;
+use winit::keyboard::{Key, NamedKey};
+#[cfg(feature = "gui")]
+use winit::{
+    event::ElementState,
+    keyboard::{ModifiersState, SmolStr},
+};
+
+#[cfg(feature = "gui")]
+use super::Keymap;
+use super::{Action, Direction, MoveMode, CORNER_RADIUS_STEP};
+use crate::annotate::Tool;
+
+pub const ACTION_NAMES: &str =
+    "confirm, confirm-fullscreen, select-all, select-center, record, cancel, move-up, move-down, \
+    move-left, move-right, mode-move, mode-resize, mode-inverse-resize, start-selection, \
+    grow-corners, shrink-corners, lock-aspect, annotate, tool-rectangle, tool-arrow, \
+    tool-freehand, undo, undo-selection, redo-selection, restore-selection, toggle-hud, \
+    enter-coordinates";
+
+fn action_by_name(name: &str) -> Option<Action> {
+    let action = match name {
+        "confirm" => Action::Confirm,
+        "confirm-fullscreen" => Action::ConfirmFullscreen,
+        "select-all" => Action::SelectAll,
+        "select-center" => Action::SelectCenter,
+        "record" => Action::Record,
+        "cancel" => Action::Cancel,
+        "move-up" => Action::Move(Direction::Up),
+        "move-down" => Action::Move(Direction::Down),
+        "move-left" => Action::Move(Direction::Left),
+        "move-right" => Action::Move(Direction::Right),
+        "mode-move" => Action::SetMode(MoveMode::Move),
+        "mode-resize" => Action::SetMode(MoveMode::Resize),
+        "mode-inverse-resize" => Action::SetMode(MoveMode::InverseResize),
+        "start-selection" => Action::StartSelection,
+        "grow-corners" => Action::AdjustCornerRadius(CORNER_RADIUS_STEP),
+        "shrink-corners" => Action::AdjustCornerRadius(-CORNER_RADIUS_STEP),
+        "lock-aspect" => Action::LockAspect(true),
+        "annotate" => Action::ToggleAnnotation,
+        "tool-rectangle" => Action::SetAnnotationTool(Tool::Rectangle),
+        "tool-arrow" => Action::SetAnnotationTool(Tool::Arrow),
+        "tool-freehand" => Action::SetAnnotationTool(Tool::Freehand),
+        "undo" => Action::UndoAnnotation,
+        "undo-selection" => Action::UndoSelection,
+        "redo-selection" => Action::RedoSelection,
+        "restore-selection" => Action::RestoreSelection,
+        "toggle-hud" => Action::ToggleHud,
+        "enter-coordinates" => Action::EnterCoordinates,
+        _ => return None,
+    };
+    Some(action)
+}
+
+fn key_by_name(name: &str) -> Option<Key> {
+    let named = match name {
+        "space" => NamedKey::Space,
+        "enter" | "return" => NamedKey::Enter,
+        "escape" | "esc" => NamedKey::Escape,
+        "tab" => NamedKey::Tab,
+        "backspace" => NamedKey::Backspace,
+        "delete" => NamedKey::Delete,
+        "up" => NamedKey::ArrowUp,
+        "down" => NamedKey::ArrowDown,
+        "left" => NamedKey::ArrowLeft,
+        "right" => NamedKey::ArrowRight,
+        "f12" => NamedKey::F12,
+        // Anything else has to be a single character
+        name => {
+            let mut chars = name.chars();
+            return match (chars.next(), chars.next()) {
+                (Some(c), None) => Some(Key::Character(c.to_lowercase().to_string().into())),
+                _ => None,
+            };
+        }
+    };
+    Some(Key::Named(named))
+}
+
+/// How `key` is written in a binding, the reverse of `key_by_name`
+#[cfg(feature = "gui")]
+pub fn key_name(key: &Key) -> Option<String> {
+    let name = match key {
+        Key::Named(NamedKey::Space) => "space",
+        Key::Named(NamedKey::Enter) => "enter",
+        Key::Named(NamedKey::Escape) => "escape",
+        Key::Named(NamedKey::Tab) => "tab",
+        Key::Named(NamedKey::Backspace) => "backspace",
+        Key::Named(NamedKey::Delete) => "delete",
+        Key::Named(NamedKey::ArrowUp) => "up",
+        Key::Named(NamedKey::ArrowDown) => "down",
+        Key::Named(NamedKey::ArrowLeft) => "left",
+        Key::Named(NamedKey::ArrowRight) => "right",
+        Key::Named(NamedKey::F12) => "f12",
+        Key::Character(c) if c.chars().count() == 1 => return Some(c.to_lowercase()),
+        _ => return None,
+    };
+    Some(name.to_string())
+}
+
+/// One `KEY=ACTION` pair from `--bind`
+#[cfg_attr(not(feature = "gui"), allow(dead_code))]
+#[derive(Clone, Debug)]
+pub struct Binding {
+    key: Key,
+    action: Action,
+}
+
+impl FromStr for Binding {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (key, action) = s
+            .split_once('=')
+            .with_context(|| format!("Expected KEY=ACTION, got {s:?}"))?;
+        let (key, action) = (key.trim().to_lowercase(), action.trim().to_lowercase());
+        let key = key_by_name(&key).with_context(|| {
+            format!("Unknown key {key:?}, expected a single character or a name like space or up")
+        })?;
+        let action = action_by_name(&action).with_context(|| {
+            format!("Unknown action {action:?}, expected one of {ACTION_NAMES}")
+        })?;
+        Ok(Self { key, action })
+    }
+}
+
+/// User bindings layered over one of the built in keymaps
+#[cfg(feature = "gui")]
+pub struct Keybindings {
+    keymap: Keymap,
+    bindings: Vec<Binding>,
+}
+
+#[cfg(feature = "gui")]
+impl Keybindings {
+    pub fn new(keymap: Keymap, bindings: Vec<Binding>) -> Self {
+        Self { keymap, bindings }
+    }
+
+    pub fn action(
+        &self,
+        state: ElementState,
+        key: &Key,
+        modifiers: ModifiersState,
+    ) -> Option<Action> {
+        // Shift changes the reported character, bindings are matched without it
+        let unshifted = match key {
+            Key::Character(c) => Key::Character(SmolStr::new(c.to_lowercase())),
+            key => key.clone(),
+        };
+        // Later bindings win, the same as repeating any other option
+        let bound = self.bindings.iter().rev().find(|b| b.key == unshifted);
+        match bound.map(|b| b.action) {
+            // Held like Alt, released when the key comes back up
+            Some(Action::LockAspect(_)) => Some(Action::LockAspect(state.is_pressed())),
+            Some(action) if state.is_pressed() => Some(action),
+            Some(_) => None,
+            None => self.keymap.action(state, key, modifiers),
+        }
+    }
+}
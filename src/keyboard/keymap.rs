@@ -0,0 +1,124 @@
+use clap::ValueEnum;
+#[cfg(feature = "gui")]
+use winit::{
+    event::ElementState,
+    keyboard::{Key, ModifiersState, NamedKey},
+};
+
+#[cfg(feature = "gui")]
+use super::{Action, Direction, MoveMode, CORNER_RADIUS_STEP};
+#[cfg(feature = "gui")]
+use crate::annotate::Tool;
+
+#[derive(Clone, Copy, Debug, Default, ValueEnum)]
+pub enum Keymap {
+    /// Space to copy, V to record, Escape to cancel, arrow keys to adjust
+    #[default]
+    Default,
+    /// y to copy, q to cancel, hjkl to adjust, v to start a selection at the cursor
+    Vim,
+}
+
+#[cfg(feature = "gui")]
+impl Keymap {
+    pub fn action(
+        self,
+        state: ElementState,
+        key: &Key,
+        modifiers: ModifiersState,
+    ) -> Option<Action> {
+        // Modifiers pick the move mode the same way in every keymap
+        match (state, key) {
+            (ElementState::Pressed, Key::Named(NamedKey::Shift)) => {
+                return Some(Action::SetMode(MoveMode::InverseResize))
+            }
+            (ElementState::Pressed, Key::Named(NamedKey::Control)) => {
+                return Some(Action::SetMode(MoveMode::Move))
+            }
+            (ElementState::Released, Key::Named(NamedKey::Shift | NamedKey::Control)) => {
+                return Some(Action::SetMode(MoveMode::Resize))
+            }
+            (state, Key::Named(NamedKey::Alt)) => {
+                return Some(Action::LockAspect(state.is_pressed()))
+            }
+            (ElementState::Released, _) => return None,
+            _ => {}
+        }
+
+        // Ctrl shortcuts come first, so Ctrl+Y isn't taken as the vim confirm
+        if let (true, Key::Character(c)) = (modifiers.control_key(), key) {
+            match c.to_lowercase().as_str() {
+                "z" if modifiers.shift_key() => return Some(Action::RedoSelection),
+                "z" => return Some(Action::UndoSelection),
+                "y" => return Some(Action::RedoSelection),
+                "a" => return Some(Action::SelectAll),
+                _ => {}
+            }
+        }
+
+        match self {
+            Keymap::Default => default_action(key),
+            Keymap::Vim => vim_action(key),
+        }
+        .or_else(|| common_action(key))
+    }
+}
+
+// Bindings that don't clash with either keymap
+#[cfg(feature = "gui")]
+fn common_action(key: &Key) -> Option<Action> {
+    let action = match key {
+        Key::Character(c) => match c.as_str() {
+            "[" => Action::AdjustCornerRadius(-CORNER_RADIUS_STEP),
+            "]" => Action::AdjustCornerRadius(CORNER_RADIUS_STEP),
+            "a" | "A" => Action::ToggleAnnotation,
+            "f" | "F" => Action::ConfirmFullscreen,
+            "r" | "R" => Action::RestoreSelection,
+            ":" => Action::EnterCoordinates,
+            "1" => Action::SetAnnotationTool(Tool::Rectangle),
+            "2" => Action::SetAnnotationTool(Tool::Arrow),
+            "3" => Action::SetAnnotationTool(Tool::Freehand),
+            _ => return None,
+        },
+        Key::Named(NamedKey::Enter) => Action::SelectCenter,
+        Key::Named(NamedKey::Backspace) => Action::UndoAnnotation,
+        Key::Named(NamedKey::F12) => Action::ToggleHud,
+        _ => return None,
+    };
+    Some(action)
+}
+
+#[cfg(feature = "gui")]
+fn default_action(key: &Key) -> Option<Action> {
+    let action = match key {
+        Key::Named(NamedKey::Space) => Action::Confirm,
+        Key::Named(NamedKey::Escape) => Action::Cancel,
+        Key::Character(c) if c.eq_ignore_ascii_case("v") => Action::Record,
+        Key::Named(NamedKey::ArrowUp) => Action::Move(Direction::Up),
+        Key::Named(NamedKey::ArrowDown) => Action::Move(Direction::Down),
+        Key::Named(NamedKey::ArrowLeft) => Action::Move(Direction::Left),
+        Key::Named(NamedKey::ArrowRight) => Action::Move(Direction::Right),
+        _ => return None,
+    };
+    Some(action)
+}
+
+#[cfg(feature = "gui")]
+fn vim_action(key: &Key) -> Option<Action> {
+    let action = match key {
+        Key::Named(NamedKey::Escape) => Action::Cancel,
+        // Shift changes the reported character, so match both cases
+        Key::Character(c) => match c.as_str() {
+            "y" | "Y" => Action::Confirm,
+            "q" | "Q" => Action::Cancel,
+            "v" | "V" => Action::StartSelection,
+            "k" | "K" => Action::Move(Direction::Up),
+            "j" | "J" => Action::Move(Direction::Down),
+            "h" | "H" => Action::Move(Direction::Left),
+            "l" | "L" => Action::Move(Direction::Right),
+            _ => return None,
+        },
+        _ => return None,
+    };
+    Some(action)
+}
@@ -0,0 +1,60 @@
+mod bindings;
+mod keymap;
+
+pub use bindings::Binding;
+#[cfg(feature = "gui")]
+pub use bindings::{key_name, Keybindings, ACTION_NAMES};
+pub use keymap::Keymap;
+
+use crate::annotate::Tool;
+
+// Parsed from `--bind` in every build, only the overlay acts on them
+#[cfg_attr(not(feature = "gui"), allow(dead_code))]
+#[derive(Clone, Copy, Debug)]
+pub enum Action {
+    Confirm,
+    // Select the whole capture and confirm it
+    ConfirmFullscreen,
+    // Select the whole capture, to confirm or shrink from there
+    SelectAll,
+    // Select the middle of the view to adjust with the move keys, or confirm the selection
+    // when there already is one
+    SelectCenter,
+    // Record the selection to a GIF instead of copying it
+    Record,
+    Cancel,
+    Move(Direction),
+    SetMode(MoveMode),
+    StartSelection,
+    AdjustCornerRadius(f32),
+    LockAspect(bool),
+    ToggleAnnotation,
+    SetAnnotationTool(Tool),
+    UndoAnnotation,
+    UndoSelection,
+    RedoSelection,
+    // The selection from the previous capture
+    RestoreSelection,
+    // Frame timings drawn over the capture
+    ToggleHud,
+    // Type the selection as x,y,w,h
+    EnterCoordinates,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum MoveMode {
+    Move,          // Move the selection
+    InverseResize, // Make the selection smaller
+    Resize,        // Make the selection larger
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+// Pixels added to or removed from the corner radius per key press
+const CORNER_RADIUS_STEP: f32 = 2.0;
@@ -0,0 +1,93 @@
+#[cfg(feature = "serve")]
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+use crate::{
+    args::{Args, Command, HistoryCommand},
+    config,
+    notify::Urgency,
+    protocol,
+};
+
+/// What cleave was asked to do, decided before anything is captured or shown
+pub enum Plan {
+    RunGui(Box<Args>),
+    // `cleave check`
+    Check(Box<Args>),
+    // `cleave settings`
+    Settings(Box<Args>),
+    // `cleave agent`
+    Agent(Box<Args>),
+    // `cleave install-url-handler`
+    InstallUrlHandler,
+    // `cleave verify`
+    Verify(PathBuf),
+    // `cleave serve`
+    #[cfg(feature = "serve")]
+    Serve(Box<Args>, SocketAddr, PathBuf),
+    // `cleave history search`, in the `--project` given
+    SearchHistory(Option<String>, String),
+    // The notification `--notify desktop` shows for a written file, waiting for a click
+    FileActions(Urgency, PathBuf, String),
+    // `--no-gui` or `--fullscreen`, capture straight to the sink
+    Headless(Box<Args>),
+    // The effective options in config file format
+    DumpConfig(String),
+    // `--monitor-list`
+    ListMonitors,
+    // `--window-list`
+    ListWindows,
+}
+
+pub fn plan() -> anyhow::Result<Plan> {
+    let (mut args, matches) = config::load()?;
+    if args.dump_config {
+        return Ok(Plan::DumpConfig(config::dump_config(&matches)));
+    }
+    if args.monitor_list {
+        return Ok(Plan::ListMonitors);
+    }
+    if args.window_list {
+        return Ok(Plan::ListWindows);
+    }
+    match args.command {
+        Some(Command::Check) => return Ok(Plan::Check(Box::new(args))),
+        Some(Command::Settings) => return Ok(Plan::Settings(Box::new(args))),
+        Some(Command::Agent) => return Ok(Plan::Agent(Box::new(args))),
+        Some(Command::InstallUrlHandler) => return Ok(Plan::InstallUrlHandler),
+        Some(Command::Verify { file }) => return Ok(Plan::Verify(file)),
+        #[cfg(feature = "serve")]
+        Some(Command::Serve { listen, token_file }) => {
+            args.command = None;
+            return Ok(Plan::Serve(Box::new(args), listen, token_file));
+        }
+        #[cfg(not(feature = "serve"))]
+        Some(Command::Serve { .. }) => {
+            anyhow::bail!("This cleave was built without the serve feature")
+        }
+        Some(Command::History {
+            command: HistoryCommand::Search { term },
+        }) => return Ok(Plan::SearchHistory(args.project, term)),
+        // Captured like any other run from here on
+        Some(Command::Url { url }) => {
+            args.command = None;
+            protocol::apply(&url, &mut args)?;
+        }
+        Some(Command::FileActions {
+            urgency,
+            file,
+            message,
+        }) => return Ok(Plan::FileActions(urgency, file, message)),
+        _ => {}
+    }
+    #[cfg(not(feature = "ocr"))]
+    anyhow::ensure!(
+        !args.ocr_select && args.find_text.is_none(),
+        "This cleave was built without the ocr feature, so --ocr-select and --find-text aren't available"
+    );
+    let headless = args.region.is_some() || args.if_changed.is_some() || args.find_text.is_some();
+    if args.no_gui || args.fullscreen || headless {
+        return Ok(Plan::Headless(Box::new(args)));
+    }
+    Ok(Plan::RunGui(Box::new(args)))
+}
@@ -1,160 +1,200 @@
 #![windows_subsystem = "windows"]
 
-use winit::{
-    application::ApplicationHandler,
-    event::{ElementState, KeyEvent, MouseButton, WindowEvent},
-    keyboard::{Key, NamedKey},
-};
-
+use std::process::ExitCode;
+
+mod active_window;
+mod agent;
+mod annotate;
+#[cfg(feature = "gui")]
+mod app;
+mod args;
+mod check;
+mod config;
+#[cfg(feature = "gui")]
 mod context;
-use context::{AppContext, Direction, MoveMode};
-
-pub struct Drag {
-    start: (f64, f64),
-    end: Option<(f64, f64)>,
-}
-
-impl Drag {
-    fn coords(&self) -> Option<((u32, u32), (u32, u32))> {
-        let end = self.end?;
-        let (start_x, start_y) = (self.start.0 as u32, self.start.1 as u32);
-        let (end_x, end_y) = (end.0 as u32, end.1 as u32);
-
-        let (min_x, max_x) = (start_x.min(end_x), start_x.max(end_x));
-        let (min_y, max_y) = (start_y.min(end_y), start_y.max(end_y));
-        Some(((min_x, min_y), (max_x, max_y)))
+mod error;
+mod headless;
+mod history;
+mod keyboard;
+mod launcher;
+mod notify;
+mod ocr;
+mod output;
+mod palette;
+mod phash;
+mod project;
+mod protocol;
+mod provenance;
+mod recent;
+mod repo;
+mod report;
+#[cfg(feature = "serve")]
+mod serve;
+#[cfg(feature = "gui")]
+mod settings;
+#[cfg(feature = "gui")]
+mod snap;
+#[cfg(feature = "gui")]
+mod timings;
+mod util;
+mod vectorize;
+use args::Args;
+use error::{CleaveError, OrReport};
+use launcher::Plan;
+use notify::{Notifier, StderrNotifier, Urgency};
+use output::Delivered;
+
+fn main() -> ExitCode {
+    attach_console();
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => ExitCode::from(err.exit_code()),
     }
 }
 
-pub struct Selection {
-    start: (f64, f64),
-    end: (f64, f64),
-}
-
-impl Selection {
-    fn dimensions(&self) -> (f64, f64) {
-        let width = (self.end.0 - self.start.0).abs();
-        let height = (self.end.1 - self.start.1).abs();
-        (width, height)
+// The windows subsystem keeps a console from opening behind the overlay, but leaves
+// stdout and stderr nowhere to go. Run from a terminal, they go to its console instead
+#[cfg(windows)]
+fn attach_console() {
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn AttachConsole(process_id: u32) -> i32;
     }
-
-    fn area(&self) -> f64 {
-        let (width, height) = self.dimensions();
-        width * height
-    }
-
-    // fn aspect_ratio(&self) -> f64 {
-    //     let (width, height) = self.dimensions();
-    //     width / height
-    // }
-
-    // fn center(&self) -> (f64, f64) {
-    //     let x = (self.start.0 + self.end.0) / 2.0;
-    //     let y = (self.start.1 + self.end.1) / 2.0;
-    //     (x, y)
-    // }
-
-    fn coords(&self) -> ((u32, u32), (u32, u32)) {
-        let (start_x, start_y) = (self.start.0, self.start.1);
-        let (end_x, end_y) = (self.end.0, self.end.1);
-
-        let (min_x, max_x) = (start_x.min(end_x).ceil(), start_x.max(end_x).floor());
-        let (min_y, max_y) = (start_y.min(end_y).ceil(), start_y.max(end_y).floor());
-        ((min_x as u32, min_y as u32), (max_x as u32, max_y as u32))
+    const ATTACH_PARENT_PROCESS: u32 = u32::MAX;
+    // Fails when started from Explorer or a shortcut, which have no console to share.
+    // Redirected output keeps its handles either way
+    unsafe {
+        AttachConsole(ATTACH_PARENT_PROCESS);
     }
 }
 
-struct App {
-    context: Option<AppContext>,
-}
+#[cfg(not(windows))]
+fn attach_console() {}
 
-impl ApplicationHandler for App {
-    fn resumed(&mut self, event_loop: &winit::event_loop::ActiveEventLoop) {
-        let context = AppContext::new(event_loop).expect("Could not start context");
-        self.context = Some(context);
+// Clap renders its own errors, as well as help and the version which aren't errors at all
+fn bad_args(err: anyhow::Error) -> CleaveError {
+    match err.downcast::<clap::Error>() {
+        Ok(err) if !err.use_stderr() => err.exit(),
+        Ok(err) => {
+            let _ = err.print();
+        }
+        Err(err) => StderrNotifier::new().notify(Urgency::Error, &format!("{err:#}")),
     }
+    CleaveError::BadArgs
+}
 
-    fn window_event(
-        &mut self,
-        event_loop: &winit::event_loop::ActiveEventLoop,
-        id: winit::window::WindowId,
-        event: winit::event::WindowEvent,
-    ) {
-        let Some(context) = &mut self.context else {
-            return;
-        };
-        if id != context.window_id() {
-            return;
+// Errors are reported where they happen, only the exit status is left by now
+fn run() -> Result<(), CleaveError> {
+    match launcher::plan().map_err(bad_args)? {
+        Plan::RunGui(args) => run_overlay(*args),
+        Plan::Check(args) => match check::run(&args) {
+            true => Ok(()),
+            false => Err(CleaveError::Other),
+        },
+        Plan::Settings(args) => run_settings(&args),
+        Plan::Agent(args) => agent::run(&args),
+        #[cfg(feature = "serve")]
+        Plan::Serve(args, listen, token_file) => serve::run(&args, listen, &token_file),
+        Plan::InstallUrlHandler => {
+            let notifier = StderrNotifier::new();
+            let entry =
+                protocol::install_desktop_entry().or_report(&notifier, CleaveError::Other)?;
+            notifier.notify(
+                Urgency::Info,
+                &format!("Registered {} for cleave:// URLs", entry.display()),
+            );
+            Ok(())
         }
-
-        match event {
-            WindowEvent::RedrawRequested => {
-                context.draw();
+        Plan::SearchHistory(project, term) => {
+            let notifier = StderrNotifier::new();
+            let found = history::search_tags(project.as_deref(), &term)
+                .or_report(&notifier, CleaveError::Other)?;
+            if found.is_empty() {
+                notifier.notify(
+                    Urgency::Error,
+                    &format!("No tagged captures match {term:?}"),
+                );
+                return Err(CleaveError::Other);
             }
-            WindowEvent::CursorMoved { position, .. } => {
-                context.update_mouse_position(position.x, position.y);
-            }
-            WindowEvent::KeyboardInput {
-                event:
-                    KeyEvent {
-                        state,
-                        logical_key: key,
-                        ..
-                    },
-                ..
-            } => match (state, key) {
-                (ElementState::Pressed, Key::Named(NamedKey::Escape)) => {
-                    event_loop.exit();
-                    context.destroy();
-                }
-                (ElementState::Pressed, Key::Named(NamedKey::Space)) => {
-                    context.hide_window();
-                    context.save_selection_to_clipboard();
-                    event_loop.exit();
+            for tagged in found {
+                let mut line = format!("{}\t{}", util::utc_timestamp(tagged.time), tagged.tags);
+                for file in &tagged.files {
+                    line.push('\t');
+                    line.push_str(&file.to_string_lossy());
                 }
-                (ElementState::Pressed, Key::Named(NamedKey::ArrowDown)) => {
-                    context.handle_move(Direction::Down);
-                }
-                (ElementState::Pressed, Key::Named(NamedKey::ArrowUp)) => {
-                    context.handle_move(Direction::Up);
-                }
-                (ElementState::Pressed, Key::Named(NamedKey::ArrowLeft)) => {
-                    context.handle_move(Direction::Left);
-                }
-                (ElementState::Pressed, Key::Named(NamedKey::ArrowRight)) => {
-                    context.handle_move(Direction::Right);
-                }
-                (ElementState::Pressed, Key::Named(NamedKey::Shift)) => {
-                    context.set_mode(MoveMode::InverseResize);
-                }
-                (ElementState::Released, Key::Named(NamedKey::Shift)) => {
-                    context.set_mode(MoveMode::Resize);
-                }
-                (ElementState::Pressed, Key::Named(NamedKey::Control)) => {
-                    context.set_mode(MoveMode::Move);
-                }
-                (ElementState::Released, Key::Named(NamedKey::Control)) => {
-                    context.set_mode(MoveMode::Resize);
+                println!("{line}");
+            }
+            Ok(())
+        }
+        Plan::Verify(file) => {
+            let stamp =
+                provenance::verify(&file).or_report(&StderrNotifier::new(), CleaveError::Other)?;
+            println!("{}: unmodified since it was stamped", file.display());
+            println!("sha256={}\n{}", stamp.hash, stamp.metadata.trim_end());
+            Ok(())
+        }
+        Plan::FileActions(urgency, file, message) => {
+            notify::run_file_actions(urgency, &file, &message)
+                .or_report(&StderrNotifier::new(), CleaveError::Other)
+        }
+        Plan::Headless(args) => match headless::run(&args)? {
+            Delivered::Clipboard(copied) if args.verify_clipboard => {
+                match output::verify_clipboard(&args, &copied) {
+                    true => Ok(()),
+                    false => Err(CleaveError::Delivery),
                 }
-                _ => {}
-            },
-            WindowEvent::MouseInput { state, button, .. } => match (state, button) {
-                (ElementState::Pressed, MouseButton::Left) => context.start_drag(),
-                (ElementState::Released, MouseButton::Left) => context.end_drag(),
-                (_, MouseButton::Right) => context.cancel_drag(),
-                _ => {}
-            },
-            WindowEvent::CloseRequested => {
-                event_loop.exit();
             }
-            _ => {}
+            Delivered::Unchanged => Err(CleaveError::Unchanged),
+            #[cfg(feature = "gui")]
+            Delivered::NothingSelected => Err(CleaveError::NoSelection),
+            Delivered::Failed => Err(CleaveError::Delivery),
+            Delivered::Stdout | Delivered::Saved | Delivered::Clipboard(_) => Ok(()),
+        },
+        Plan::DumpConfig(config) => {
+            print!("{config}");
+            Ok(())
+        }
+        Plan::ListMonitors => {
+            let monitors = util::capture::list_monitors();
+            println!(
+                "{}",
+                monitors.or_report(&StderrNotifier::new(), CleaveError::Capture)?
+            );
+            Ok(())
+        }
+        Plan::ListWindows => {
+            let windows = util::capture::list_windows();
+            println!(
+                "{}",
+                windows.or_report(&StderrNotifier::new(), CleaveError::Capture)?
+            );
+            Ok(())
         }
     }
 }
 
-fn main() -> anyhow::Result<()> {
-    let mut app = App { context: None };
-    let event_loop = winit::event_loop::EventLoop::new()?;
-    event_loop.run_app(&mut app)?;
-    Ok(())
+#[cfg(feature = "gui")]
+fn run_overlay(args: Args) -> Result<(), CleaveError> {
+    app::run(args)
+}
+
+#[cfg(feature = "gui")]
+fn run_settings(args: &Args) -> Result<(), CleaveError> {
+    let notifier = args.notify.notifier(args.verbosity());
+    settings::run(args).or_report(notifier.as_ref(), CleaveError::Other)
+}
+
+// Builds without the `gui` feature only capture headlessly
+#[cfg(not(feature = "gui"))]
+fn run_overlay(_: Args) -> Result<(), CleaveError> {
+    Err(bad_args(anyhow::anyhow!(
+        "This cleave was built without the overlay, pass --region, --fullscreen or --no-gui"
+    )))
+}
+
+#[cfg(not(feature = "gui"))]
+fn run_settings(_: &Args) -> Result<(), CleaveError> {
+    Err(bad_args(anyhow::anyhow!(
+        "This cleave was built without the settings window, edit the config file instead"
+    )))
 }
@@ -0,0 +1,264 @@
+use std::{io::IsTerminal, path::Path};
+
+use clap::ValueEnum;
+
+#[cfg(all(feature = "notifications", unix, not(target_os = "macos")))]
+use crate::util::clipboard;
+
+#[derive(Clone, Copy, Debug, Default, ValueEnum)]
+pub enum NotifyBackend {
+    /// Print messages to stderr
+    #[default]
+    Stderr,
+    /// Show messages as desktop notifications
+    Desktop,
+}
+
+impl NotifyBackend {
+    /// Only passes on the messages `verbosity` asks for
+    pub fn notifier(self, verbosity: Verbosity) -> Box<dyn Notifier> {
+        let notifier: Box<dyn Notifier> = match self {
+            NotifyBackend::Stderr => Box::new(StderrNotifier::new()),
+            NotifyBackend::Desktop => Box::new(DesktopNotifier),
+        };
+        Box::new(FilteredNotifier {
+            inner: notifier,
+            verbosity,
+        })
+    }
+}
+
+/// How much cleave reports, set with `--quiet` and `--verbose`
+#[derive(Clone, Copy, Debug, Default, PartialEq, PartialOrd)]
+pub enum Verbosity {
+    // Warnings and errors
+    Quiet,
+    #[default]
+    Normal,
+    // Everything, including what each step did and how long it took
+    Verbose,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum Urgency {
+    // Details only shown with `--verbose`
+    Debug,
+    Info,
+    Warning,
+    Error,
+}
+
+#[cfg(feature = "notifications")]
+impl Urgency {
+    fn summary(self) -> &'static str {
+        match self {
+            Urgency::Debug | Urgency::Info => "Cleave",
+            Urgency::Warning => "Cleave warning",
+            Urgency::Error => "Cleave error",
+        }
+    }
+}
+
+pub trait Notifier {
+    fn notify(&self, urgency: Urgency, message: &str);
+
+    /// Like `notify`, followed by a suggestion for what to try instead
+    fn notify_with_hint(&self, urgency: Urgency, message: &str, hint: &str) {
+        self.notify(urgency, &format!("{message}\nHint: {hint}"));
+    }
+
+    /// Like `notify`, for a message about `file` having been written. Desktop
+    /// notifications offer to open it or copy its path
+    fn notify_file(&self, urgency: Urgency, message: &str, _: &Path) {
+        self.notify(urgency, message);
+    }
+}
+
+pub struct StderrNotifier {
+    color: bool,
+}
+
+impl StderrNotifier {
+    pub fn new() -> Self {
+        // https://no-color.org, any non-empty value turns colors off
+        let no_color = std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+        Self {
+            color: !no_color && std::io::stderr().is_terminal(),
+        }
+    }
+
+    fn paint(&self, code: &str, text: &str) -> String {
+        if self.color {
+            format!("\x1b[{code}m{text}\x1b[0m")
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+impl Notifier for StderrNotifier {
+    fn notify(&self, urgency: Urgency, message: &str) {
+        match urgency {
+            Urgency::Debug => eprintln!("{}", self.paint("2", message)),
+            Urgency::Info => eprintln!("{}", self.paint("32", message)),
+            Urgency::Warning => eprintln!("{} {message}", self.paint("1;33", "Warning:")),
+            Urgency::Error => eprintln!("{} {message}", self.paint("1;31", "Error:")),
+        }
+    }
+
+    fn notify_with_hint(&self, urgency: Urgency, message: &str, hint: &str) {
+        self.notify(urgency, message);
+        eprintln!("  {} {hint}", self.paint("36", "hint:"));
+    }
+}
+
+// Drops the messages below the verbosity, `--quiet` is for scripts that only care about problems
+struct FilteredNotifier {
+    inner: Box<dyn Notifier>,
+    verbosity: Verbosity,
+}
+
+impl FilteredNotifier {
+    fn passes(&self, urgency: Urgency) -> bool {
+        match urgency {
+            Urgency::Debug => self.verbosity == Verbosity::Verbose,
+            Urgency::Info => self.verbosity >= Verbosity::Normal,
+            Urgency::Warning | Urgency::Error => true,
+        }
+    }
+}
+
+impl Notifier for FilteredNotifier {
+    fn notify(&self, urgency: Urgency, message: &str) {
+        if self.passes(urgency) {
+            self.inner.notify(urgency, message);
+        }
+    }
+
+    fn notify_with_hint(&self, urgency: Urgency, message: &str, hint: &str) {
+        if self.passes(urgency) {
+            self.inner.notify_with_hint(urgency, message, hint);
+        }
+    }
+
+    fn notify_file(&self, urgency: Urgency, message: &str, file: &Path) {
+        if self.passes(urgency) {
+            self.inner.notify_file(urgency, message, file);
+        }
+    }
+}
+
+pub struct DesktopNotifier;
+
+impl DesktopNotifier {
+    /// Shows a notification, failing if there is no notification server
+    #[cfg(feature = "notifications")]
+    pub fn show(&self, urgency: Urgency, message: &str) -> anyhow::Result<()> {
+        notify_rust::Notification::new()
+            .appname("Cleave")
+            .summary(urgency.summary())
+            .body(message)
+            .show()?;
+        Ok(())
+    }
+
+    #[cfg(not(feature = "notifications"))]
+    pub fn show(&self, _: Urgency, _: &str) -> anyhow::Result<()> {
+        anyhow::bail!("This cleave was built without desktop notifications")
+    }
+}
+
+impl Notifier for DesktopNotifier {
+    fn notify(&self, urgency: Urgency, message: &str) {
+        // A popup for every step would be too much, and without the feature there are none
+        if matches!(urgency, Urgency::Debug) || cfg!(not(feature = "notifications")) {
+            StderrNotifier::new().notify(urgency, message);
+            return;
+        }
+        // Don't lose the message if the notification server is unavailable
+        if let Err(err) = self.show(urgency, message) {
+            let stderr = StderrNotifier::new();
+            stderr.notify(
+                Urgency::Warning,
+                &format!("Could not show notification: {err}"),
+            );
+            stderr.notify(urgency, message);
+        }
+    }
+
+    // Waiting for a click would keep cleave running, so a copy of it waits instead, the
+    // same way the clipboard tools stay behind to serve the capture
+    #[cfg(all(feature = "notifications", unix, not(target_os = "macos")))]
+    fn notify_file(&self, urgency: Urgency, message: &str, file: &Path) {
+        // Also covers a missing notification server, which `notify` falls back from
+        let actions = notify_rust::get_capabilities()
+            .is_ok_and(|capabilities| capabilities.iter().any(|c| c == "actions"));
+        if matches!(urgency, Urgency::Debug) || !actions {
+            self.notify(urgency, message);
+            return;
+        }
+        let urgency_name = urgency
+            .to_possible_value()
+            .map(|value| value.get_name().to_string())
+            .unwrap_or_default();
+        let spawned = std::env::current_exe().and_then(|exe| {
+            std::process::Command::new(exe)
+                .args(["file-actions", "--urgency", &urgency_name, "--"])
+                .arg(file)
+                .arg(message)
+                // Held open, they would keep anything reading cleave's output waiting
+                .stdin(std::process::Stdio::null())
+                .stdout(std::process::Stdio::null())
+                .stderr(std::process::Stdio::null())
+                .spawn()
+        });
+        if let Err(err) = spawned {
+            StderrNotifier::new().notify(
+                Urgency::Warning,
+                &format!("Could not offer to open {}: {err}", file.display()),
+            );
+            self.notify(urgency, message);
+        }
+    }
+}
+
+// Notification action identifiers for `run_file_actions`
+#[cfg(all(feature = "notifications", unix, not(target_os = "macos")))]
+const OPEN: &str = "open";
+#[cfg(all(feature = "notifications", unix, not(target_os = "macos")))]
+const COPY_PATH: &str = "copy-path";
+
+/// Runs `cleave file-actions`: shows `message` about `file` with buttons to open it or
+/// copy its path, and does whichever is clicked before the notification goes away
+#[cfg(all(feature = "notifications", unix, not(target_os = "macos")))]
+pub fn run_file_actions(urgency: Urgency, file: &Path, message: &str) -> anyhow::Result<()> {
+    let handle = notify_rust::Notification::new()
+        .appname("Cleave")
+        .summary(urgency.summary())
+        .body(message)
+        .action(OPEN, "Open")
+        .action(COPY_PATH, "Copy path")
+        .show()?;
+    let mut clicked = Ok(());
+    handle.wait_for_action(|action| {
+        clicked = match action {
+            OPEN => std::process::Command::new("xdg-open")
+                .arg(file)
+                .status()
+                .map(drop)
+                .map_err(anyhow::Error::from),
+            COPY_PATH => {
+                let path = std::fs::canonicalize(file).unwrap_or_else(|_| file.to_path_buf());
+                clipboard::serve_text(&path.to_string_lossy())
+            }
+            // Dismissed or timed out
+            _ => Ok(()),
+        }
+    });
+    clicked
+}
+
+#[cfg(not(all(feature = "notifications", unix, not(target_os = "macos"))))]
+pub fn run_file_actions(_: Urgency, _: &Path, _: &str) -> anyhow::Result<()> {
+    anyhow::bail!("Notification actions need the notifications feature on Linux or BSD")
+}
@@ -0,0 +1,542 @@
+#[cfg(feature = "gui")]
+use std::{
+    collections::BTreeMap,
+    sync::mpsc::{self, Receiver, TryRecvError},
+};
+#[cfg(feature = "ocr")]
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+#[cfg(feature = "ocr")]
+use anyhow::Context;
+#[cfg(feature = "gui")]
+use glam::Vec2;
+use image::RgbaImage;
+
+use crate::args::{Args, Region};
+#[cfg(feature = "ocr")]
+use crate::util;
+
+// Tesseract's page, block, paragraph and line numbers, which together name a line
+type LineKey = (u32, u32, u32, u32);
+
+// Every installed language at once, for `--ocr-language all`. Tesseract can't tell which
+// language a capture is in, only its script, so this isn't detection
+#[cfg(feature = "ocr")]
+const ALL: &str = "all";
+// The fast models tesseract's packages ship, fetched by `--ocr-download`
+#[cfg(feature = "ocr")]
+const MODELS_URL: &str = "https://github.com/tesseract-ocr/tessdata_fast/raw/main";
+// The SHA-256 of each downloaded model, in the cache next to them as `sha256sum` writes it
+#[cfg(feature = "ocr")]
+const CHECKSUMS: &str = "SHA256SUMS";
+
+/// The languages to recognize text in, from `--ocr-language` and `--ocr-download`
+#[derive(Clone)]
+pub struct Languages {
+    #[cfg(feature = "ocr")]
+    requested: String,
+    #[cfg(feature = "ocr")]
+    download: bool,
+}
+
+#[cfg(feature = "ocr")]
+impl From<&Args> for Languages {
+    fn from(args: &Args) -> Self {
+        Self {
+            requested: args.ocr_language.clone(),
+            download: args.ocr_download,
+        }
+    }
+}
+
+// Nothing to pick from without tesseract
+#[cfg(not(feature = "ocr"))]
+impl From<&Args> for Languages {
+    fn from(_: &Args) -> Self {
+        Self {}
+    }
+}
+
+// A word tesseract found and the line it's on
+struct Word {
+    line: LineKey,
+    region: Region,
+    text: String,
+}
+
+/// Lines of text in the capture for `--ocr-select`, recognized by tesseract alongside
+/// the overlay so it doesn't wait on them
+#[cfg(feature = "gui")]
+pub struct TextLines {
+    pending: Option<Receiver<anyhow::Result<Vec<Region>>>>,
+    lines: Vec<Region>,
+}
+
+#[cfg(feature = "gui")]
+impl TextLines {
+    pub fn recognize(img: RgbaImage, languages: Languages) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            let _ = sender.send(run_tesseract(&img, &languages).map(|words| lines(&words)));
+        });
+        Self {
+            pending: Some(receiver),
+            lines: Vec::new(),
+        }
+    }
+
+    /// Takes the lines once tesseract is done, returning its error the one time it fails
+    pub fn poll(&mut self) -> Option<anyhow::Error> {
+        let result = match self.pending.as_ref()?.try_recv() {
+            Ok(result) => result,
+            Err(TryRecvError::Empty) => return None,
+            Err(TryRecvError::Disconnected) => Err(anyhow::anyhow!("Text recognition stopped")),
+        };
+        self.pending = None;
+        match result {
+            Ok(lines) => {
+                self.lines = lines;
+                None
+            }
+            Err(err) => Some(err),
+        }
+    }
+
+    /// The line under `point`, in capture pixels
+    pub fn line_at(&self, point: Vec2) -> Option<Region> {
+        self.lines.iter().copied().find(|line| {
+            let (x, y) = (point.x as u32, point.y as u32);
+            point.x >= 0.0
+                && point.y >= 0.0
+                && (line.x..line.x + line.width).contains(&x)
+                && (line.y..line.y + line.height).contains(&y)
+        })
+    }
+}
+
+#[cfg(feature = "ocr")]
+fn run_tesseract(img: &RgbaImage, languages: &Languages) -> anyhow::Result<Vec<Word>> {
+    let (language, models) = languages.resolve()?;
+    let mut png = Vec::new();
+    util::write_png(img, Default::default(), &mut png)?;
+    let mut tesseract = Command::new("tesseract");
+    if let Some(models) = models {
+        tesseract.arg("--tessdata-dir").arg(models);
+    }
+    let mut child = tesseract
+        .args(["stdin", "stdout", "-l", &language, "tsv"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("Could not run tesseract, is it installed?")?;
+    let mut stdin = child.stdin.take().context("No stdin to write to")?;
+    // Tesseract only starts writing once it has read the whole image
+    let writer = std::thread::spawn(move || stdin.write_all(&png));
+    let output = child.wait_with_output()?;
+    writer
+        .join()
+        .map_err(|_| anyhow::anyhow!("Could not send the capture to tesseract"))??;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        anyhow::bail!(
+            "tesseract failed with {}: {}",
+            output.status,
+            stderr.lines().next().unwrap_or_default()
+        );
+    }
+    Ok(parse_tsv(&String::from_utf8_lossy(&output.stdout)))
+}
+
+// `--ocr-select` and `--find-text` are turned away up front without the feature
+#[cfg(not(feature = "ocr"))]
+fn run_tesseract(_: &RgbaImage, _: &Languages) -> anyhow::Result<Vec<Word>> {
+    anyhow::bail!("This cleave was built without the ocr feature")
+}
+
+#[cfg(feature = "ocr")]
+impl Languages {
+    // The languages to hand tesseract and the directory to load their models from, when
+    // it isn't tesseract's own. Tesseract reads models from one directory only, so
+    // downloaded ones are used when the installed ones don't cover every language
+    fn resolve(&self) -> anyhow::Result<(String, Option<PathBuf>)> {
+        let installed = installed_languages()?;
+        if self.requested == ALL {
+            // osd only detects the script and orientation, it can't be read with
+            let all: Vec<&str> = installed
+                .iter()
+                .map(String::as_str)
+                .filter(|language| *language != "osd")
+                .collect();
+            anyhow::ensure!(!all.is_empty(), "tesseract has no languages installed");
+            return Ok((all.join("+"), None));
+        }
+        let requested: Vec<&str> = self.requested.split('+').collect();
+        let uninstalled: Vec<&str> = requested
+            .iter()
+            .copied()
+            .filter(|language| !installed.iter().any(|i| i == language))
+            .collect();
+        if uninstalled.is_empty() {
+            return Ok((self.requested.clone(), None));
+        }
+        let cache = dirs::cache_dir()
+            .context("No cache directory to keep OCR models in")?
+            .join("cleave")
+            .join("tessdata");
+        let mut missing = Vec::new();
+        for language in requested {
+            if !verify_model(&cache, language)? {
+                missing.push(language);
+            }
+        }
+        if !missing.is_empty() {
+            anyhow::ensure!(
+                self.download,
+                "tesseract has no model for {} installed, install it or pass --ocr-download to fetch the models into {}",
+                uninstalled.join(", "),
+                cache.display()
+            );
+            std::fs::create_dir_all(&cache)?;
+            for language in missing {
+                download_model(&cache, language)?;
+            }
+        }
+        Ok((self.requested.clone(), Some(cache)))
+    }
+}
+
+#[cfg(feature = "ocr")]
+fn installed_languages() -> anyhow::Result<Vec<String>> {
+    let output = Command::new("tesseract")
+        .arg("--list-langs")
+        .output()
+        .context("Could not run tesseract, is it installed?")?;
+    // Older versions list them on stderr. Either way the first line is a heading
+    let listed = if output.stdout.is_empty() {
+        output.stderr
+    } else {
+        output.stdout
+    };
+    Ok(String::from_utf8_lossy(&listed)
+        .lines()
+        .skip(1)
+        .map(|language| language.trim().to_string())
+        .filter(|language| !language.is_empty())
+        .collect())
+}
+
+#[cfg(feature = "ocr")]
+fn model_path(models: &Path, language: &str) -> PathBuf {
+    models.join(format!("{language}.traineddata"))
+}
+
+// The checksum `SHA256SUMS` lists for `language`'s model
+#[cfg(feature = "ocr")]
+fn listed_checksum(models: &Path, language: &str) -> anyhow::Result<Option<String>> {
+    let file = format!("{language}.traineddata");
+    let sums = match std::fs::read_to_string(models.join(CHECKSUMS)) {
+        Ok(sums) => sums,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err).context("Could not read the model checksums"),
+    };
+    Ok(sums.lines().find_map(|line| {
+        let (hash, name) = line.split_once(char::is_whitespace)?;
+        // sha256sum marks binary files with a star
+        (name.trim_start().trim_start_matches('*') == file).then(|| hash.to_lowercase())
+    }))
+}
+
+// Whether `language`'s model is downloaded and listed in `SHA256SUMS`, failing if it was
+// changed since. Unlisted models are downloaded again
+#[cfg(feature = "ocr")]
+fn verify_model(models: &Path, language: &str) -> anyhow::Result<bool> {
+    let model = model_path(models, language);
+    let Some(listed) = listed_checksum(models, language)? else {
+        return Ok(false);
+    };
+    let Ok(contents) = std::fs::read(&model) else {
+        return Ok(false);
+    };
+    let actual = util::sha256_hex(&contents);
+    anyhow::ensure!(
+        actual == listed,
+        "{} doesn't match its checksum in {}, delete it to download it again",
+        model.display(),
+        models.join(CHECKSUMS).display()
+    );
+    Ok(true)
+}
+
+// Fetched with curl, which is already there wherever tesseract can be installed
+#[cfg(feature = "ocr")]
+fn download_model(models: &Path, language: &str) -> anyhow::Result<()> {
+    // The name ends up in a path and a URL
+    anyhow::ensure!(
+        !language.is_empty()
+            && language
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_'),
+        "{language:?} isn't a tesseract language"
+    );
+    let model = model_path(models, language);
+    // Moved into place once complete, an interrupted download would otherwise look cached
+    let partial = model.with_extension("part");
+    let status = Command::new("curl")
+        .args([
+            "--fail",
+            "--silent",
+            "--show-error",
+            "--location",
+            "--output",
+        ])
+        .arg(&partial)
+        .arg(format!("{MODELS_URL}/{language}.traineddata"))
+        .status()
+        .context("Could not run curl to download the model")?;
+    if !status.success() {
+        let _ = std::fs::remove_file(&partial);
+        anyhow::bail!("Could not download the {language} model, curl failed with {status}");
+    }
+    let actual = util::sha256_hex(&std::fs::read(&partial)?);
+    match listed_checksum(models, language)? {
+        Some(listed) if listed != actual => {
+            let _ = std::fs::remove_file(&partial);
+            anyhow::bail!(
+                "The downloaded {language} model hashes to {actual} instead of {listed}, it was thrown away"
+            );
+        }
+        Some(_) => {}
+        None => {
+            let mut sums = std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(models.join(CHECKSUMS))?;
+            writeln!(sums, "{actual}  {language}.traineddata")?;
+        }
+    }
+    std::fs::rename(&partial, &model)?;
+    Ok(())
+}
+
+/// Where `text` first appears in `img` for `--find-text`, in reading order. Words are
+/// compared ignoring case and the punctuation around them, and a phrase has to be on one line
+pub fn find_text(
+    img: &RgbaImage,
+    text: &str,
+    languages: &Languages,
+) -> anyhow::Result<Option<Region>> {
+    let words = run_tesseract(img, languages)?;
+    Ok(find_phrase(&words, text))
+}
+
+fn find_phrase(words: &[Word], text: &str) -> Option<Region> {
+    let wanted: Vec<String> = text.split_whitespace().map(normalize).collect();
+    if wanted.is_empty() {
+        return None;
+    }
+    words.windows(wanted.len()).find_map(|run| {
+        let on_one_line = run.iter().all(|word| word.line == run[0].line);
+        let matches = run
+            .iter()
+            .zip(&wanted)
+            .all(|(word, wanted)| normalize(&word.text) == *wanted);
+        (on_one_line && matches).then(|| {
+            run.iter()
+                .map(|word| word.region)
+                .reduce(Region::union)
+                .expect("runs aren't empty")
+        })
+    })
+}
+
+fn normalize(word: &str) -> String {
+    word.trim_matches(|c: char| !c.is_alphanumeric())
+        .to_lowercase()
+}
+
+// Line rows in tesseract's output cover their words' whitespace too, so lines are made
+// from the words with text instead
+#[cfg(feature = "gui")]
+fn lines(words: &[Word]) -> Vec<Region> {
+    let mut lines: BTreeMap<LineKey, Region> = BTreeMap::new();
+    for word in words {
+        lines
+            .entry(word.line)
+            .and_modify(|line| *line = line.union(word.region))
+            .or_insert(word.region);
+    }
+    lines.into_values().collect()
+}
+
+// Tesseract's TSV has a row per page, block, paragraph, line and word, in reading order.
+// Only the words with text are kept
+#[cfg(feature = "ocr")]
+fn parse_tsv(tsv: &str) -> Vec<Word> {
+    const WORD: &str = "5";
+    let mut words = Vec::new();
+    for row in tsv.lines().skip(1) {
+        let fields: Vec<&str> = row.split('\t').collect();
+        let [level, page, block, paragraph, line, _word, left, top, width, height, _conf, text] =
+            fields[..]
+        else {
+            continue;
+        };
+        if level != WORD || text.trim().is_empty() {
+            continue;
+        }
+        let numbers = [page, block, paragraph, line, left, top, width, height]
+            .map(|field| field.parse::<u32>().ok());
+        let [Some(page), Some(block), Some(paragraph), Some(line), Some(x), Some(y), Some(width), Some(height)] =
+            numbers
+        else {
+            continue;
+        };
+        words.push(Word {
+            line: (page, block, paragraph, line),
+            region: Region {
+                x,
+                y,
+                width,
+                height,
+            },
+            text: text.to_string(),
+        });
+    }
+    words
+}
+
+#[cfg(all(test, feature = "ocr"))]
+mod tests {
+    use super::{find_phrase, normalize, parse_tsv, verify_model, CHECKSUMS};
+    use crate::{args::Region, util};
+
+    // Two lines of a paragraph with the page, block, paragraph and line rows tesseract
+    // writes around the words, an empty word and a malformed row
+    const TSV: &str = "level\tpage_num\tblock_num\tpar_num\tline_num\tword_num\tleft\ttop\twidth\theight\tconf\ttext
+1\t1\t0\t0\t0\t0\t0\t0\t800\t600\t-1\t
+2\t1\t1\t0\t0\t0\t10\t10\t400\t60\t-1\t
+3\t1\t1\t1\t0\t0\t10\t10\t400\t60\t-1\t
+4\t1\t1\t1\t1\t0\t10\t10\t300\t20\t-1\t
+5\t1\t1\t1\t1\t1\t10\t10\t60\t20\t96.5\tHello,
+5\t1\t1\t1\t1\t2\t80\t12\t70\t18\t95.1\tWORLD!
+5\t1\t1\t1\t1\t3\t160\t10\t20\t20\t30.0\t 
+4\t1\t1\t1\t2\t0\t10\t40\t400\t30\t-1\t
+5\t1\t1\t1\t2\t1\t10\t40\t50\t20\t91.0\t\"Save
+5\t1\t1\t1\t2\t2\t70\t45\t40\t25\t90.2\tas...\"
+5\t1\t1\t1\t2\t3\t120\t40\tx\t20\t90.2\tbroken
+";
+
+    #[test]
+    fn only_words_with_text_are_parsed() {
+        let words = parse_tsv(TSV);
+        let texts: Vec<&str> = words.iter().map(|word| word.text.as_str()).collect();
+        assert_eq!(texts, ["Hello,", "WORLD!", "\"Save", "as...\""]);
+        assert_eq!(words[1].line, (1, 1, 1, 1));
+        assert_eq!(words[2].line, (1, 1, 1, 2));
+        assert_eq!(
+            words[1].region,
+            Region {
+                x: 80,
+                y: 12,
+                width: 70,
+                height: 18,
+            }
+        );
+        assert!(parse_tsv("").is_empty());
+    }
+
+    #[test]
+    fn normalize_drops_case_and_surrounding_punctuation() {
+        assert_eq!(normalize("WORLD!"), "world");
+        assert_eq!(normalize("\"Save"), "save");
+        // Punctuation inside a word is part of it
+        assert_eq!(normalize("(don't)"), "don't");
+        assert_eq!(normalize("..."), "");
+    }
+
+    #[test]
+    fn phrases_are_found_on_one_line() {
+        let words = parse_tsv(TSV);
+        // Both words' boxes
+        assert_eq!(
+            find_phrase(&words, "hello world"),
+            Some(Region {
+                x: 10,
+                y: 10,
+                width: 140,
+                height: 20,
+            })
+        );
+        assert_eq!(
+            find_phrase(&words, "save AS"),
+            Some(Region {
+                x: 10,
+                y: 40,
+                width: 100,
+                height: 30,
+            })
+        );
+        assert_eq!(find_phrase(&words, "world"), Some(words[1].region));
+    }
+
+    #[test]
+    fn missing_phrases_are_not_found() {
+        let words = parse_tsv(TSV);
+        assert_eq!(find_phrase(&words, "goodbye"), None);
+        // Across two lines
+        assert_eq!(find_phrase(&words, "world save"), None);
+        // Out of order
+        assert_eq!(find_phrase(&words, "world hello"), None);
+        assert_eq!(find_phrase(&words, "  "), None);
+    }
+
+    #[test]
+    fn models_must_match_their_checksums() {
+        let models = std::env::temp_dir().join(format!("cleave-tessdata-{}", std::process::id()));
+        std::fs::create_dir_all(&models).unwrap();
+        std::fs::write(models.join("eng.traineddata"), b"model").unwrap();
+        // Not listed yet, so it's downloaded again
+        assert!(!verify_model(&models, "eng").unwrap());
+
+        let sums = format!(
+            "{}  eng.traineddata\n{} *deu.traineddata\n",
+            util::sha256_hex(b"model"),
+            util::sha256_hex(b"other")
+        );
+        std::fs::write(models.join(CHECKSUMS), sums).unwrap();
+        assert!(verify_model(&models, "eng").unwrap());
+        // Listed but not downloaded
+        assert!(!verify_model(&models, "deu").unwrap());
+        std::fs::write(models.join("deu.traineddata"), b"tampered").unwrap();
+        assert!(verify_model(&models, "deu").is_err());
+        std::fs::remove_dir_all(&models).unwrap();
+    }
+
+    #[cfg(feature = "gui")]
+    #[test]
+    fn lines_cover_their_words() {
+        let lines = super::lines(&parse_tsv(TSV));
+        assert_eq!(
+            lines,
+            [
+                Region {
+                    x: 10,
+                    y: 10,
+                    width: 140,
+                    height: 20,
+                },
+                Region {
+                    x: 10,
+                    y: 40,
+                    width: 100,
+                    height: 30,
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,653 @@
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Context;
+use image::{imageops::FilterType, RgbaImage};
+
+use crate::{
+    args::{Args, Command, Region, TileGrid},
+    history,
+    notify::{Notifier, Urgency},
+    palette, phash, project,
+    provenance::Stamp,
+    recent, repo,
+    report::Report,
+    util::{
+        self,
+        capture::{MonitorInfo, WindowInfo},
+        clipboard::{self, Copied},
+        decorate, quantize, Sink,
+    },
+    vectorize,
+};
+
+// Tries at putting the capture back on the clipboard with `--verify-clipboard`
+const CLIPBOARD_VERIFY_ATTEMPTS: u32 = 3;
+// Time for a clipboard manager to take over before checking what it holds
+const CLIPBOARD_VERIFY_DELAY: Duration = Duration::from_millis(250);
+// `--retina-pair` scale for images given to `cleave edit`, which come from no monitor
+const RETINA_EDIT_SCALE: f32 = 2.0;
+
+/// Where `Output::deliver` got the capture to
+pub enum Delivered {
+    Stdout,
+    // What was copied, to check on with `--verify-clipboard`
+    Clipboard(RgbaImage),
+    // Skipped with `--if-changed`, the capture matched the baseline
+    Unchanged,
+    // Saved to `--fallback-dir` since the clipboard didn't work
+    Saved,
+    // Confirmed in the overlay without a selection
+    #[cfg(feature = "gui")]
+    NothingSelected,
+    Failed,
+}
+
+/// Everything that happens to a finished capture, with or without the overlay
+pub struct Output<'a> {
+    args: &'a Args,
+    notifier: &'a dyn Notifier,
+    // For the time taken in the summary
+    started: Instant,
+    // Where the capture came from, for `--json`
+    monitor: Option<&'a MonitorInfo>,
+    window: Option<&'a WindowInfo>,
+    // What was asked for, when it had to be fitted to the capture
+    requested: Option<Region>,
+    // Typed for `--prompt-tags`
+    tags: Option<&'a str>,
+}
+
+impl<'a> Output<'a> {
+    pub fn new(
+        args: &'a Args,
+        notifier: &'a dyn Notifier,
+        started: Instant,
+        monitor: Option<&'a MonitorInfo>,
+    ) -> Self {
+        Self {
+            args,
+            notifier,
+            started,
+            monitor,
+            window: None,
+            requested: None,
+            tags: None,
+        }
+    }
+
+    /// Notes that `region` was asked for but had to be changed, for `--json`
+    pub fn with_requested_region(mut self, region: Region) -> Self {
+        self.requested = Some(region);
+        self
+    }
+
+    /// Sets the window the capture is of, so `--json` can place the region on the screen
+    pub fn with_window(mut self, window: Option<&'a WindowInfo>) -> Self {
+        self.window = window;
+        self
+    }
+
+    /// Sets the tags typed for `--prompt-tags`, written into the PNG and the tag index
+    #[cfg(feature = "gui")]
+    pub fn with_tags(mut self, tags: Option<&'a str>) -> Self {
+        self.tags = tags.map(str::trim).filter(|tags| !tags.is_empty());
+        self
+    }
+
+    /// Sends the image to its sink, then runs the extra outputs asked for.
+    /// `region` is where the image was cut from
+    pub fn deliver(&self, selection_image: RgbaImage, region: Region) -> Delivered {
+        let mut files = Vec::new();
+        let mut selection_image = decorate::decorate(selection_image, self.args);
+        if let Some(colors) = self.args.colors {
+            quantize::reduce_colors(&mut selection_image, colors as usize, self.args.dither);
+        }
+        if self.args.warn_duplicate {
+            match phash::check_duplicate(
+                &selection_image,
+                self.args.project.as_deref(),
+                !self.args.private,
+            ) {
+                Ok(true) => self.notifier.notify(
+                    Urgency::Warning,
+                    "Capture is nearly identical to the previous one",
+                ),
+                Ok(false) => {}
+                Err(err) => self.notifier.notify(
+                    Urgency::Error,
+                    &format!("Could not check for duplicate capture: {err}"),
+                ),
+            }
+        }
+
+        // The sink goes first so the capture can be pasted while slower files are written
+        let sink = match Sink::for_args(self.args) {
+            Sink::Stdout => self
+                .write_to_stdout(&selection_image)
+                .then_some(Sink::Stdout),
+            Sink::Clipboard | Sink::File => self.copy_to_clipboard(&selection_image, &mut files),
+        };
+        // The one file that is the capture itself, for `--copy-path`
+        let mut capture_file = files.first().cloned().filter(|_| sink == Some(Sink::File));
+
+        if let Some(count) = self.args.palette {
+            self.report_palette(&selection_image, count, &mut files);
+        }
+        if let Some(path) = &self.args.retina_pair {
+            self.save_retina_pair(&selection_image, path, &mut files);
+        }
+        if let Some(grid) = self.args.tiles {
+            self.save_tiles(&selection_image, grid, &mut files);
+        }
+        if let Some(dir) = &self.args.repo_assets {
+            capture_file = self
+                .save_repo_asset(&selection_image, dir, &mut files)
+                .or(capture_file);
+        }
+        if let Some(path) = &self.args.vectorize {
+            let written = project::output_path(self.args, path).and_then(|path| {
+                std::fs::write(&path, vectorize::to_svg(&selection_image))?;
+                Ok(path)
+            });
+            match written {
+                Ok(path) => {
+                    self.notifier.notify_file(
+                        Urgency::Info,
+                        &format!("Saved the SVG to {}", path.display()),
+                        &path,
+                    );
+                    files.push(path);
+                }
+                Err(err) => self.notifier.notify(
+                    Urgency::Error,
+                    &format!("Could not save SVG to {}: {err}", path.display()),
+                ),
+            }
+        }
+
+        if self.args.copy_path {
+            self.copy_path(capture_file.as_deref());
+        }
+        for file in &files {
+            self.notifier
+                .notify(Urgency::Debug, &format!("Wrote {}", file.display()));
+        }
+        if self.args.add_to_recent && !self.args.private && !files.is_empty() {
+            if let Err(err) = recent::add(&files) {
+                self.notifier.notify(
+                    Urgency::Warning,
+                    &format!("Could not add the saved files to the recent documents: {err:#}"),
+                );
+            }
+        }
+        if let Some(tags) = self.tags.filter(|_| !self.args.private) {
+            if let Err(err) = history::record_tags(self.args.project.as_deref(), tags, &files) {
+                self.notifier.notify(
+                    Urgency::Warning,
+                    &format!("Could not add the tags to the history: {err:#}"),
+                );
+            }
+        }
+        let Some(sink) = sink else {
+            return Delivered::Failed;
+        };
+        if self.args.json {
+            let input = match &self.args.command {
+                Some(Command::Edit { input }) => Some(input.as_path()),
+                _ => None,
+            };
+            let report = Report {
+                sink,
+                region,
+                requested: self.requested,
+                monitor: self.monitor,
+                window: self.window,
+                input,
+                files: &files,
+            };
+            if self.stdout_is_free() {
+                println!("{}", report.to_json());
+            } else {
+                eprintln!("{}", report.to_json());
+            }
+        }
+        match sink {
+            Sink::Stdout => Delivered::Stdout,
+            Sink::Clipboard => Delivered::Clipboard(selection_image),
+            Sink::File => Delivered::Saved,
+        }
+    }
+
+    fn save_tiles(&self, selection_image: &RgbaImage, grid: TileGrid, files: &mut Vec<PathBuf>) {
+        if selection_image.width() < grid.columns || selection_image.height() < grid.rows {
+            self.notifier.notify(
+                Urgency::Warning,
+                &format!(
+                    "Selection is too small to split into {}x{} tiles",
+                    grid.columns, grid.rows
+                ),
+            );
+            return;
+        }
+        let tiles = util::split_tiles(selection_image, grid.columns, grid.rows);
+        let prefix = self.args.tile_prefix.to_string_lossy();
+        let mut saved_tiles = Vec::new();
+        for (i, tile) in tiles.iter().enumerate() {
+            let (row, column) = (i as u32 / grid.columns + 1, i as u32 % grid.columns + 1);
+            let path = format!("{prefix}-r{row}-c{column}.png");
+            let saved = project::output_path(self.args, path.as_ref())
+                .map_err(anyhow::Error::from)
+                .and_then(|path| {
+                    self.save_png(tile, &path)?;
+                    Ok(path)
+                });
+            match saved {
+                Ok(path) => saved_tiles.push(path),
+                Err(err) => {
+                    self.notifier.notify(
+                        Urgency::Error,
+                        &format!("Could not save tile {path}: {err}"),
+                    );
+                    break;
+                }
+            }
+        }
+        // One notification for all of them, opening the directory they're in
+        if let Some(dir) = saved_tiles.first().and_then(|tile| tile.parent()) {
+            let dir = if dir.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                dir
+            };
+            self.notifier.notify_file(
+                Urgency::Info,
+                &format!("Saved {} tiles to {}", saved_tiles.len(), dir.display()),
+                dir,
+            );
+        }
+        files.extend(saved_tiles);
+    }
+
+    // The selection as it is and scaled down to logical pixels, named like web assets
+    fn save_retina_pair(&self, selection_image: &RgbaImage, path: &Path, files: &mut Vec<PathBuf>) {
+        let scale = self.monitor.map_or(RETINA_EDIT_SCALE, |m| m.scale_factor);
+        if scale <= 1.0 {
+            self.notifier.notify(
+                Urgency::Warning,
+                "The capture isn't from a HiDPI monitor, so its @1x version is the same size",
+            );
+        }
+        let (width, height) = selection_image.dimensions();
+        let scaled = |side: u32| ((side as f32 / scale.max(1.0)).round() as u32).max(1);
+        let one_x = image::imageops::resize(
+            selection_image,
+            scaled(width),
+            scaled(height),
+            FilterType::Lanczos3,
+        );
+        let mut saved = Vec::new();
+        for (image, suffix) in [(selection_image, "@2x"), (&one_x, "@1x")] {
+            let written = project::output_path(self.args, &retina_path(path, suffix))
+                .map_err(anyhow::Error::from)
+                .and_then(|path| {
+                    self.save_png(image, &path)?;
+                    Ok(path)
+                });
+            match written {
+                Ok(path) => saved.push(path),
+                Err(err) => {
+                    self.notifier.notify(
+                        Urgency::Error,
+                        &format!(
+                            "Could not save the {suffix} version of {}: {err}",
+                            path.display()
+                        ),
+                    );
+                    break;
+                }
+            }
+        }
+        if let [two_x, one_x] = saved.as_slice() {
+            self.notifier.notify_file(
+                Urgency::Info,
+                &format!("Saved {} and {}", two_x.display(), one_x.display()),
+                two_x,
+            );
+        }
+        files.extend(saved);
+    }
+
+    fn save_repo_asset(
+        &self,
+        selection_image: &RgbaImage,
+        dir: &Path,
+        files: &mut Vec<PathBuf>,
+    ) -> Option<PathBuf> {
+        let saved =
+            repo::asset_path(dir, self.args.project.as_deref()).and_then(|(path, relative)| {
+                self.save_png(selection_image, &path)?;
+                Ok((path, relative))
+            });
+        let (path, relative) = match saved {
+            Ok(saved) => saved,
+            Err(err) => {
+                self.notifier.notify(
+                    Urgency::Error,
+                    &format!("Could not save to the repository: {err}"),
+                );
+                return None;
+            }
+        };
+        if self.stdout_is_free() {
+            println!("![]({relative})");
+        } else {
+            self.notifier
+                .notify_file(Urgency::Info, &format!("Saved to {relative}"), &path);
+        }
+        files.push(path.clone());
+        Some(path)
+    }
+
+    // Replaces the image on the clipboard with where it was saved
+    fn copy_path(&self, saved: Option<&Path>) {
+        let Some(path) = saved else {
+            self.notifier.notify(
+                Urgency::Warning,
+                "The capture wasn't saved to a file, so --copy-path left the image on the clipboard",
+            );
+            return;
+        };
+        let path = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        match clipboard::copy_text(&path.to_string_lossy()) {
+            Ok(()) => self.notifier.notify(
+                Urgency::Info,
+                &format!("Copied the path {} to the clipboard", path.display()),
+            ),
+            Err(err) => self.notifier.notify(
+                Urgency::Error,
+                &format!("Could not copy the path to the clipboard: {err}"),
+            ),
+        }
+    }
+
+    fn report_palette(&self, selection_image: &RgbaImage, count: usize, files: &mut Vec<PathBuf>) {
+        let swatches = palette::extract(selection_image, count);
+        let colors: Vec<String> = swatches.iter().map(|s| s.hex()).collect();
+        if self.stdout_is_free() {
+            println!("{}", colors.join("\n"));
+        } else {
+            self.notifier
+                .notify(Urgency::Info, &format!("Palette: {}", colors.join(" ")));
+        }
+        if let Some(path) = &self.args.palette_swatch {
+            let saved = project::output_path(self.args, path)
+                .map_err(anyhow::Error::from)
+                .and_then(|path| {
+                    palette::swatch_image(&swatches).save(&path)?;
+                    Ok(path)
+                });
+            match saved {
+                Ok(path) => {
+                    self.notifier.notify_file(
+                        Urgency::Info,
+                        &format!("Saved the palette swatch to {}", path.display()),
+                        &path,
+                    );
+                    files.push(path);
+                }
+                Err(err) => self.notifier.notify(
+                    Urgency::Error,
+                    &format!("Could not save palette swatch to {}: {err}", path.display()),
+                ),
+            }
+        }
+    }
+
+    // Text meant for scripts goes to stdout unless the image is being written there, in
+    // which case it's moved aside so the image stays readable
+    fn stdout_is_free(&self) -> bool {
+        !matches!(Sink::for_args(self.args), Sink::Stdout)
+    }
+
+    fn write_to_stdout(&self, selection_image: &RgbaImage) -> bool {
+        let (width, height) = selection_image.dimensions();
+        let written = (|| -> anyhow::Result<usize> {
+            let png = self.stamp(selection_image, self.encode_png(selection_image)?, "stdout")?;
+            std::io::stdout().lock().write_all(&png)?;
+            Ok(png.len())
+        })();
+        match written {
+            Ok(bytes) => self.notifier.notify(
+                Urgency::Info,
+                &format!(
+                    "Wrote {width}x{height} selection to stdout ({}) in {:.1}s",
+                    util::human_size(bytes),
+                    self.started.elapsed().as_secs_f32()
+                ),
+            ),
+            Err(err) => {
+                self.notifier.notify_with_hint(
+                    Urgency::Error,
+                    &format!("Could not write to stdout: {err}"),
+                    "check that whatever cleave is piped into is still reading",
+                );
+                return false;
+            }
+        }
+        true
+    }
+
+    // Indexed when `--colors` reduced the palette, described by the `--prompt-tags`
+    fn encode_png(&self, selection_image: &RgbaImage) -> anyhow::Result<Vec<u8>> {
+        if let Some(quality) = self.args.quality {
+            self.notifier.notify(
+                Urgency::Debug,
+                &format!("Ignoring --quality {quality}, PNG is lossless"),
+            );
+        }
+        let mut png = Vec::new();
+        if self.args.colors.is_some() {
+            quantize::write_indexed_png(selection_image, self.args.png_compression, &mut png)?;
+        } else {
+            util::write_png(selection_image, self.args.png_compression, &mut png)?;
+        }
+        match self.tags {
+            Some(tags) => util::add_png_text(&png, "Description", tags),
+            None => Ok(png),
+        }
+    }
+
+    fn save_png(&self, img: &RgbaImage, path: &Path) -> anyhow::Result<()> {
+        let png = self.stamp(img, self.encode_png(img)?, &path.to_string_lossy())?;
+        std::fs::write(path, png)?;
+        Ok(())
+    }
+
+    // Adds the `--stamp-provenance` hash to an encoded PNG and prints it, like sha256sum
+    // would with `name`
+    fn stamp(&self, img: &RgbaImage, png: Vec<u8>, name: &str) -> anyhow::Result<Vec<u8>> {
+        if !self.args.stamp_provenance {
+            return Ok(png);
+        }
+        let captured = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_secs());
+        let mut fields = vec![
+            ("software", format!("cleave {}", env!("CARGO_PKG_VERSION"))),
+            ("captured", captured.to_string()),
+            ("size", format!("{}x{}", img.width(), img.height())),
+        ];
+        if let Some(monitor) = self.monitor {
+            fields.push(("monitor", monitor.name.clone()));
+        }
+        if let Some(Command::Edit { input }) = &self.args.command {
+            fields.push(("input", input.to_string_lossy().into_owned()));
+        }
+        let stamp = Stamp::new(img, &fields);
+        let png = stamp.embed(&png)?;
+        let line = format!("{}  {name}", stamp.hash);
+        if self.stdout_is_free() {
+            println!("{line}");
+        } else {
+            self.notifier.notify(Urgency::Info, &line);
+        }
+        Ok(png)
+    }
+
+    // The clipboard, or a file in `--fallback-dir` when it didn't work
+    fn copy_to_clipboard(
+        &self,
+        selection_image: &RgbaImage,
+        files: &mut Vec<PathBuf>,
+    ) -> Option<Sink> {
+        let (width, height) = selection_image.dimensions();
+        match clipboard::copy_image(selection_image, self.args) {
+            Ok(Copied::Full) => self.notifier.notify(
+                Urgency::Info,
+                &format!(
+                    "Copied {width}x{height} selection to clipboard in {:.1}s",
+                    self.started.elapsed().as_secs_f32()
+                ),
+            ),
+            Ok(Copied::Downscaled {
+                width: copied_width,
+                height: copied_height,
+            }) => match self.save_to_fallback_dir(selection_image) {
+                // Kept at full resolution next to the smaller copy
+                Ok(path) => {
+                    self.notifier.notify_file(
+                        Urgency::Warning,
+                        &format!(
+                            "Clipboard rejected the {width}x{height} selection, copied a {copied_width}x{copied_height} version instead and saved the full one to {}",
+                            path.display()
+                        ),
+                        &path,
+                    );
+                    files.push(path);
+                }
+                Err(err) => self.notifier.notify(
+                    Urgency::Warning,
+                    &format!(
+                        "Clipboard rejected the {width}x{height} selection, copied a {copied_width}x{copied_height} version instead, and the full one could not be saved: {err}"
+                    ),
+                ),
+            },
+            Err(err) => return self.save_fallback(selection_image, err, files),
+        }
+        Some(Sink::Clipboard)
+    }
+
+    // A new file in `--fallback-dir`, for captures the clipboard couldn't take as they are
+    fn save_to_fallback_dir(&self, selection_image: &RgbaImage) -> anyhow::Result<PathBuf> {
+        // Captures of a `--project` go in its directory rather than the pictures directory
+        let dir = match &self.args.fallback_dir {
+            Some(dir) => dir.clone(),
+            None if self.args.project.is_some() => PathBuf::from("."),
+            None => dirs::picture_dir().unwrap_or_else(|| PathBuf::from(".")),
+        };
+        let dir = project::output_path(self.args, &dir)
+            .with_context(|| format!("Could not create the directory for {}", dir.display()))?;
+        let saved = (|| -> anyhow::Result<PathBuf> {
+            std::fs::create_dir_all(&dir)?;
+            let path = util::new_capture_path(&dir);
+            self.save_png(selection_image, &path)?;
+            Ok(path)
+        })();
+        saved.with_context(|| format!("Could not save to {}", dir.display()))
+    }
+
+    fn save_fallback(
+        &self,
+        selection_image: &RgbaImage,
+        err: anyhow::Error,
+        files: &mut Vec<PathBuf>,
+    ) -> Option<Sink> {
+        let (width, height) = selection_image.dimensions();
+        match self.save_to_fallback_dir(selection_image) {
+            Ok(path) => {
+                self.notifier.notify_file(
+                    Urgency::Warning,
+                    &format!(
+                        "Could not copy to clipboard ({err}), saved the {width}x{height} selection to {} instead",
+                        path.display()
+                    ),
+                    &path,
+                );
+                files.push(path);
+                Some(Sink::File)
+            }
+            Err(save_err) => {
+                self.notifier.notify_with_hint(
+                    Urgency::Error,
+                    &format!("Could not copy to clipboard ({err}): {save_err:#}"),
+                    "pass --stdout to write the capture to stdout instead",
+                );
+                None
+            }
+        }
+    }
+}
+
+// `shot.png` becomes `shot@2x.png`, files without an extension get `.png`
+fn retina_path(path: &Path, suffix: &str) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = path
+        .extension()
+        .map_or("png".into(), |e| e.to_string_lossy());
+    path.with_file_name(format!("{stem}{suffix}.{extension}"))
+}
+
+/// Checks that `img` is still on the clipboard now that cleave has let go of it, and
+/// copies it again if a clipboard manager missed the hand over. Returns whether it stuck
+pub fn verify_clipboard(args: &Args, img: &RgbaImage) -> bool {
+    let notifier = args.notify.notifier(args.verbosity());
+    let mut last_error = None;
+    for attempt in 0..CLIPBOARD_VERIFY_ATTEMPTS {
+        std::thread::sleep(CLIPBOARD_VERIFY_DELAY);
+        match clipboard::holds_image(img, args.clipboard_max_dimension) {
+            Ok(true) if attempt == 0 => return true,
+            Ok(true) => {
+                notifier.notify(
+                    Urgency::Warning,
+                    "Capture was lost from the clipboard and had to be copied again",
+                );
+                return true;
+            }
+            Ok(false) => {}
+            Err(err) => last_error = Some(err),
+        }
+        if let Err(err) = clipboard::copy_image(img, args) {
+            last_error = Some(err);
+        }
+    }
+    let reason = last_error.map_or(String::new(), |err| format!(": {err}"));
+    notifier.notify_with_hint(
+        Urgency::Error,
+        &format!("Capture didn't stay on the clipboard{reason}"),
+        "pass --stdout to write the capture to stdout instead",
+    );
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::retina_path;
+
+    #[test]
+    fn retina_suffix_goes_before_the_extension() {
+        assert_eq!(
+            retina_path(Path::new("img/shot.png"), "@2x"),
+            PathBuf::from("img/shot@2x.png")
+        );
+        assert_eq!(
+            retina_path(Path::new("shot"), "@1x"),
+            PathBuf::from("shot@1x.png")
+        );
+    }
+}
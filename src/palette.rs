@@ -0,0 +1,125 @@
+use anyhow::Context;
+use image::{Rgb, Rgba, RgbaImage};
+
+// Enough pixels for a stable palette, larger selections are sampled evenly
+const MAX_SAMPLES: usize = 65_536;
+const SWATCH_SIZE: u32 = 32;
+
+/// A dominant color and how many of the sampled pixels it stands for
+#[derive(Clone, Copy, Debug)]
+pub struct Swatch {
+    pub color: Rgb<u8>,
+    pub population: usize,
+}
+
+impl Swatch {
+    pub fn hex(&self) -> String {
+        hex(self.color)
+    }
+}
+
+/// `#rrggbb`
+pub fn hex(color: Rgb<u8>) -> String {
+    let [r, g, b] = color.0;
+    format!("#{r:02x}{g:02x}{b:02x}")
+}
+
+/// Reads `#rgb`, `#rrggbb` or `#rrggbbaa`, the `#` being optional
+pub fn parse_hex(text: &str) -> anyhow::Result<Rgba<u8>> {
+    let digits = text.trim().trim_start_matches('#');
+    let expanded: String = match digits.len() {
+        3 => digits
+            .chars()
+            .flat_map(|c| [c, c])
+            .chain("ff".chars())
+            .collect(),
+        6 => format!("{digits}ff"),
+        8 => digits.to_string(),
+        _ => anyhow::bail!("Expected a color like #ff0000, got {text:?}"),
+    };
+    let value = u32::from_str_radix(&expanded, 16)
+        .with_context(|| format!("Expected a color like #ff0000, got {text:?}"))?;
+    Ok(Rgba(value.to_be_bytes()))
+}
+
+/// Up to `count` dominant colors of `img` by median cut, most common first.
+/// Transparent pixels, such as rounded corners, are ignored
+pub fn extract(img: &RgbaImage, count: usize) -> Vec<Swatch> {
+    let opaque = img.pixels().filter(|p| p[3] > 0).count();
+    let step = opaque.div_ceil(MAX_SAMPLES).max(1);
+    let pixels: Vec<[u8; 3]> = img
+        .pixels()
+        .filter(|p| p[3] > 0)
+        .step_by(step)
+        .map(|p| [p[0], p[1], p[2]])
+        .collect();
+    if pixels.is_empty() || count == 0 {
+        return Vec::new();
+    }
+
+    let mut boxes = vec![pixels];
+    while boxes.len() < count {
+        // Split the box with the widest channel range, at the median of that channel
+        let Some((index, channel, _)) = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| b.len() > 1)
+            .map(|(i, b)| {
+                let (channel, range) = widest_channel(b);
+                (i, channel, range)
+            })
+            .filter(|(_, _, range)| *range > 0)
+            .max_by_key(|(_, _, range)| *range)
+        else {
+            break;
+        };
+        let mut pixels = boxes.swap_remove(index);
+        pixels.sort_unstable_by_key(|p| p[channel]);
+        let upper = pixels.split_off(pixels.len() / 2);
+        boxes.push(pixels);
+        boxes.push(upper);
+    }
+
+    let mut swatches: Vec<Swatch> = boxes.iter().map(|b| average(b)).collect();
+    swatches.sort_by_key(|s| std::cmp::Reverse(s.population));
+    swatches
+}
+
+fn widest_channel(pixels: &[[u8; 3]]) -> (usize, u8) {
+    (0..3)
+        .map(|c| {
+            let min = pixels.iter().map(|p| p[c]).min().unwrap_or(0);
+            let max = pixels.iter().map(|p| p[c]).max().unwrap_or(0);
+            (c, max - min)
+        })
+        .max_by_key(|(_, range)| *range)
+        .unwrap_or((0, 0))
+}
+
+fn average(pixels: &[[u8; 3]]) -> Swatch {
+    let mut sum = [0usize; 3];
+    for p in pixels {
+        for (total, value) in sum.iter_mut().zip(p) {
+            *total += *value as usize;
+        }
+    }
+    let len = pixels.len().max(1);
+    Swatch {
+        color: Rgb(sum.map(|s| (s / len) as u8)),
+        population: pixels.len(),
+    }
+}
+
+/// A row of squares, one per swatch in order
+pub fn swatch_image(swatches: &[Swatch]) -> RgbaImage {
+    let width = SWATCH_SIZE * swatches.len().max(1) as u32;
+    RgbaImage::from_fn(width, SWATCH_SIZE, |x, _| {
+        match swatches.get((x / SWATCH_SIZE) as usize) {
+            Some(swatch) => {
+                let [r, g, b] = swatch.color.0;
+                image::Rgba([r, g, b, 255])
+            }
+            None => image::Rgba([0, 0, 0, 0]),
+        }
+    })
+}
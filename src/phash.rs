@@ -0,0 +1,82 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use image::{imageops::FilterType, RgbaImage};
+
+use crate::project;
+
+// Maximum number of differing bits for two captures to count as duplicates
+const DUPLICATE_THRESHOLD: u32 = 5;
+
+// 64-bit difference hash: compares neighbouring pixels of a 9x8 grayscale thumbnail
+pub fn dhash(img: &RgbaImage) -> u64 {
+    let gray = image::imageops::grayscale(img);
+    let small = image::imageops::resize(&gray, 9, 8, FilterType::Triangle);
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            let brighter = small.get_pixel(x, y)[0] < small.get_pixel(x + 1, y)[0];
+            hash = (hash << 1) | brighter as u64;
+        }
+    }
+    hash
+}
+
+// 64-bit FNV-1a over the size and every pixel, for telling any change apart
+pub fn content_hash(img: &RgbaImage) -> u64 {
+    let (width, height) = img.dimensions();
+    let mut hash = 0xcbf2_9ce4_8422_2325u64;
+    for byte in [width.to_le_bytes(), height.to_le_bytes()]
+        .iter()
+        .flatten()
+        .chain(img.as_raw())
+    {
+        hash = (hash ^ *byte as u64).wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+/// Whether `img` differs from the capture whose hash is in `baseline`, which
+/// counts as changed when there isn't one yet
+pub fn differs_from_baseline(img: &RgbaImage, baseline: &Path) -> bool {
+    let previous = std::fs::read_to_string(baseline)
+        .ok()
+        .and_then(|s| u64::from_str_radix(s.trim(), 16).ok());
+    previous != Some(content_hash(img))
+}
+
+/// Makes `img` the capture later ones are compared with by `differs_from_baseline`
+pub fn save_baseline(img: &RgbaImage, baseline: &Path) -> anyhow::Result<()> {
+    if let Some(parent) = baseline.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(baseline, format!("{:016x}", content_hash(img)))?;
+    Ok(())
+}
+
+fn last_hash_path(project: Option<&str>) -> Option<PathBuf> {
+    Some(project::state_dir(project)?.join("last_capture.hash"))
+}
+
+/// Returns whether `img` is nearly identical to the previous capture,
+/// recording its hash for the next comparison when `record` is set
+pub fn check_duplicate(
+    img: &RgbaImage,
+    project: Option<&str>,
+    record: bool,
+) -> anyhow::Result<bool> {
+    let hash = dhash(img);
+    let path = last_hash_path(project).context("Could not find cache directory")?;
+    let previous = std::fs::read_to_string(&path)
+        .ok()
+        .and_then(|s| u64::from_str_radix(s.trim(), 16).ok());
+
+    if record {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&path, format!("{hash:016x}"))?;
+    }
+
+    Ok(previous.is_some_and(|prev| (prev ^ hash).count_ones() <= DUPLICATE_THRESHOLD))
+}
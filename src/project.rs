@@ -0,0 +1,33 @@
+use std::path::{Path, PathBuf};
+
+use crate::args::Args;
+
+/// Where cleave keeps what it remembers between captures, separately for each `--project`
+pub fn state_dir(project: Option<&str>) -> Option<PathBuf> {
+    let dir = dirs::cache_dir()?.join("cleave");
+    Some(match project {
+        Some(project) => dir.join("projects").join(project),
+        None => dir,
+    })
+}
+
+/// Where to write an output file given as `path`. Relative paths go in the
+/// `--project` directory, which is created if it doesn't exist yet
+pub fn output_path(args: &Args, path: &Path) -> std::io::Result<PathBuf> {
+    match &args.project {
+        Some(project) if path.is_relative() => {
+            std::fs::create_dir_all(project)?;
+            Ok(Path::new(project).join(path))
+        }
+        _ => Ok(path.to_path_buf()),
+    }
+}
+
+/// Project names become directory names, so they can't contain or be a path
+pub fn parse_name(name: &str) -> anyhow::Result<String> {
+    anyhow::ensure!(
+        !name.is_empty() && name != "." && name != ".." && !name.contains(['/', '\\']),
+        "Project names can't be empty or contain path separators"
+    );
+    Ok(name.to_string())
+}
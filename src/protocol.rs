@@ -0,0 +1,156 @@
+use std::path::PathBuf;
+
+use anyhow::Context;
+
+use crate::{args::Args, util};
+
+const SCHEME: &str = "cleave:";
+
+/// Sets up the capture a `cleave://capture?region=...&sink=clipboard` URL asks for.
+///
+/// `region` captures that part of the screen without the overlay, like `--region`, and
+/// `fullscreen=true` the whole screen. Without either the overlay opens as usual. Only
+/// the clipboard can be the `sink`, nothing reads the output of a URL handler
+pub fn apply(url: &str, args: &mut Args) -> anyhow::Result<()> {
+    let rest = url
+        .get(..SCHEME.len())
+        .filter(|scheme| scheme.eq_ignore_ascii_case(SCHEME))
+        .map(|_| &url[SCHEME.len()..])
+        .with_context(|| format!("{url:?} isn't a cleave:// URL"))?;
+    let rest = rest.trim_start_matches('/');
+    let (action, query) = rest.split_once('?').unwrap_or((rest, ""));
+    anyhow::ensure!(
+        action.trim_end_matches('/') == "capture",
+        "Unknown action {action:?} in {url:?}, only capture is supported"
+    );
+
+    for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        let value = util::percent_decode(value);
+        match util::percent_decode(key).as_str() {
+            "region" => args.region = Some(value.parse()?),
+            "fullscreen" => {
+                args.fullscreen = value
+                    .parse()
+                    .with_context(|| format!("fullscreen must be true or false, got {value:?}"))?
+            }
+            "sink" => anyhow::ensure!(
+                value == "clipboard",
+                "Unknown sink {value:?}, URLs can only capture to the clipboard"
+            ),
+            key => anyhow::bail!("Unknown parameter {key:?} in {url:?}"),
+        }
+    }
+    Ok(())
+}
+
+/// Registers cleave as the handler for cleave:// URLs with a desktop entry, returning
+/// where it was written. Only the freedesktop.org mechanism GTK and KDE use is supported
+#[cfg(all(unix, not(target_os = "macos")))]
+pub fn install_desktop_entry() -> anyhow::Result<PathBuf> {
+    let exe = std::env::current_exe().context("Could not find the cleave executable")?;
+    let dir = dirs::data_dir()
+        .context("No data directory for desktop entries")?
+        .join("applications");
+    std::fs::create_dir_all(&dir)?;
+    let entry = dir.join("cleave.desktop");
+    std::fs::write(&entry, desktop_entry(&exe.to_string_lossy()))
+        .with_context(|| format!("Could not write {}", entry.display()))?;
+
+    let status = std::process::Command::new("xdg-mime")
+        .args(["default", "cleave.desktop", "x-scheme-handler/cleave"])
+        .status()
+        .context("Could not run xdg-mime, is xdg-utils installed?")?;
+    anyhow::ensure!(status.success(), "xdg-mime failed with {status}");
+    // Only some desktops cache the entries, it's fine for this to be missing
+    let _ = std::process::Command::new("update-desktop-database")
+        .arg(&dir)
+        .status();
+    Ok(entry)
+}
+
+#[cfg(not(all(unix, not(target_os = "macos"))))]
+pub fn install_desktop_entry() -> anyhow::Result<PathBuf> {
+    anyhow::bail!("Installing the URL handler is only supported on Linux and BSD")
+}
+
+// Hidden from application menus, it only handles URLs
+#[cfg(all(unix, not(target_os = "macos")))]
+fn desktop_entry(exe: &str) -> String {
+    // The Exec key quotes arguments like a shell, without expanding anything
+    let mut quoted = String::from("\"");
+    for c in exe.chars() {
+        if matches!(c, '"' | '`' | '$' | '\\') {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    // Backslashes are escaped once more for the desktop entry's own string syntax
+    let exec = quoted.replace('\\', "\\\\");
+    format!(
+        "[Desktop Entry]\n\
+         Type=Application\n\
+         Name=Cleave\n\
+         Comment=Capture the screen for cleave:// links\n\
+         Exec={exec} url %u\n\
+         Terminal=false\n\
+         NoDisplay=true\n\
+         MimeType=x-scheme-handler/cleave;\n"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::apply;
+    use crate::args::{Args, Region};
+
+    fn applied(url: &str) -> anyhow::Result<Args> {
+        let mut args = Args::parse_from(["cleave"]);
+        apply(url, &mut args)?;
+        Ok(args)
+    }
+
+    #[test]
+    fn region_and_sink_are_read_from_the_query() {
+        let args = applied("cleave://capture?region=640x480%2B100%2B50&sink=clipboard").unwrap();
+        let expected = Region {
+            x: 100,
+            y: 50,
+            width: 640,
+            height: 480,
+        };
+        assert_eq!(args.region, Some(expected));
+        // Unescaped plus signs are left alone, they're part of the geometry
+        let args = applied("CLEAVE://capture/?region=640x480+100+50").unwrap();
+        assert_eq!(args.region, Some(expected));
+    }
+
+    #[test]
+    fn bare_capture_opens_the_overlay() {
+        let args = applied("cleave://capture").unwrap();
+        assert!(args.region.is_none() && !args.fullscreen);
+        assert!(
+            applied("cleave://capture?fullscreen=true")
+                .unwrap()
+                .fullscreen
+        );
+    }
+
+    #[test]
+    fn anything_else_is_rejected() {
+        assert!(applied("https://capture?region=1x1+0+0").is_err());
+        assert!(applied("cleave://delete").is_err());
+        assert!(applied("cleave://capture?sink=stdout").is_err());
+        assert!(applied("cleave://capture?output=/etc/passwd").is_err());
+    }
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    #[test]
+    fn desktop_entry_quotes_the_executable() {
+        let entry = super::desktop_entry("/opt/my apps/cle\"ave");
+        assert!(entry.contains("Exec=\"/opt/my apps/cle\\\\\"ave\" url %u\n"));
+    }
+}
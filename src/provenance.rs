@@ -0,0 +1,119 @@
+use std::{fmt::Write, io::Cursor, path::Path};
+
+use anyhow::Context;
+use image::RgbaImage;
+use sha2::{Digest, Sha256};
+
+use crate::util;
+
+// Keyword of the PNG text chunk holding the stamp
+const KEYWORD: &str = "cleave provenance";
+
+/// A SHA-256 of an image's pixels and what cleave knew about the capture, written into
+/// the PNG with `--stamp-provenance` and checked by `cleave verify`
+pub struct Stamp {
+    pub hash: String,
+    // `key=value` lines, hashed after the pixels
+    pub metadata: String,
+}
+
+impl Stamp {
+    pub fn new(img: &RgbaImage, fields: &[(&str, String)]) -> Self {
+        let mut metadata = String::new();
+        for (key, value) in fields {
+            let _ = writeln!(metadata, "{key}={}", value.replace('\n', " "));
+        }
+        Self {
+            hash: hash(img, &metadata),
+            metadata,
+        }
+    }
+
+    /// Adds the stamp to an encoded PNG, just after its header
+    pub fn embed(&self, png: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let text = format!("sha256={}\n{}", self.hash, self.metadata);
+        util::add_png_text(png, KEYWORD, &text)
+    }
+}
+
+/// Reads the stamp of a PNG and checks it against the pixels, failing if there's no
+/// stamp or anything changed since it was written
+pub fn verify(path: &Path) -> anyhow::Result<Stamp> {
+    let png = std::fs::read(path).with_context(|| format!("Could not read {}", path.display()))?;
+    let reader = png::Decoder::new(Cursor::new(&png))
+        .read_info()
+        .with_context(|| format!("{} isn't a PNG", path.display()))?;
+    let text = reader
+        .info()
+        .utf8_text
+        .iter()
+        .find(|chunk| chunk.keyword == KEYWORD)
+        .with_context(|| format!("{} has no provenance stamp", path.display()))?
+        .get_text()?;
+    let (hash_line, metadata) = text.split_once('\n').unwrap_or((&text, ""));
+    let stamped = hash_line
+        .strip_prefix("sha256=")
+        .context("The provenance stamp is malformed")?;
+
+    let img = image::load_from_memory(&png)
+        .with_context(|| format!("{} is damaged", path.display()))?
+        .to_rgba8();
+    let actual = hash(&img, metadata);
+    anyhow::ensure!(
+        actual == stamped,
+        "{} was modified after it was stamped, it hashes to {actual} instead of {stamped}",
+        path.display()
+    );
+    Ok(Stamp {
+        hash: actual,
+        metadata: metadata.to_string(),
+    })
+}
+
+fn hash(img: &RgbaImage, metadata: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(img.as_raw());
+    hasher.update(metadata.as_bytes());
+    util::hex(&hasher.finalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{Rgba, RgbaImage};
+
+    use super::{verify, Stamp};
+    use crate::util;
+
+    fn stamped_png(img: &RgbaImage) -> Vec<u8> {
+        let mut png = Vec::new();
+        util::write_png(img, Default::default(), &mut png).unwrap();
+        let stamp = Stamp::new(img, &[("captured", "1700000000".into())]);
+        stamp.embed(&png).unwrap()
+    }
+
+    #[test]
+    fn stamped_files_verify_until_changed() {
+        let dir = std::env::temp_dir().join(format!("cleave-provenance-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("capture.png");
+
+        let mut img = RgbaImage::from_pixel(4, 3, Rgba([10, 20, 30, 255]));
+        std::fs::write(&path, stamped_png(&img)).unwrap();
+        let stamp = verify(&path).unwrap();
+        assert_eq!(stamp.metadata, "captured=1700000000\n");
+        assert_eq!(stamp.hash.len(), 64);
+
+        // Same stamp, different pixels
+        let stamp = Stamp::new(&img, &[("captured", "1700000000".into())]);
+        img.put_pixel(0, 0, Rgba([11, 20, 30, 255]));
+        let mut png = Vec::new();
+        util::write_png(&img, Default::default(), &mut png).unwrap();
+        std::fs::write(&path, stamp.embed(&png).unwrap()).unwrap();
+        assert!(verify(&path).is_err());
+
+        // No stamp at all
+        std::fs::write(&path, &png).unwrap();
+        assert!(verify(&path).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,177 @@
+use std::path::PathBuf;
+#[cfg(all(unix, not(target_os = "macos")))]
+use std::{
+    fmt::Write as _,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+#[cfg(all(unix, not(target_os = "macos")))]
+use anyhow::Context;
+
+#[cfg(all(unix, not(target_os = "macos")))]
+use crate::util;
+
+// Written when the desktop hasn't created the list yet
+#[cfg(all(unix, not(target_os = "macos")))]
+const EMPTY_XBEL: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+<xbel version=\"1.0\"\n      \
+xmlns:bookmark=\"http://www.freedesktop.org/standards/desktop-bookmarks\"\n      \
+xmlns:mime=\"http://www.freedesktop.org/standards/shared-mime-info\"\n>\n</xbel>\n";
+
+/// Adds `files` to the desktop's recently used documents for `--add-to-recent`, so file
+/// pickers list them under "Recent". Only the freedesktop.org list GTK and KDE read is
+/// supported
+#[cfg(all(unix, not(target_os = "macos")))]
+pub fn add(files: &[PathBuf]) -> anyhow::Result<()> {
+    let list = dirs::data_dir()
+        .context("No data directory for the recently used list")?
+        .join("recently-used.xbel");
+    let xbel = match std::fs::read_to_string(&list) {
+        Ok(xbel) => xbel,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => EMPTY_XBEL.to_string(),
+        Err(err) => return Err(err).context("Could not read the recently used list"),
+    };
+    let bookmarks = files
+        .iter()
+        .map(|file| Ok((file_uri(&std::fs::canonicalize(file)?), mime_type(file))))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let xbel = with_bookmarks(xbel, &bookmarks, &timestamp())?;
+    // Replaced in one go, file pickers may be reading it
+    let temporary = list.with_extension("xbel.cleave");
+    std::fs::create_dir_all(list.parent().context("The list is in a directory")?)?;
+    std::fs::write(&temporary, xbel)?;
+    std::fs::rename(&temporary, &list)?;
+    Ok(())
+}
+
+// `xbel` with a bookmark for each `(href, mime type)` it doesn't list yet
+#[cfg(all(unix, not(target_os = "macos")))]
+fn with_bookmarks(
+    mut xbel: String,
+    bookmarks: &[(String, &str)],
+    now: &str,
+) -> anyhow::Result<String> {
+    let end = xbel
+        .rfind("</xbel>")
+        .context("The recently used list is not an XBEL file")?;
+    let mut added = String::new();
+    for (href, mime_type) in bookmarks {
+        // Already listed files keep their entry, the desktop tracks their use itself
+        if xbel.contains(&format!("href=\"{href}\"")) {
+            continue;
+        }
+        let _ = write!(
+            added,
+            "  <bookmark href=\"{href}\" added=\"{now}\" modified=\"{now}\" visited=\"{now}\">\n    \
+             <info>\n      <metadata owner=\"http://freedesktop.org\">\n        \
+             <mime:mime-type type=\"{mime_type}\"/>\n        <bookmark:applications>\n          \
+             <bookmark:application name=\"cleave\" exec=\"&apos;cleave&apos;\" modified=\"{now}\" count=\"1\"/>\n        \
+             </bookmark:applications>\n      </metadata>\n    </info>\n  </bookmark>\n"
+        );
+    }
+    xbel.insert_str(end, &added);
+    Ok(xbel)
+}
+
+#[cfg(not(all(unix, not(target_os = "macos"))))]
+pub fn add(_: &[PathBuf]) -> anyhow::Result<()> {
+    anyhow::bail!("Adding captures to the recent documents is only supported on Linux and BSD")
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn mime_type(file: &Path) -> &'static str {
+    match file.extension().and_then(|e| e.to_str()) {
+        Some("svg") => "image/svg+xml",
+        Some("gif") => "image/gif",
+        _ => "image/png",
+    }
+}
+
+// Everything outside the unreserved characters is escaped, which also keeps the URI
+// safe to put in an XML attribute as it is
+#[cfg(all(unix, not(target_os = "macos")))]
+fn file_uri(path: &Path) -> String {
+    let mut uri = String::from("file://");
+    for byte in path.to_string_lossy().bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                uri.push(byte as char)
+            }
+            _ => {
+                let _ = write!(uri, "%{byte:02X}");
+            }
+        }
+    }
+    uri
+}
+
+// Now as an ISO 8601 date and time in UTC, like 2024-05-01T09:30:00Z
+#[cfg(all(unix, not(target_os = "macos")))]
+fn timestamp() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs());
+    util::utc_timestamp(secs)
+}
+
+#[cfg(all(test, unix, not(target_os = "macos")))]
+mod tests {
+    use std::path::Path;
+
+    use super::{file_uri, with_bookmarks, EMPTY_XBEL};
+
+    const NOW: &str = "2024-05-01T09:30:00Z";
+
+    #[test]
+    fn uris_escape_spaces_percents_and_non_ascii() {
+        assert_eq!(
+            file_uri(Path::new("/home/me/My Shots/100% é.png")),
+            "file:///home/me/My%20Shots/100%25%20%C3%A9.png"
+        );
+        assert_eq!(
+            file_uri(Path::new("/tmp/a&b\"<c>.png")),
+            "file:///tmp/a%26b%22%3Cc%3E.png"
+        );
+        assert_eq!(
+            file_uri(Path::new("/tmp/plain-name_1.png")),
+            "file:///tmp/plain-name_1.png"
+        );
+    }
+
+    #[test]
+    fn bookmarks_are_added_to_an_empty_list() {
+        let bookmarks = [("file:///tmp/a.png".to_string(), "image/png")];
+        let xbel = with_bookmarks(EMPTY_XBEL.to_string(), &bookmarks, NOW).unwrap();
+        assert!(xbel.starts_with("<?xml"));
+        assert!(xbel.trim_end().ends_with("</xbel>"));
+        assert!(xbel.contains(&format!(
+            "<bookmark href=\"file:///tmp/a.png\" added=\"{NOW}\" modified=\"{NOW}\" visited=\"{NOW}\">"
+        )));
+        assert!(xbel.contains("<mime:mime-type type=\"image/png\"/>"));
+        assert_eq!(xbel.matches("<bookmark ").count(), 1);
+    }
+
+    #[test]
+    fn existing_lists_keep_their_bookmarks() {
+        let existing = "<?xml version=\"1.0\"?>\n<xbel version=\"1.0\">\n  \
+                        <bookmark href=\"file:///tmp/old.txt\"/>\n  \
+                        <bookmark href=\"file:///tmp/a.png\"/>\n</xbel>\n";
+        let bookmarks = [
+            ("file:///tmp/a.png".to_string(), "image/png"),
+            ("file:///tmp/b.gif".to_string(), "image/gif"),
+        ];
+        let xbel = with_bookmarks(existing.to_string(), &bookmarks, NOW).unwrap();
+        assert!(xbel.starts_with(existing.trim_end_matches("</xbel>\n")));
+        // Only the file that wasn't listed is added, before the end of the list
+        assert_eq!(xbel.matches("file:///tmp/a.png").count(), 1);
+        let added = xbel.find("file:///tmp/b.gif").unwrap();
+        assert!(added < xbel.rfind("</xbel>").unwrap());
+        assert!(xbel.contains("<mime:mime-type type=\"image/gif\"/>"));
+    }
+
+    #[test]
+    fn other_files_are_refused() {
+        assert!(with_bookmarks("<html></html>".into(), &[], NOW).is_err());
+    }
+}
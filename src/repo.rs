@@ -0,0 +1,51 @@
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::Context;
+
+use crate::util;
+
+/// Top of the git repository the current directory is in
+pub fn git_root() -> anyhow::Result<PathBuf> {
+    let cwd = std::env::current_dir()?;
+    // `.git` is a file rather than a directory in worktrees and submodules
+    cwd.ancestors()
+        .find(|dir| dir.join(".git").exists())
+        .map(Path::to_path_buf)
+        .with_context(|| format!("{} is not inside a git repository", cwd.display()))
+}
+
+/// A new file for a capture in `dir` under the repository's root, which is
+/// created if needed, along with its path from the root as written in markdown.
+/// Captures of a `--project` go in a subdirectory of that name
+pub fn asset_path(dir: &Path, project: Option<&str>) -> anyhow::Result<(PathBuf, String)> {
+    let dir = match project {
+        Some(project) => dir.join(project),
+        None => dir.to_path_buf(),
+    };
+    let assets = git_root()?.join(&dir);
+    std::fs::create_dir_all(&assets)?;
+    let path = util::new_capture_path(&assets);
+    let name = path
+        .file_name()
+        .context("Capture paths end in a file name")?;
+    let relative: Vec<_> = dir
+        .join(name)
+        .components()
+        .filter_map(|c| match c {
+            Component::Normal(part) => Some(part.to_string_lossy().into_owned()),
+            _ => None,
+        })
+        .collect();
+    Ok((path, relative.join("/")))
+}
+
+/// Asset directories have to stay inside the repository
+pub fn parse_assets_dir(dir: &str) -> anyhow::Result<PathBuf> {
+    let path = PathBuf::from(dir);
+    anyhow::ensure!(
+        path.components()
+            .all(|c| matches!(c, Component::Normal(_) | Component::CurDir)),
+        "Asset directories are relative to the repository root and can't contain .., got {dir:?}"
+    );
+    Ok(path)
+}
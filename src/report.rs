@@ -0,0 +1,132 @@
+use std::{
+    fmt::Write,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    args::Region,
+    util::{
+        self,
+        capture::{MonitorInfo, WindowInfo},
+        Sink,
+    },
+};
+
+/// What `--json` prints about a finished capture
+pub struct Report<'a> {
+    pub sink: Sink,
+    // Relative to the window for `--window` captures, the monitor otherwise
+    pub region: Region,
+    // What `--region` asked for, when it didn't fit the capture
+    pub requested: Option<Region>,
+    pub monitor: Option<&'a MonitorInfo>,
+    pub window: Option<&'a WindowInfo>,
+    // Image given to `cleave edit`
+    pub input: Option<&'a Path>,
+    // Everything written to disk, the capture itself too when the sink is a file
+    pub files: &'a [PathBuf],
+}
+
+impl Report<'_> {
+    /// A single line JSON object, for scripts
+    pub fn to_json(&self) -> String {
+        let sink = match self.sink {
+            Sink::Clipboard => "clipboard",
+            Sink::Stdout => "stdout",
+            Sink::File => "file",
+        };
+        let Region {
+            x,
+            y,
+            width,
+            height,
+        } = self.region;
+        let (center_x, center_y) = self.region.center();
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_secs());
+
+        let mut json = format!(
+            "{{\"sink\":\"{sink}\",\"format\":\"png\",\"timestamp\":{timestamp},\
+             \"region\":{{\"x\":{x},\"y\":{y},\"width\":{width},\"height\":{height},\
+             \"center_x\":{center_x},\"center_y\":{center_y}}},\"monitor\":"
+        );
+        match self.monitor {
+            Some(monitor) => {
+                let _ = write!(
+                    json,
+                    "{{\"id\":{},\"name\":{},\"x\":{},\"y\":{},\"scale_factor\":{}}}",
+                    monitor.id,
+                    util::quote(&monitor.name),
+                    monitor.position.x,
+                    monitor.position.y,
+                    monitor.scale_factor
+                );
+            }
+            None => json.push_str("null"),
+        }
+        json.push_str(",\"window\":");
+        match self.window {
+            Some(window) => {
+                let _ = write!(
+                    json,
+                    "{{\"id\":{},\"title\":{},\"app\":{},\"x\":{},\"y\":{}}}",
+                    window.id,
+                    util::quote(&window.title),
+                    util::quote(&window.app_name),
+                    window.position.x,
+                    window.position.y
+                );
+            }
+            None => json.push_str("null"),
+        }
+        // The region on the desktop, for tools that click on what was captured
+        json.push_str(",\"screen_region\":");
+        let origin = self
+            .window
+            .map(|window| window.position)
+            .or(self.monitor.map(|monitor| monitor.position));
+        match origin {
+            Some(origin) => {
+                let _ = write!(
+                    json,
+                    "{{\"x\":{},\"y\":{},\"width\":{width},\"height\":{height},\
+                     \"center_x\":{},\"center_y\":{}}}",
+                    origin.x + x as i32,
+                    origin.y + y as i32,
+                    origin.x as f64 + center_x,
+                    origin.y as f64 + center_y
+                );
+            }
+            None => json.push_str("null"),
+        }
+        json.push_str(",\"requested_region\":");
+        match self.requested {
+            Some(Region {
+                x,
+                y,
+                width,
+                height,
+            }) => {
+                let _ = write!(
+                    json,
+                    "{{\"x\":{x},\"y\":{y},\"width\":{width},\"height\":{height}}}"
+                );
+            }
+            None => json.push_str("null"),
+        }
+        json.push_str(",\"input\":");
+        match self.input {
+            Some(input) => json.push_str(&util::quote(&input.to_string_lossy())),
+            None => json.push_str("null"),
+        }
+        let files: Vec<String> = self
+            .files
+            .iter()
+            .map(|file| util::quote(&file.to_string_lossy()))
+            .collect();
+        let _ = write!(json, ",\"files\":[{}]}}", files.join(","));
+        json
+    }
+}
@@ -0,0 +1,241 @@
+use std::{io::Read, net::SocketAddr, path::Path};
+
+use anyhow::Context;
+use image::GenericImageView;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::{
+    args::{Args, MonitorTarget, Region},
+    error::{CleaveError, OrReport},
+    notify::Urgency,
+    util::{self, capture},
+};
+
+// Request bodies are a few fields of JSON, anything bigger isn't one
+const MAX_BODY: u64 = 64 * 1024;
+
+// Why a request got no capture, with the status it's answered with
+struct Refused(u16, String);
+
+impl Refused {
+    fn bad_request(err: anyhow::Error) -> Self {
+        Self(400, format!("{err:#}"))
+    }
+}
+
+/// Runs `cleave serve`: answers `POST /capture` on `listen` with a PNG of this machine's
+/// screen, for requests carrying the token kept in `token_file`
+pub fn run(args: &Args, listen: SocketAddr, token_file: &Path) -> Result<(), CleaveError> {
+    let notifier = args.notify.notifier(args.verbosity());
+    let notifier = notifier.as_ref();
+    let token = std::fs::read_to_string(token_file)
+        .with_context(|| format!("Could not read the token from {}", token_file.display()))
+        .or_report(notifier, CleaveError::BadArgs)?;
+    let token = token.trim();
+    if token.is_empty() {
+        return Err(anyhow::anyhow!("{} is empty", token_file.display()))
+            .or_report(notifier, CleaveError::BadArgs);
+    }
+    let server = Server::http(listen)
+        .map_err(|err| anyhow::anyhow!("Could not listen on {listen}: {err}"))
+        .or_report(notifier, CleaveError::Other)?;
+    if !listen.ip().is_loopback() {
+        notifier.notify(
+            Urgency::Warning,
+            &format!(
+                "Serving captures of this screen on {listen} without encryption, anyone who can see the traffic can read the token"
+            ),
+        );
+    }
+    notifier.notify(Urgency::Info, &format!("Listening on {listen}"));
+
+    for mut request in server.incoming_requests() {
+        let from = request
+            .remote_addr()
+            .map_or("unknown".to_string(), |addr| addr.to_string());
+        let response = match answer(args, token, &mut request) {
+            Ok((png, region)) => {
+                notifier.notify(
+                    Urgency::Debug,
+                    &format!(
+                        "Sent {from} a {region} capture of {}",
+                        util::human_size(png.len())
+                    ),
+                );
+                Response::from_data(png)
+                    .with_header(header("Content-Type", "image/png"))
+                    .with_header(header("X-Cleave-Region", &region.to_string()))
+            }
+            Err(Refused(status, message)) => {
+                notifier.notify(
+                    Urgency::Debug,
+                    &format!("Refused {from} with {status}: {message}"),
+                );
+                let response = Response::from_string(message + "\n").with_status_code(status);
+                match status {
+                    401 => response.with_header(header("WWW-Authenticate", "Bearer")),
+                    _ => response,
+                }
+            }
+        };
+        // The client hanging up isn't worth stopping the server for
+        if let Err(err) = request.respond(response) {
+            notifier.notify(Urgency::Debug, &format!("Could not answer {from}: {err}"));
+        }
+    }
+    Ok(())
+}
+
+// A PNG of what the request asks for and where it was cut from
+fn answer(args: &Args, token: &str, request: &mut Request) -> Result<(Vec<u8>, Region), Refused> {
+    if request.url() != "/capture" {
+        return Err(Refused(404, "Only /capture is served".into()));
+    }
+    if *request.method() != Method::Post {
+        return Err(Refused(405, "Captures are taken with POST".into()));
+    }
+    let authorized = request
+        .headers()
+        .iter()
+        .find(|header| header.field.equiv("Authorization"))
+        .and_then(|header| header.value.as_str().strip_prefix("Bearer "))
+        .is_some_and(|given| same_token(given.trim(), token));
+    if !authorized {
+        return Err(Refused(401, "Missing or wrong token".into()));
+    }
+
+    let mut body = String::new();
+    request
+        .as_reader()
+        .take(MAX_BODY + 1)
+        .read_to_string(&mut body)
+        .map_err(|err| Refused(400, format!("Could not read the request: {err}")))?;
+    if body.len() as u64 > MAX_BODY {
+        return Err(Refused(413, "The request is too large".into()));
+    }
+    let (args, region) = parse_request(args, &body).map_err(Refused::bad_request)?;
+
+    let capture = capture::backend(&args)
+        .capture(&args)
+        .map_err(|err| Refused(500, format!("Could not capture the screen: {err:#}")))?;
+    let (width, height) = capture.image.dimensions();
+    let region = match region {
+        Some(region) => region.fit_within(width, height).ok_or_else(|| {
+            Refused(
+                400,
+                format!("Region {region} is entirely outside the {width}x{height} capture"),
+            )
+        })?,
+        None => Region {
+            x: 0,
+            y: 0,
+            width,
+            height,
+        },
+    };
+    let selection = capture
+        .image
+        .view(region.x, region.y, region.width, region.height)
+        .to_image();
+    let mut png = Vec::new();
+    util::write_png(&selection, args.png_compression, &mut png)
+        .map_err(|err| Refused(500, format!("Could not encode the capture: {err}")))?;
+    Ok((png, region))
+}
+
+// The body is a JSON object with an optional `region` like --region, `monitor` like
+// --monitor and `format`, which can only be png for now. An empty body takes the screen
+// `cleave serve` was started for
+fn parse_request(args: &Args, body: &str) -> anyhow::Result<(Args, Option<Region>)> {
+    let mut args = args.clone();
+    if body.trim().is_empty() {
+        return Ok((args, None));
+    }
+    let serde_json::Value::Object(fields) = serde_json::from_str(body)? else {
+        anyhow::bail!("Expected a JSON object");
+    };
+    let mut region = None;
+    for (key, value) in &fields {
+        // Numbers are taken too, monitors are often picked by theirs
+        let text = match value {
+            serde_json::Value::String(text) => text.clone(),
+            serde_json::Value::Number(number) => number.to_string(),
+            _ => anyhow::bail!("{key} should be a string"),
+        };
+        match key.as_str() {
+            "region" => {
+                let requested: Region = text.parse()?;
+                region = Some(requested.anchored(args.region_origin)?);
+            }
+            "monitor" => match text.parse()? {
+                MonitorTarget::Pick => anyhow::bail!("Picking a monitor needs the overlay"),
+                target => args.monitor = target,
+            },
+            "format" => anyhow::ensure!(
+                text.eq_ignore_ascii_case("png"),
+                "Unknown format {text:?}, only png is supported"
+            ),
+            key => anyhow::bail!("Unknown field {key:?}"),
+        }
+    }
+    Ok((args, region))
+}
+
+// Compares every byte, so how long the check takes doesn't give away how much matched
+fn same_token(given: &str, token: &str) -> bool {
+    given.len() == token.len()
+        && given
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+fn header(field: &str, value: &str) -> Header {
+    Header::from_bytes(field, value).expect("header names and values are ASCII")
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::{parse_request, same_token};
+    use crate::args::{Args, MonitorTarget, Region};
+
+    #[test]
+    fn requests_set_the_region_and_monitor() {
+        let args = Args::parse_from(["cleave"]);
+        let (parsed, region) = parse_request(
+            &args,
+            r#"{"region": "640x480+100+50", "monitor": 2, "format": "png"}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            region,
+            Some(Region {
+                x: 100,
+                y: 50,
+                width: 640,
+                height: 480,
+            })
+        );
+        assert!(matches!(parsed.monitor, MonitorTarget::Named(name) if name == "2"));
+        assert!(parse_request(&args, "").unwrap().1.is_none());
+    }
+
+    #[test]
+    fn bad_requests_are_refused() {
+        let args = Args::parse_from(["cleave"]);
+        assert!(parse_request(&args, "[]").is_err());
+        assert!(parse_request(&args, r#"{"format": "jpeg"}"#).is_err());
+        assert!(parse_request(&args, r#"{"monitor": "pick"}"#).is_err());
+        assert!(parse_request(&args, r#"{"output": "/etc/passwd"}"#).is_err());
+    }
+
+    #[test]
+    fn tokens_must_match_exactly() {
+        assert!(same_token("secret", "secret"));
+        assert!(!same_token("secre", "secret"));
+        assert!(!same_token("secreT", "secret"));
+    }
+}
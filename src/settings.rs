@@ -0,0 +1,543 @@
+use std::{path::PathBuf, time::Duration};
+
+use anyhow::Context;
+use clap::ArgAction;
+use cleave_graphics::prelude::*;
+use toml_edit::{DocumentMut, Item, Value};
+use winit::{
+    application::ApplicationHandler,
+    dpi::LogicalSize,
+    event::{ElementState, KeyEvent, WindowEvent},
+    event_loop::ActiveEventLoop,
+    window::{Window, WindowAttributes, WindowId},
+};
+
+use crate::{
+    args::Args,
+    config,
+    keyboard::{self, ACTION_NAMES},
+    notify::{Notifier, Urgency},
+};
+
+const WINDOW_SIZE: LogicalSize<f64> = LogicalSize::new(640.0, 720.0);
+
+/// Opens a window for editing the config file, which is written back on save
+pub fn run(args: &Args) -> anyhow::Result<()> {
+    let path = config::config_path(args).context("Could not find config directory")?;
+    let doc = match std::fs::read_to_string(&path) {
+        Ok(text) => text
+            .parse()
+            .with_context(|| format!("Invalid config file {}", path.display()))?,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => DocumentMut::new(),
+        Err(err) => return Err(err).with_context(|| format!("Could not read {}", path.display())),
+    };
+    let mut app = SettingsApp {
+        form: Form::new(path, doc),
+        window: None,
+        error: None,
+        notifier: args.notify.notifier(args.verbosity()),
+    };
+    let event_loop = winit::event_loop::EventLoop::new()?;
+    event_loop.run_app(&mut app)?;
+    match app.error {
+        Some(err) => Err(err),
+        None => Ok(()),
+    }
+}
+
+// What the form shows for one option
+enum FieldValue {
+    Flag(bool),
+    // Value enums, empty for the default
+    Choice(Vec<String>, String),
+    Text(String),
+    List(Vec<String>),
+    // `bind`, as key and action pairs
+    Bindings(Vec<(String, String)>),
+}
+
+struct Field {
+    long: String,
+    help: String,
+    // Shown greyed out while the option isn't set
+    hint: String,
+    value: FieldValue,
+}
+
+impl Field {
+    fn new(arg: &clap::Arg, doc: &DocumentMut) -> Option<Self> {
+        let long = arg.get_long()?.to_string();
+        let values = match find_key(doc, &long) {
+            Some(key) => item_values(&doc[key.as_str()]),
+            None => Vec::new(),
+        };
+        let choices: Vec<String> = arg
+            .get_possible_values()
+            .iter()
+            .filter(|value| !value.is_hide_set())
+            .map(|value| value.get_name().to_string())
+            .collect();
+        let value = match arg.get_action() {
+            ArgAction::SetTrue => FieldValue::Flag(values.first().is_some_and(|v| v == "true")),
+            ArgAction::Append if long == "bind" => FieldValue::Bindings(
+                values
+                    .iter()
+                    .filter_map(|binding| binding.split_once('='))
+                    .map(|(key, action)| (key.trim().to_string(), action.trim().to_string()))
+                    .collect(),
+            ),
+            ArgAction::Append => FieldValue::List(values),
+            _ if !choices.is_empty() => {
+                FieldValue::Choice(choices, values.into_iter().next().unwrap_or_default())
+            }
+            _ => FieldValue::Text(values.into_iter().next().unwrap_or_default()),
+        };
+        let defaults: Vec<_> = arg
+            .get_default_values()
+            .iter()
+            .map(|value| value.to_string_lossy())
+            .collect();
+        let hint = match (defaults.as_slice(), arg.get_value_names()) {
+            ([], Some([name, ..])) => format!("<{name}>"),
+            ([], _) => String::new(),
+            (defaults, _) => defaults.join(", "),
+        };
+        Some(Self {
+            help: arg
+                .get_help()
+                .map_or(String::new(), |help| help.to_string()),
+            long,
+            hint,
+            value,
+        })
+    }
+
+    // The config file value, or `None` to leave the option out
+    fn item(&self) -> Option<Item> {
+        let value: Value = match &self.value {
+            FieldValue::Flag(false) => return None,
+            FieldValue::Flag(true) => true.into(),
+            FieldValue::Choice(_, value) | FieldValue::Text(value) => {
+                let value = value.trim();
+                if value.is_empty() {
+                    return None;
+                }
+                // Numbers stay bare, like `--dump-config` writes them
+                if let Ok(int) = value.parse::<i64>() {
+                    int.into()
+                } else if let Some(float) = value.parse::<f64>().ok().filter(|f| f.is_finite()) {
+                    float.into()
+                } else {
+                    value.into()
+                }
+            }
+            FieldValue::List(values) => {
+                let values: toml_edit::Array = values
+                    .iter()
+                    .map(|value| value.trim())
+                    .filter(|value| !value.is_empty())
+                    .collect();
+                if values.is_empty() {
+                    return None;
+                }
+                values.into()
+            }
+            FieldValue::Bindings(bindings) => {
+                let values: toml_edit::Array = bindings
+                    .iter()
+                    .filter(|(key, _)| !key.is_empty())
+                    .map(|(key, action)| format!("{key}={action}"))
+                    .collect();
+                if values.is_empty() {
+                    return None;
+                }
+                values.into()
+            }
+        };
+        Some(Item::Value(value))
+    }
+}
+
+// Config keys may use underscores instead of dashes
+fn find_key(doc: &DocumentMut, long: &str) -> Option<String> {
+    [long.to_string(), long.replace('-', "_")]
+        .into_iter()
+        .find(|key| doc.contains_key(key))
+}
+
+// Same conversions as `config::load`
+fn item_values(item: &Item) -> Vec<String> {
+    let Item::Value(value) = item else {
+        return Vec::new();
+    };
+    let values = match value {
+        Value::Array(array) => array.iter().collect(),
+        value => vec![value],
+    };
+    values
+        .into_iter()
+        .filter_map(|value| match value {
+            Value::String(s) => Some(s.value().clone()),
+            Value::Integer(i) => Some(i.value().to_string()),
+            Value::Float(f) => Some(f.value().to_string()),
+            Value::Boolean(b) => Some(b.value().to_string()),
+            _ => None,
+        })
+        .collect()
+}
+
+struct Form {
+    path: PathBuf,
+    // Kept around so comments and layout in the file survive saving
+    doc: DocumentMut,
+    fields: Vec<Field>,
+    // Field and row of the binding waiting for a key press
+    capturing: Option<(usize, usize)>,
+    // Outcome of the last save, and whether it worked
+    status: Option<(bool, String)>,
+}
+
+impl Form {
+    fn new(path: PathBuf, doc: DocumentMut) -> Self {
+        let fields = config::options()
+            .iter()
+            .filter_map(|arg| Field::new(arg, &doc))
+            .collect();
+        Self {
+            path,
+            doc,
+            fields,
+            capturing: None,
+            status: None,
+        }
+    }
+
+    fn save(&mut self) -> anyhow::Result<()> {
+        let mut doc = self.doc.clone();
+        for field in &self.fields {
+            let key = find_key(&doc, &field.long).unwrap_or_else(|| field.long.clone());
+            match field.item() {
+                // Untouched values keep their formatting and trailing comments
+                Some(item) if doc.get(&key).map(item_values) == Some(item_values(&item)) => {}
+                // Assigning in place keeps the key's position and comments
+                Some(item) => doc[key.as_str()] = item,
+                None => {
+                    doc.remove(&key);
+                }
+            }
+        }
+        let text = doc.to_string();
+        config::validate(&text)?;
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&self.path, text)?;
+        self.doc = doc;
+        Ok(())
+    }
+
+    // Binds the key pressed while a binding was waiting for one
+    fn capture_key(&mut self, key: &winit::keyboard::Key) -> bool {
+        let Some((field, row)) = self.capturing.take() else {
+            return false;
+        };
+        if let (Some(name), FieldValue::Bindings(bindings)) =
+            (keyboard::key_name(key), &mut self.fields[field].value)
+        {
+            bindings[row].0 = name;
+        }
+        true
+    }
+
+    fn ui(&mut self, ctx: &egui::Context) {
+        egui::TopBottomPanel::bottom("actions").show(ctx, |ui| {
+            ui.add_space(4.0);
+            ui.horizontal(|ui| {
+                if ui.button("Save").clicked() {
+                    self.status = Some(match self.save() {
+                        Ok(()) => (true, format!("Saved to {}", self.path.display())),
+                        Err(err) => (false, format!("{err:#}")),
+                    });
+                }
+                match &self.status {
+                    Some((true, message)) => ui.label(message),
+                    Some((false, message)) => {
+                        ui.colored_label(ui.visuals().error_fg_color, message)
+                    }
+                    None => ui.weak(self.path.display().to_string()),
+                };
+            });
+            ui.add_space(4.0);
+        });
+        egui::CentralPanel::default().show(ctx, |ui| {
+            egui::ScrollArea::vertical()
+                .auto_shrink(false)
+                .show(ui, |ui| {
+                    egui::Grid::new("options")
+                        .num_columns(2)
+                        .striped(true)
+                        .show(ui, |ui| {
+                            for (index, field) in self.fields.iter_mut().enumerate() {
+                                ui.label(&field.long).on_hover_text(&field.help);
+                                field_ui(ui, index, field, &mut self.capturing);
+                                ui.end_row();
+                            }
+                        });
+                });
+        });
+    }
+}
+
+fn field_ui(
+    ui: &mut egui::Ui,
+    index: usize,
+    field: &mut Field,
+    capturing: &mut Option<(usize, usize)>,
+) {
+    match &mut field.value {
+        FieldValue::Flag(set) => {
+            ui.checkbox(set, "");
+        }
+        FieldValue::Choice(choices, value) => {
+            let selected = match value.as_str() {
+                "" => format!("default ({})", field.hint),
+                value => value.to_string(),
+            };
+            egui::ComboBox::from_id_salt(&field.long)
+                .selected_text(selected)
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(value, String::new(), "default");
+                    for choice in choices.iter() {
+                        ui.selectable_value(value, choice.clone(), choice);
+                    }
+                });
+        }
+        FieldValue::Text(value) => {
+            ui.add(egui::TextEdit::singleline(value).hint_text(&field.hint));
+        }
+        FieldValue::List(values) => {
+            ui.vertical(|ui| {
+                values.retain_mut(|value| {
+                    ui.horizontal(|ui| {
+                        ui.text_edit_singleline(value);
+                        !ui.small_button("✕").clicked()
+                    })
+                    .inner
+                });
+                if ui.small_button("Add").clicked() {
+                    values.push(String::new());
+                }
+            });
+        }
+        FieldValue::Bindings(bindings) => {
+            let actions: Vec<&str> = ACTION_NAMES.split(", ").collect();
+            ui.vertical(|ui| {
+                let mut removed = None;
+                for (row, (key, action)) in bindings.iter_mut().enumerate() {
+                    ui.horizontal(|ui| {
+                        let label = match (*capturing == Some((index, row)), key.as_str()) {
+                            (true, _) => "Press a key…",
+                            (false, "") => "Set key",
+                            (false, key) => key,
+                        };
+                        if ui.button(label).clicked() {
+                            *capturing = Some((index, row));
+                        }
+                        egui::ComboBox::from_id_salt((&field.long, row))
+                            .selected_text(action.as_str())
+                            .show_ui(ui, |ui| {
+                                for name in &actions {
+                                    ui.selectable_value(action, name.to_string(), *name);
+                                }
+                            });
+                        if ui.small_button("✕").clicked() {
+                            removed = Some(row);
+                        }
+                    });
+                }
+                if let Some(row) = removed {
+                    bindings.remove(row);
+                    *capturing = None;
+                }
+                if ui.small_button("Add").clicked() {
+                    bindings.push((String::new(), actions[0].to_string()));
+                    *capturing = Some((index, bindings.len() - 1));
+                }
+            });
+        }
+    }
+}
+
+struct SettingsWindow {
+    graphics: Graphics<Window>,
+    state: egui_winit::State,
+    renderer: egui_wgpu::Renderer,
+}
+
+struct SettingsApp {
+    form: Form,
+    window: Option<SettingsWindow>,
+    // Why the window couldn't be opened
+    error: Option<anyhow::Error>,
+    notifier: Box<dyn Notifier>,
+}
+
+impl SettingsApp {
+    fn open(&self, event_loop: &ActiveEventLoop) -> anyhow::Result<SettingsWindow> {
+        let window = event_loop.create_window(
+            WindowAttributes::default()
+                .with_title("Cleave settings")
+                .with_inner_size(WINDOW_SIZE),
+        )?;
+        let size = window.inner_size();
+        let graphics = pollster::block_on(Graphics::new(window, size.width, size.height))?;
+        let ctx = egui::Context::default();
+        let state = egui_winit::State::new(
+            ctx.clone(),
+            egui::ViewportId::ROOT,
+            &*graphics.window,
+            Some(graphics.window.scale_factor() as f32),
+            None,
+            None,
+        );
+        let renderer =
+            egui_wgpu::Renderer::new(&graphics.device, graphics.config.format, None, 1, false);
+        Ok(SettingsWindow {
+            graphics,
+            state,
+            renderer,
+        })
+    }
+
+    fn draw(&mut self) {
+        let Some(window) = &mut self.window else {
+            return;
+        };
+        let graphics = &mut window.graphics;
+        let input = window.state.take_egui_input(&graphics.window);
+        let ctx = window.state.egui_ctx().clone();
+        let output = ctx.run(input, |ctx| self.form.ui(ctx));
+        window
+            .state
+            .handle_platform_output(&graphics.window, output.platform_output);
+
+        let primitives = ctx.tessellate(output.shapes, output.pixels_per_point);
+        for (id, delta) in &output.textures_delta.set {
+            window
+                .renderer
+                .update_texture(&graphics.device, &graphics.queue, *id, delta);
+        }
+        let screen = egui_wgpu::ScreenDescriptor {
+            size_in_pixels: [graphics.config.width, graphics.config.height],
+            pixels_per_point: output.pixels_per_point,
+        };
+        let frame = match graphics.surface.get_current_texture() {
+            Ok(frame) => frame,
+            Err(err) => {
+                self.notifier
+                    .notify(Urgency::Warning, &format!("Could not render frame: {err}"));
+                graphics
+                    .surface
+                    .configure(&graphics.device, &graphics.config);
+                return;
+            }
+        };
+        let view = frame
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        let mut encoder = graphics
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        let mut commands = window.renderer.update_buffers(
+            &graphics.device,
+            &graphics.queue,
+            &mut encoder,
+            &primitives,
+            &screen,
+        );
+        {
+            let pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("settings"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            window
+                .renderer
+                .render(&mut pass.forget_lifetime(), &primitives, &screen);
+        }
+        commands.push(encoder.finish());
+        graphics.queue.submit(commands);
+        frame.present();
+        for id in &output.textures_delta.free {
+            window.renderer.free_texture(id);
+        }
+
+        let repaint = output
+            .viewport_output
+            .get(&egui::ViewportId::ROOT)
+            .is_some_and(|viewport| viewport.repaint_delay == Duration::ZERO);
+        if repaint {
+            graphics.window.request_redraw();
+        }
+    }
+}
+
+impl ApplicationHandler for SettingsApp {
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        if self.window.is_some() {
+            return;
+        }
+        match self.open(event_loop) {
+            Ok(window) => self.window = Some(window),
+            Err(err) => {
+                self.error = Some(err.context("Could not open the settings window"));
+                event_loop.exit();
+            }
+        }
+    }
+
+    fn window_event(&mut self, event_loop: &ActiveEventLoop, _: WindowId, event: WindowEvent) {
+        let Some(window) = &mut self.window else {
+            return;
+        };
+        // A binding waiting for a key takes the next press for itself
+        if let WindowEvent::KeyboardInput {
+            event:
+                KeyEvent {
+                    state: ElementState::Pressed,
+                    logical_key,
+                    ..
+                },
+            ..
+        } = &event
+        {
+            if self.form.capture_key(logical_key) {
+                window.graphics.window.request_redraw();
+                return;
+            }
+        }
+
+        let response = window
+            .state
+            .on_window_event(&window.graphics.window, &event);
+        match event {
+            WindowEvent::CloseRequested => event_loop.exit(),
+            WindowEvent::Resized(size) => {
+                window.graphics.resize(size.width, size.height);
+                window.graphics.window.request_redraw();
+            }
+            WindowEvent::RedrawRequested => self.draw(),
+            _ if response.repaint => window.graphics.window.request_redraw(),
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,51 @@
+use glam::Vec2;
+
+use crate::util::capture::MonitorInfo;
+
+/// Lines the selection's edges are pulled onto with `--snap`, in capture pixels
+pub struct Guides {
+    xs: Vec<f32>,
+    ys: Vec<f32>,
+}
+
+impl Guides {
+    /// The capture's edges and center lines, plus the edges of every window on
+    /// `monitor` when the capture came from one
+    pub fn new(size: Vec2, monitor: Option<&MonitorInfo>) -> Self {
+        let mut xs = vec![0.0, size.x * 0.5, size.x];
+        let mut ys = vec![0.0, size.y * 0.5, size.y];
+        // Guides are a nicety, so a failed window listing just leaves them out
+        let windows = monitor.and_then(|monitor| Some((monitor, xcap::Window::all().ok()?)));
+        if let Some((monitor, windows)) = windows {
+            for window in windows.iter().filter(|w| !w.is_minimized()) {
+                // Windows are in global coordinates, the capture starts at the monitor's corner
+                let x = (window.x() - monitor.position.x) as f32;
+                let y = (window.y() - monitor.position.y) as f32;
+                let (width, height) = (window.width() as f32, window.height() as f32);
+                let on_capture = x < size.x && y < size.y && x + width > 0.0 && y + height > 0.0;
+                if on_capture {
+                    xs.extend([x, x + width]);
+                    ys.extend([y, y + height]);
+                }
+            }
+        }
+        Self { xs, ys }
+    }
+
+    /// Moves each coordinate of `point` onto the nearest guide within `tolerance`
+    pub fn snap(&self, point: Vec2, tolerance: f32) -> Vec2 {
+        Vec2::new(
+            nearest(&self.xs, point.x, tolerance),
+            nearest(&self.ys, point.y, tolerance),
+        )
+    }
+}
+
+fn nearest(lines: &[f32], value: f32, tolerance: f32) -> f32 {
+    lines
+        .iter()
+        .copied()
+        .filter(|line| (line - value).abs() <= tolerance)
+        .min_by(|a, b| (a - value).abs().total_cmp(&(b - value).abs()))
+        .unwrap_or(value)
+}
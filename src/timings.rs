@@ -0,0 +1,54 @@
+use std::time::{Duration, Instant};
+
+/// How long each step of opening the overlay took, for `--timings`
+pub struct Timings {
+    started: Instant,
+    // When the last step ended, and so the next one started
+    last: Instant,
+    steps: Vec<(&'static str, Duration)>,
+}
+
+impl Timings {
+    pub fn new() -> Self {
+        let now = Instant::now();
+        Self {
+            started: now,
+            last: now,
+            steps: Vec::new(),
+        }
+    }
+
+    /// Ends a step that started when the previous one ended
+    pub fn lap(&mut self, step: &'static str) {
+        let now = Instant::now();
+        self.steps.push((step, now - self.last));
+        self.last = now;
+    }
+
+    /// Adds a step that ran alongside the others
+    pub fn record(&mut self, step: &'static str, took: Duration) {
+        self.steps.push((step, took));
+    }
+
+    pub fn get(&self, step: &str) -> Duration {
+        self.steps
+            .iter()
+            .filter(|(name, _)| *name == step)
+            .map(|(_, took)| *took)
+            .sum()
+    }
+
+    /// Every step so far and the time since `new`, on one line
+    pub fn summary(&self) -> String {
+        let steps: Vec<String> = self
+            .steps
+            .iter()
+            .map(|(name, took)| format!("{name} {:.1}ms", took.as_secs_f32() * 1000.0))
+            .collect();
+        format!(
+            "Overlay shown after {:.1}ms: {}",
+            self.started.elapsed().as_secs_f32() * 1000.0,
+            steps.join(", ")
+        )
+    }
+}
@@ -0,0 +1,502 @@
+use anyhow::Context;
+use image::{Rgba, RgbaImage};
+use winit::dpi::PhysicalPosition;
+
+use crate::{
+    active_window,
+    args::{Args, ExcludeStyle, MonitorTarget},
+};
+
+/// A captured monitor
+pub struct Capture {
+    pub image: RgbaImage,
+    // Which monitor it was, if the backend knows
+    pub monitor: Option<MonitorInfo>,
+    // The window captured with `--window`, whose corner the image starts at
+    pub window: Option<WindowInfo>,
+}
+
+impl Capture {
+    /// Uses `--dpi-scale` over the scale factor the platform reported
+    pub fn override_scale(&mut self, scale: Option<f32>) {
+        if let (Some(monitor), Some(scale)) = (self.monitor.as_mut(), scale) {
+            monitor.scale_factor = scale;
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct MonitorInfo {
+    pub id: u32,
+    pub name: String,
+    // Top left corner on the desktop
+    pub position: PhysicalPosition<i32>,
+    pub scale_factor: f32,
+}
+
+impl From<&xcap::Monitor> for MonitorInfo {
+    fn from(monitor: &xcap::Monitor) -> Self {
+        Self {
+            id: monitor.id(),
+            name: monitor.name().to_string(),
+            position: PhysicalPosition::new(monitor.x(), monitor.y()),
+            scale_factor: monitor.scale_factor(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct WindowInfo {
+    pub id: u32,
+    pub title: String,
+    pub app_name: String,
+    // Top left corner on the desktop
+    pub position: PhysicalPosition<i32>,
+}
+
+impl From<&xcap::Window> for WindowInfo {
+    fn from(window: &xcap::Window) -> Self {
+        Self {
+            id: window.id(),
+            title: window.title().to_string(),
+            app_name: window.app_name().to_string(),
+            position: PhysicalPosition::new(window.x(), window.y()),
+        }
+    }
+}
+
+pub trait CaptureBackend {
+    /// Captures the monitor `args` asks for
+    fn capture(&self, args: &Args) -> anyhow::Result<Capture>;
+
+    fn name(&self) -> &'static str;
+}
+
+/// Picks the agent `--remote` runs, then the portal on desktops where xcap can't list the
+/// monitors, such as wlroots compositors without XWayland, and xcap everywhere else
+pub fn backend(args: &Args) -> Box<dyn CaptureBackend> {
+    if let Some(command) = &args.remote {
+        return Box::new(crate::agent::RemoteBackend {
+            command: command.clone(),
+        });
+    }
+    #[cfg(all(target_os = "linux", feature = "portal"))]
+    if xcap::Monitor::all().map_or(true, |monitors| monitors.is_empty()) {
+        return Box::new(portal::PortalBackend);
+    }
+    Box::new(XcapBackend)
+}
+
+pub struct XcapBackend;
+
+impl CaptureBackend for XcapBackend {
+    fn capture(&self, args: &Args) -> anyhow::Result<Capture> {
+        if let Some(query) = &args.window {
+            return capture_window(&get_window(query)?, args);
+        }
+        let monitor = find_monitor(&args.monitor)?;
+        let mut capture = capture_monitor(&monitor, args)?;
+        capture.override_scale(args.dpi_scale);
+        Ok(capture)
+    }
+
+    fn name(&self) -> &'static str {
+        "xcap"
+    }
+}
+
+/// Captures `monitor` with what `--exclude-window` and `--exclude-region` cover hidden
+pub fn capture_monitor(monitor: &xcap::Monitor, args: &Args) -> anyhow::Result<Capture> {
+    let info = MonitorInfo::from(monitor);
+    let mut capture = Capture {
+        image: monitor.capture_image()?,
+        monitor: Some(info.clone()),
+        window: None,
+    };
+    hide_excluded(&mut capture, Some(&info), args)?;
+    Ok(capture)
+}
+
+/// Hides every window matching one of `--exclude-window` and every `--exclude-region` for
+/// `monitor`, so they never reach the overlay or a sink. Window captures are on `monitor`
+/// too, and what's hidden is moved to the window's corner. Windows can only be found on
+/// captures that know their monitor
+fn hide_excluded(
+    capture: &mut Capture,
+    monitor: Option<&MonitorInfo>,
+    args: &Args,
+) -> anyhow::Result<()> {
+    // Where the image starts on the desktop
+    let origin = match (&capture.window, monitor) {
+        (Some(window), _) => Some(window.position),
+        (None, Some(monitor)) => Some(monitor.position),
+        (None, None) => None,
+    };
+    // Excluded regions are in the monitor's pixels
+    let (offset_x, offset_y) = match (monitor, origin) {
+        (Some(monitor), Some(origin)) => {
+            (monitor.position.x - origin.x, monitor.position.y - origin.y)
+        }
+        _ => (0, 0),
+    };
+    let mut hidden: Vec<(i32, i32, u32, u32)> = args
+        .exclude_region
+        .iter()
+        .filter(|excluded| excluded.applies_to(monitor))
+        .map(|excluded| {
+            let region = excluded.region;
+            (
+                region.x as i32 + offset_x,
+                region.y as i32 + offset_y,
+                region.width,
+                region.height,
+            )
+        })
+        .collect();
+    let captured_window = capture.window.as_ref().map(|window| window.id);
+    if let (Some(origin), false) = (origin, args.exclude_window.is_empty()) {
+        let patterns: Vec<_> = args
+            .exclude_window
+            .iter()
+            .map(|p| p.to_lowercase())
+            .collect();
+        for window in xcap::Window::all()? {
+            // Hiding the window being captured would leave nothing
+            if window.is_minimized() || Some(window.id()) == captured_window {
+                continue;
+            }
+            let title = window.title().to_lowercase();
+            let app_name = window.app_name().to_lowercase();
+            if !patterns
+                .iter()
+                .any(|p| title.contains(p) || app_name.contains(p))
+            {
+                continue;
+            }
+            // Windows are in global coordinates, the capture starts at `origin`
+            hidden.push((
+                window.x() - origin.x,
+                window.y() - origin.y,
+                window.width(),
+                window.height(),
+            ));
+        }
+    }
+    for (x, y, width, height) in hidden {
+        match args.exclude_style {
+            ExcludeStyle::Black => super::fill_rect(
+                &mut capture.image,
+                x,
+                y,
+                width,
+                height,
+                Rgba([0, 0, 0, 255]),
+            ),
+            ExcludeStyle::Blur => super::blur_rect(&mut capture.image, x, y, width, height),
+        }
+    }
+    Ok(())
+}
+
+fn find_monitor(target: &MonitorTarget) -> anyhow::Result<xcap::Monitor> {
+    match target {
+        // Picking only needs asking with more than one monitor, which the caller handles
+        MonitorTarget::Primary | MonitorTarget::Pick => xcap::Monitor::all()?
+            .into_iter()
+            .find(|m| m.is_primary())
+            .with_context(|| "Could not get primary monitor"),
+        MonitorTarget::Window => {
+            let windows = xcap::Window::all()?;
+            let active = active_window::active_window_id();
+            // Without an active window id, the first visible window in the front to back list has focus
+            let window = windows
+                .into_iter()
+                .filter(|w| !w.is_minimized() && w.width() > 0 && w.height() > 0)
+                .find(|w| active.is_none_or(|id| w.id() == id))
+                .with_context(|| "Could not find the focused window")?;
+            Ok(window.current_monitor())
+        }
+        MonitorTarget::Named(query) => get_monitor(query),
+    }
+}
+
+/// The monitor `query` names: its ID, its number in `--monitor-list`, or part of its
+/// name in any case
+pub fn get_monitor(query: &str) -> anyhow::Result<xcap::Monitor> {
+    let monitors = xcap::Monitor::all()?;
+    let position = match query.parse::<u32>() {
+        // IDs come first since they don't change when monitors are plugged in or out
+        Ok(number) => monitors
+            .iter()
+            .position(|m| m.id() == number)
+            .or_else(|| (number as usize).checked_sub(1))
+            .filter(|&i| i < monitors.len()),
+        Err(_) => None,
+    };
+    if let Some(i) = position {
+        return Ok(monitors.into_iter().nth(i).expect("index is in bounds"));
+    }
+    let lowercase = query.to_lowercase();
+    let matching: Vec<usize> = (0..monitors.len())
+        .filter(|&i| monitors[i].name().to_lowercase().contains(&lowercase))
+        .collect();
+    match matching[..] {
+        [i] => Ok(monitors.into_iter().nth(i).expect("index is in bounds")),
+        [] => anyhow::bail!(
+            "No monitor matches {query:?}, the monitors are:\n{}",
+            describe_monitors(&monitors, 0..monitors.len())
+        ),
+        _ => anyhow::bail!(
+            "{query:?} matches more than one monitor, use its number or ID instead:\n{}",
+            describe_monitors(&monitors, matching)
+        ),
+    }
+}
+
+/// Every monitor for `--monitor-list`, one per line and numbered the way `--monitor` takes them
+pub fn list_monitors() -> anyhow::Result<String> {
+    let monitors = xcap::Monitor::all()?;
+    if monitors.is_empty() {
+        anyhow::bail!("No monitors found");
+    }
+    Ok(describe_monitors(&monitors, 0..monitors.len()))
+}
+
+fn describe_monitors(monitors: &[xcap::Monitor], which: impl IntoIterator<Item = usize>) -> String {
+    let lines: Vec<String> = which
+        .into_iter()
+        .map(|i| {
+            let monitor = &monitors[i];
+            let primary = if monitor.is_primary() {
+                ", primary"
+            } else {
+                ""
+            };
+            format!(
+                "{}: {} (ID {}) {}x{} at {},{}, scale {}{primary}",
+                i + 1,
+                monitor.name(),
+                monitor.id(),
+                monitor.width(),
+                monitor.height(),
+                monitor.x(),
+                monitor.y(),
+                monitor.scale_factor()
+            )
+        })
+        .collect();
+    lines.join("\n")
+}
+
+/// Captures just `window`, even where it's covered by others, with what `--exclude-window`
+/// and `--exclude-region` cover on top of it hidden. On X11 windows on a workspace that
+/// isn't showing aren't drawn, so those are an error naming the workspace
+pub fn capture_window(window: &xcap::Window, args: &Args) -> anyhow::Result<Capture> {
+    let title = window.title();
+    if window.is_minimized() {
+        anyhow::bail!("{title:?} is minimized, restore it to capture it");
+    }
+    let workspaces = active_window::workspaces(&[window.id()]);
+    let image = window.capture_image().with_context(|| {
+        match (workspaces.current, workspaces.of_windows[0]) {
+            (Some(current), Some(on)) if current != on => format!(
+                "{title:?} is on workspace {}, which isn't showing. Switch to it to capture the window",
+                on + 1
+            ),
+            _ => format!("Could not capture {title:?}"),
+        }
+    })?;
+    let mut capture = Capture {
+        image,
+        monitor: None,
+        window: Some(window.into()),
+    };
+    let monitor = MonitorInfo::from(&window.current_monitor());
+    hide_excluded(&mut capture, Some(&monitor), args)?;
+    Ok(capture)
+}
+
+/// The window `query` names: its ID, its number in `--window-list`, or part of its title
+/// or app name in any case
+pub fn get_window(query: &str) -> anyhow::Result<xcap::Window> {
+    let windows = listed_windows()?;
+    let position = match query.parse::<u32>() {
+        Ok(number) => windows
+            .iter()
+            .position(|w| w.id() == number)
+            .or_else(|| (number as usize).checked_sub(1))
+            .filter(|&i| i < windows.len()),
+        Err(_) => None,
+    };
+    if let Some(i) = position {
+        return Ok(windows.into_iter().nth(i).expect("index is in bounds"));
+    }
+    let lowercase = query.to_lowercase();
+    let matching: Vec<usize> = (0..windows.len())
+        .filter(|&i| {
+            windows[i].title().to_lowercase().contains(&lowercase)
+                || windows[i].app_name().to_lowercase().contains(&lowercase)
+        })
+        .collect();
+    match matching[..] {
+        [i] => Ok(windows.into_iter().nth(i).expect("index is in bounds")),
+        [] => anyhow::bail!(
+            "No window matches {query:?}, the windows are:\n{}",
+            describe_windows(&windows, 0..windows.len())
+        ),
+        _ => anyhow::bail!(
+            "{query:?} matches more than one window, use its number or ID instead:\n{}",
+            describe_windows(&windows, matching)
+        ),
+    }
+}
+
+/// Every window for `--window-list`, one per line and numbered the way `--window` takes them
+pub fn list_windows() -> anyhow::Result<String> {
+    let windows = listed_windows()?;
+    if windows.is_empty() {
+        anyhow::bail!("No windows found");
+    }
+    Ok(describe_windows(&windows, 0..windows.len()))
+}
+
+// Zero sized windows are helpers that can't be captured
+fn listed_windows() -> anyhow::Result<Vec<xcap::Window>> {
+    Ok(xcap::Window::all()?
+        .into_iter()
+        .filter(|w| w.width() > 0 && w.height() > 0)
+        .collect())
+}
+
+fn describe_windows(windows: &[xcap::Window], which: impl IntoIterator<Item = usize>) -> String {
+    let which: Vec<usize> = which.into_iter().collect();
+    let ids: Vec<u32> = which.iter().map(|&i| windows[i].id()).collect();
+    let workspaces = active_window::workspaces(&ids);
+    let lines: Vec<String> = which
+        .iter()
+        .zip(&workspaces.of_windows)
+        .map(|(&i, workspace)| {
+            let window = &windows[i];
+            let mut notes = String::new();
+            if let Some(on) = workspace {
+                notes += &format!(", workspace {}", on + 1);
+                if workspaces.current.is_some_and(|current| current != *on) {
+                    notes += " (hidden)";
+                }
+            }
+            if window.is_minimized() {
+                notes += ", minimized";
+            }
+            format!(
+                "{}: {:?} ({}, ID {}) {}x{} at {},{}{notes}",
+                i + 1,
+                window.title(),
+                window.app_name(),
+                window.id(),
+                window.width(),
+                window.height(),
+                window.x(),
+                window.y()
+            )
+        })
+        .collect();
+    lines.join("\n")
+}
+
+#[cfg(all(target_os = "linux", feature = "portal"))]
+mod portal {
+    use std::{
+        sync::{Arc, Mutex},
+        time::{Duration, Instant},
+    };
+
+    use anyhow::Context;
+    use dbus::{
+        arg::{PropMap, RefArg, Variant},
+        blocking::Connection,
+        message::MatchRule,
+    };
+
+    use super::{Capture, CaptureBackend};
+    use crate::{args::Args, util};
+
+    // The user may have to approve the screenshot in a dialog first
+    const RESPONSE_TIMEOUT: Duration = Duration::from_secs(60);
+
+    /// Screenshots through the xdg-desktop-portal Screenshot interface.
+    ///
+    /// The portal captures the whole desktop and doesn't say where each monitor is, so
+    /// `--monitor`, `--window`, `--exclude-window` and monitor-specific `--exclude-region`s
+    /// don't apply and the overlay opens on whichever monitor the compositor chooses.
+    pub struct PortalBackend;
+
+    impl CaptureBackend for PortalBackend {
+        fn capture(&self, args: &Args) -> anyhow::Result<Capture> {
+            let conn = Connection::new_session()?;
+            let proxy = conn.with_proxy(
+                "org.freedesktop.portal.Desktop",
+                "/org/freedesktop/portal/desktop",
+                Duration::from_secs(5),
+            );
+
+            // Listen on the request path before asking, so a fast response can't be missed
+            let token = format!("cleave{}", std::process::id());
+            let sender = conn.unique_name().trim_start_matches(':').replace('.', "_");
+            let request = format!("/org/freedesktop/portal/desktop/request/{sender}/{token}");
+            let response = Arc::new(Mutex::new(None));
+            let slot = response.clone();
+            let rule = MatchRule::new_signal("org.freedesktop.portal.Request", "Response")
+                .with_path(request);
+            conn.add_match(rule, move |(code, results): (u32, PropMap), _, _| {
+                *slot.lock().unwrap() = Some((code, results));
+                false
+            })?;
+
+            let mut options = PropMap::new();
+            options.insert("handle_token".into(), Variant(Box::new(token)));
+            options.insert("interactive".into(), Variant(Box::new(false)));
+            let _: (dbus::Path,) = proxy.method_call(
+                "org.freedesktop.portal.Screenshot",
+                "Screenshot",
+                ("", options),
+            )?;
+
+            let started = Instant::now();
+            let (code, results) = loop {
+                conn.process(Duration::from_millis(100))?;
+                if let Some(response) = response.lock().unwrap().take() {
+                    break response;
+                }
+                if started.elapsed() > RESPONSE_TIMEOUT {
+                    anyhow::bail!("The screenshot portal didn't respond");
+                }
+            };
+            if code != 0 {
+                anyhow::bail!("The screenshot was cancelled or denied");
+            }
+
+            let uri = results
+                .get("uri")
+                .and_then(|uri| uri.0.as_str())
+                .context("The screenshot portal didn't return an image")?;
+            let path = util::percent_decode(
+                uri.strip_prefix("file://")
+                    .with_context(|| format!("Unsupported screenshot location {uri}"))?,
+            );
+            let image = image::open(&path)?.to_rgba8();
+            // The portal saves into the user's pictures, the overlay only needed it once
+            let _ = std::fs::remove_file(&path);
+            let mut capture = Capture {
+                image,
+                monitor: None,
+                window: None,
+            };
+            super::hide_excluded(&mut capture, None, args)?;
+            Ok(capture)
+        }
+
+        fn name(&self) -> &'static str {
+            "xdg-desktop-portal"
+        }
+    }
+}
@@ -0,0 +1,164 @@
+use std::borrow::Cow;
+
+use anyhow::bail;
+use arboard::{Clipboard, ImageData};
+use image::{imageops::FilterType, RgbaImage};
+
+use crate::args::{Args, ClipboardFormat};
+
+pub enum Copied {
+    Full,
+    Downscaled { width: u32, height: u32 },
+}
+
+/// Puts `img` on the clipboard in `--clipboard-format`, falling back to a copy no
+/// larger than `--clipboard-max-dimension` on either side if the full image doesn't stick
+pub fn copy_image(img: &RgbaImage, args: &Args) -> anyhow::Result<Copied> {
+    let max_dimension = args.clipboard_max_dimension;
+    let mut clipboard = Clipboard::new()?;
+    let mut copy = |img: &RgbaImage| -> anyhow::Result<()> {
+        match args.clipboard_format {
+            ClipboardFormat::Auto => set_image(&mut clipboard, img)?,
+            ClipboardFormat::Png => png::set(img, args.png_compression)?,
+        }
+        verify(&mut clipboard, img)
+    };
+    match copy(img) {
+        Ok(()) => return Ok(Copied::Full),
+        Err(err) if img.width().max(img.height()) <= max_dimension => return Err(err),
+        Err(_) => {}
+    }
+
+    let scaled = downscale(img, max_dimension);
+    copy(&scaled)?;
+    Ok(Copied::Downscaled {
+        width: scaled.width(),
+        height: scaled.height(),
+    })
+}
+
+/// Puts `text` on the clipboard, replacing whatever was there
+pub fn copy_text(text: &str) -> anyhow::Result<()> {
+    Clipboard::new()?.set_text(text)?;
+    Ok(())
+}
+
+/// Like `copy_text`, then keeps serving the text until something else is copied, which
+/// only the background process behind a notification's actions can wait for
+#[cfg(all(feature = "notifications", unix, not(target_os = "macos")))]
+pub fn serve_text(text: &str) -> anyhow::Result<()> {
+    use arboard::SetExtLinux;
+    Clipboard::new()?.set().wait().text(text)?;
+    Ok(())
+}
+
+/// Copies a small test image and reads it back. Text that was on the clipboard
+/// is put back afterwards, anything else is lost
+pub fn roundtrip() -> anyhow::Result<()> {
+    let mut clipboard = Clipboard::new()?;
+    let previous = clipboard.get_text().ok();
+    let img = RgbaImage::new(4, 4);
+    let result = set_image(&mut clipboard, &img).and_then(|()| verify(&mut clipboard, &img));
+    if let Some(text) = previous {
+        clipboard.set_text(text)?;
+    }
+    result
+}
+
+/// Whether the clipboard currently holds `img`, or the copy of it `copy_image` would
+/// have scaled down to `max_dimension`
+pub fn holds_image(img: &RgbaImage, max_dimension: u32) -> anyhow::Result<bool> {
+    let stored = Clipboard::new()?.get_image()?;
+    let stored = (stored.width as u32, stored.height as u32);
+    let scaled = downscaled_size(img, max_dimension);
+    Ok(stored == img.dimensions() || stored == scaled)
+}
+
+fn set_image(clipboard: &mut Clipboard, img: &RgbaImage) -> anyhow::Result<()> {
+    clipboard.set_image(ImageData {
+        width: img.width() as usize,
+        height: img.height() as usize,
+        bytes: Cow::Borrowed(img.as_raw()),
+    })?;
+    Ok(())
+}
+
+// Some clipboard managers drop large images without reporting an error, so read the image back
+fn verify(clipboard: &mut Clipboard, img: &RgbaImage) -> anyhow::Result<()> {
+    let (width, height) = (img.width() as usize, img.height() as usize);
+    let stored = clipboard.get_image()?;
+    if (stored.width, stored.height) != (width, height) {
+        bail!(
+            "Clipboard holds a {}x{} image instead of {width}x{height}",
+            stored.width,
+            stored.height
+        );
+    }
+    Ok(())
+}
+
+fn downscale(img: &RgbaImage, max_dimension: u32) -> RgbaImage {
+    let (width, height) = downscaled_size(img, max_dimension);
+    image::imageops::resize(img, width, height, FilterType::Triangle)
+}
+
+fn downscaled_size(img: &RgbaImage, max_dimension: u32) -> (u32, u32) {
+    let scale = max_dimension as f32 / img.width().max(img.height()) as f32;
+    let width = ((img.width() as f32 * scale).round() as u32).max(1);
+    let height = ((img.height() as f32 * scale).round() as u32).max(1);
+    (width, height)
+}
+
+// arboard can't pick the bytes behind a format, so `--clipboard-format png` hands the
+// encoded image to the desktop's clipboard tool, which keeps serving it after cleave exits
+#[cfg(target_os = "linux")]
+mod png {
+    use std::{
+        io::Write,
+        process::{Command, Stdio},
+    };
+
+    use anyhow::Context;
+    use image::RgbaImage;
+
+    use crate::{args::PngCompression, util};
+
+    pub fn set(img: &RgbaImage, compression: PngCompression) -> anyhow::Result<()> {
+        let mut png = Vec::new();
+        util::write_png(img, compression, &mut png)?;
+        let (program, args): (&str, &[&str]) = if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+            ("wl-copy", &["--type", "image/png"])
+        } else {
+            (
+                "xclip",
+                &["-selection", "clipboard", "-target", "image/png", "-in"],
+            )
+        };
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .spawn()
+            .with_context(|| format!("Could not run {program}, is it installed?"))?;
+        child
+            .stdin
+            .take()
+            .context("No stdin to write to")?
+            .write_all(&png)?;
+        // Both fork into the background to serve the clipboard once their input is read
+        let status = child.wait()?;
+        anyhow::ensure!(status.success(), "{program} failed with {status}");
+        Ok(())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod png {
+    use image::RgbaImage;
+
+    use crate::args::PngCompression;
+
+    pub fn set(_: &RgbaImage, _: PngCompression) -> anyhow::Result<()> {
+        anyhow::bail!("--clipboard-format png is only available on Linux")
+    }
+}
@@ -0,0 +1,62 @@
+use glam::Vec2;
+use image::{imageops, Rgba, RgbaImage};
+
+use super::rounded_box_distance;
+use crate::args::{Args, Border};
+
+// Blur sigma of the drop shadow, the canvas grows by twice this on every side
+const SHADOW_BLUR: f32 = 12.0;
+// How far down the shadow falls, as if lit from slightly above
+const SHADOW_OFFSET: u32 = 6;
+const SHADOW_OPACITY: f32 = 0.45;
+
+/// Adds `--border` and `--shadow` around an already cropped and rounded capture
+pub fn decorate(img: RgbaImage, args: &Args) -> RgbaImage {
+    let img = match args.border {
+        Some(border) => add_border(&img, border, args.corner_radius),
+        None => img,
+    };
+    match args.shadow {
+        true => add_shadow(&img),
+        false => img,
+    }
+}
+
+// Grows the canvas by the border width, with the border's corners rounded to
+// match the capture's own
+fn add_border(img: &RgbaImage, border: Border, radius: f32) -> RgbaImage {
+    let width = border.width;
+    let outer_radius = match radius > 0.0 {
+        true => radius + width as f32,
+        false => 0.0,
+    };
+    let mut canvas = RgbaImage::new(img.width() + 2 * width, img.height() + 2 * width);
+    let max = Vec2::new(canvas.width() as f32, canvas.height() as f32);
+    for (x, y, pixel) in canvas.enumerate_pixels_mut() {
+        let center = Vec2::new(x as f32 + 0.5, y as f32 + 0.5);
+        let coverage =
+            (0.5 - rounded_box_distance(center, Vec2::ZERO, max, outer_radius)).clamp(0.0, 1.0);
+        let mut color = border.color;
+        color[3] = (color[3] as f32 * coverage).round() as u8;
+        *pixel = color;
+    }
+    imageops::overlay(&mut canvas, img, width as i64, width as i64);
+    canvas
+}
+
+// Shadows the capture's shape, transparent corners included, on a larger canvas
+fn add_shadow(img: &RgbaImage) -> RgbaImage {
+    let margin = (SHADOW_BLUR * 2.0).ceil() as u32;
+    let mut shadow = RgbaImage::new(img.width() + 2 * margin, img.height() + 2 * margin);
+    for (x, y, pixel) in img.enumerate_pixels() {
+        let alpha = (pixel[3] as f32 * SHADOW_OPACITY).round() as u8;
+        shadow.put_pixel(
+            x + margin,
+            y + margin + SHADOW_OFFSET,
+            Rgba([0, 0, 0, alpha]),
+        );
+    }
+    let mut canvas = imageops::fast_blur(&shadow, SHADOW_BLUR);
+    imageops::overlay(&mut canvas, img, margin as i64, margin as i64);
+    canvas
+}
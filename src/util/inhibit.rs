@@ -0,0 +1,68 @@
+#[cfg(all(target_os = "linux", feature = "portal"))]
+use std::time::Duration;
+
+#[cfg(all(target_os = "linux", feature = "portal"))]
+use dbus::blocking::Connection;
+
+use crate::notify::{Notifier, Urgency};
+
+#[cfg(all(target_os = "linux", feature = "portal"))]
+const SCREENSAVER: &str = "org.freedesktop.ScreenSaver";
+#[cfg(all(target_os = "linux", feature = "portal"))]
+const SCREENSAVER_PATH: &str = "/org/freedesktop/ScreenSaver";
+
+/// Keeps the screen from blanking or locking until dropped, so a `--delay` ends by
+/// capturing the desktop rather than the lock screen.
+///
+/// Goes through the freedesktop.org screensaver interface GNOME, KDE and most other
+/// desktops provide, so it needs dbus and the `portal` feature that brings it in
+#[cfg(all(target_os = "linux", feature = "portal"))]
+pub struct Inhibitor {
+    // The desktop drops the inhibition by itself if this connection closes first
+    conn: Connection,
+    cookie: u32,
+}
+
+#[cfg(all(target_os = "linux", feature = "portal"))]
+impl Inhibitor {
+    pub fn new(reason: &str) -> anyhow::Result<Self> {
+        let conn = Connection::new_session()?;
+        let (cookie,): (u32,) = conn
+            .with_proxy(SCREENSAVER, SCREENSAVER_PATH, Duration::from_secs(5))
+            .method_call(SCREENSAVER, "Inhibit", ("cleave", reason))?;
+        Ok(Self { conn, cookie })
+    }
+}
+
+#[cfg(all(target_os = "linux", feature = "portal"))]
+impl Drop for Inhibitor {
+    fn drop(&mut self) {
+        let _: Result<(), _> = self
+            .conn
+            .with_proxy(SCREENSAVER, SCREENSAVER_PATH, Duration::from_secs(5))
+            .method_call(SCREENSAVER, "UnInhibit", (self.cookie,));
+    }
+}
+
+#[cfg(not(all(target_os = "linux", feature = "portal")))]
+pub struct Inhibitor;
+
+#[cfg(not(all(target_os = "linux", feature = "portal")))]
+impl Inhibitor {
+    pub fn new(_: &str) -> anyhow::Result<Self> {
+        anyhow::bail!("Keeping the screen awake is only supported on Linux with the portal feature")
+    }
+}
+
+/// An `Inhibitor` for the length of `--delay`, or nothing if the screen can't be kept
+/// awake. The delay still runs, so that's only worth mentioning with `--verbose`
+pub fn during_delay(notifier: &dyn Notifier) -> Option<Inhibitor> {
+    Inhibitor::new("Waiting to take a screenshot")
+        .inspect_err(|err| {
+            notifier.notify(
+                Urgency::Debug,
+                &format!("Could not keep the screen awake: {err:#}"),
+            )
+        })
+        .ok()
+}
@@ -0,0 +1,321 @@
+pub mod capture;
+pub mod clipboard;
+pub mod decorate;
+pub mod inhibit;
+pub mod quantize;
+#[cfg(feature = "record")]
+pub mod record;
+
+use std::{
+    fmt::Write as _,
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Context;
+use glam::Vec2;
+use image::{
+    codecs::png::{CompressionType, FilterType, PngEncoder},
+    GenericImageView, ImageEncoder, Rgba, RgbaImage,
+};
+use png::text_metadata::{EncodableTextChunk, ITXtChunk};
+#[cfg(feature = "ocr")]
+use sha2::{Digest, Sha256};
+
+use crate::args::{Args, Command, PngCompression};
+
+/// Loads an image from `path`, or from stdin if it is `-`
+pub fn load_image(path: &Path) -> anyhow::Result<RgbaImage> {
+    let img = if path == Path::new("-") {
+        let mut bytes = Vec::new();
+        std::io::stdin().read_to_end(&mut bytes)?;
+        image::load_from_memory(&bytes)?
+    } else {
+        image::open(path).with_context(|| format!("Could not read {}", path.display()))?
+    };
+    Ok(img.to_rgba8())
+}
+
+/// Where a confirmed selection is sent
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Sink {
+    Clipboard,
+    // Encoded as PNG
+    Stdout,
+    // In `--fallback-dir`, only once copying to the clipboard failed
+    File,
+}
+
+impl Sink {
+    pub fn for_args(args: &Args) -> Self {
+        // Edited images have no clipboard to go back to, they are always part of a pipeline
+        if args.stdout || matches!(args.command, Some(Command::Edit { .. })) {
+            Sink::Stdout
+        } else {
+            Sink::Clipboard
+        }
+    }
+}
+
+/// Replaces the %XX escapes of a URL, leaving malformed ones as they are
+pub fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok());
+        match (bytes[i], hex.and_then(|h| u8::from_str_radix(h, 16).ok())) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// A file name for a capture in `dir` that isn't taken yet
+pub fn new_capture_path(dir: &Path) -> PathBuf {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs());
+    let mut path = dir.join(format!("cleave-{timestamp}.png"));
+    for n in 2.. {
+        if !path.exists() {
+            break;
+        }
+        path = dir.join(format!("cleave-{timestamp}-{n}.png"));
+    }
+    path
+}
+
+pub fn write_png(
+    img: &RgbaImage,
+    compression: PngCompression,
+    writer: impl Write,
+) -> image::ImageResult<()> {
+    let compression = match compression {
+        PngCompression::Fast => CompressionType::Fast,
+        PngCompression::Default => CompressionType::Default,
+        PngCompression::Best => CompressionType::Best,
+    };
+    PngEncoder::new_with_quality(writer, compression, FilterType::Adaptive).write_image(
+        img.as_raw(),
+        img.width(),
+        img.height(),
+        image::ExtendedColorType::Rgba8,
+    )
+}
+
+/// Adds a UTF-8 text chunk to an encoded PNG, just after its header
+pub fn add_png_text(png: &[u8], keyword: &str, text: &str) -> anyhow::Result<Vec<u8>> {
+    // The 8 byte signature and the IHDR chunk, which has to stay first
+    const HEADER_LEN: usize = 8 + 8 + 13 + 4;
+    anyhow::ensure!(
+        png.get(12..16) == Some(b"IHDR".as_slice()),
+        "Not a PNG, can't add text to it"
+    );
+    let mut with_text = png[..HEADER_LEN].to_vec();
+    ITXtChunk::new(keyword, text).encode(&mut with_text)?;
+    with_text.extend_from_slice(&png[HEADER_LEN..]);
+    Ok(with_text)
+}
+
+/// Lowercase hex, like sha256sum prints
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut hex, byte| {
+        let _ = write!(hex, "{byte:02x}");
+        hex
+    })
+}
+
+/// The SHA-256 of `bytes` in lowercase hex
+#[cfg(feature = "ocr")]
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    hex(&Sha256::digest(bytes))
+}
+
+/// Seconds since the Unix epoch as an ISO 8601 date and time in UTC, like
+/// 2024-05-01T09:30:00Z
+pub fn utc_timestamp(secs: u64) -> String {
+    let (days, time) = (secs / 86400, secs % 86400);
+    // Howard Hinnant's civil_from_days
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        time / 3600,
+        time / 60 % 60,
+        time % 60
+    )
+}
+
+/// Signed distance from `point` to a box with rounded corners, negative inside
+pub fn rounded_box_distance(point: Vec2, min: Vec2, max: Vec2, radius: f32) -> f32 {
+    let center = (min + max) / 2.0;
+    let half = (max - min) / 2.0;
+    let radius = radius.min(half.min_element()).max(0.0);
+    let q = (point - center).abs() - half + radius;
+    q.max(Vec2::ZERO).length() + q.max_element().min(0.0) - radius
+}
+
+// The part of a rectangle inside `img` as start and end corners
+fn clip_rect(img: &RgbaImage, x: i32, y: i32, width: u32, height: u32) -> (u32, u32, u32, u32) {
+    let x_start = x.clamp(0, img.width() as i32) as u32;
+    let y_start = y.clamp(0, img.height() as i32) as u32;
+    let x_end = (x as i64 + width as i64).clamp(0, img.width() as i64) as u32;
+    let y_end = (y as i64 + height as i64).clamp(0, img.height() as i64) as u32;
+    (x_start, y_start, x_end, y_end)
+}
+
+/// Fills a rectangle of `img` with `color`, clipping the parts that fall outside it
+pub fn fill_rect(img: &mut RgbaImage, x: i32, y: i32, width: u32, height: u32, color: Rgba<u8>) {
+    let (x_start, y_start, x_end, y_end) = clip_rect(img, x, y, width, height);
+    for y in y_start..y_end {
+        for x in x_start..x_end {
+            img.put_pixel(x, y, color);
+        }
+    }
+}
+
+/// Blurs a rectangle of `img` past reading, clipping the parts that fall outside it
+pub fn blur_rect(img: &mut RgbaImage, x: i32, y: i32, width: u32, height: u32) {
+    let (x_start, y_start, x_end, y_end) = clip_rect(img, x, y, width, height);
+    if x_end <= x_start || y_end <= y_start {
+        return;
+    }
+    let area = img
+        .view(x_start, y_start, x_end - x_start, y_end - y_start)
+        .to_image();
+    // Strong enough that text is gone, but the area still looks like it belongs
+    let sigma = ((x_end - x_start).min(y_end - y_start) as f32 / 4.0).clamp(8.0, 32.0);
+    let blurred = image::imageops::fast_blur(&area, sigma);
+    image::imageops::replace(img, &blurred, x_start as i64, y_start as i64);
+}
+
+/// Makes the corners of `img` transparent, with an antialiased edge
+pub fn round_corners(img: &mut RgbaImage, radius: f32) {
+    if radius <= 0.0 {
+        return;
+    }
+    let max = Vec2::new(img.width() as f32, img.height() as f32);
+    for (x, y, pixel) in img.enumerate_pixels_mut() {
+        let center = Vec2::new(x as f32 + 0.5, y as f32 + 0.5);
+        let coverage =
+            (0.5 - rounded_box_distance(center, Vec2::ZERO, max, radius)).clamp(0.0, 1.0);
+        pixel[3] = (pixel[3] as f32 * coverage).round() as u8;
+    }
+}
+
+/// Cuts `img` into a grid of `columns` by `rows` tiles, row by row. Tile edges are
+/// spread evenly, so no tile is more than a pixel bigger than another
+pub fn split_tiles(img: &RgbaImage, columns: u32, rows: u32) -> Vec<RgbaImage> {
+    let edges = |size: u32, count: u32| -> Vec<u32> {
+        (0..=count)
+            .map(|i| (size as u64 * i as u64 / count as u64) as u32)
+            .collect()
+    };
+    let (xs, ys) = (edges(img.width(), columns), edges(img.height(), rows));
+    let mut tiles = Vec::new();
+    for y in ys.windows(2) {
+        for x in xs.windows(2) {
+            tiles.push(img.view(x[0], y[0], x[1] - x[0], y[1] - y[0]).to_image());
+        }
+    }
+    tiles
+}
+
+/// Byte count in the largest unit that keeps it above 1, e.g. `1.2 MiB`
+pub fn human_size(bytes: usize) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{size:.1} {}", UNITS[unit])
+    }
+}
+
+/// `raw` as a double quoted string that reads back the same in both JSON and TOML, whose
+/// basic strings share these escapes
+pub fn quote(raw: &str) -> String {
+    let mut quoted = String::from('"');
+    for c in raw.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(quoted, "\\u{:04x}", c as u32);
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{quote, utc_timestamp};
+
+    #[test]
+    fn quote_escapes_quotes_and_backslashes() {
+        assert_eq!(quote(r#"say "hi""#), r#""say \"hi\"""#);
+        assert_eq!(quote(r"C:\Users\me"), r#""C:\\Users\\me""#);
+    }
+
+    #[test]
+    fn quote_escapes_control_characters() {
+        assert_eq!(quote("a\nb\tc"), r#""a\nb\tc""#);
+        assert_eq!(quote("\r\u{1b}\u{7f}"), r#""\u000d\u001b\u007f""#);
+    }
+
+    #[test]
+    fn quote_keeps_everything_else() {
+        assert_eq!(quote(""), r#""""#);
+        assert_eq!(quote("héllo 👋 'x'"), "\"héllo 👋 'x'\"");
+    }
+
+    #[test]
+    fn quote_reads_back_as_toml() {
+        let raw = "quote \" slash \\ newline \n bell \u{7} done";
+        let document: toml_edit::DocumentMut = format!("value = {}", quote(raw)).parse().unwrap();
+        assert_eq!(document["value"].as_str(), Some(raw));
+    }
+
+    #[test]
+    fn utc_timestamps_count_from_the_epoch() {
+        assert_eq!(utc_timestamp(0), "1970-01-01T00:00:00Z");
+        assert_eq!(utc_timestamp(1714555800), "2024-05-01T09:30:00Z");
+        // 2000 has a leap day, 2100 doesn't
+        assert_eq!(utc_timestamp(951782400), "2000-02-29T00:00:00Z");
+        assert_eq!(utc_timestamp(4107542399), "2100-02-28T23:59:59Z");
+        assert_eq!(utc_timestamp(4107542400), "2100-03-01T00:00:00Z");
+    }
+}
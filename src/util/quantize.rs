@@ -0,0 +1,115 @@
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    io::Write,
+};
+
+use color_quant::NeuQuant;
+use image::{Rgba, RgbaImage};
+
+use crate::args::{Dither, PngCompression};
+
+// NeuQuant's speed/quality trade off, 1 is slowest and best, 10 is its usual default
+const SAMPLE_FACTOR: i32 = 10;
+
+/// Replaces every pixel of `img` with one of at most `colors` colors
+pub fn reduce_colors(img: &mut RgbaImage, colors: usize, dither: Dither) {
+    let quantizer = NeuQuant::new(SAMPLE_FACTOR, colors, img.as_raw());
+    match dither {
+        Dither::None => {
+            for pixel in img.pixels_mut() {
+                quantizer.map_pixel(&mut pixel.0);
+            }
+        }
+        Dither::Floyd => floyd_steinberg(img, &quantizer),
+    }
+}
+
+fn floyd_steinberg(img: &mut RgbaImage, quantizer: &NeuQuant) {
+    let (width, height) = (img.width() as usize, img.height() as usize);
+    // Each pixel's color plus the error pushed onto it by the ones before
+    let mut wanted: Vec<[f32; 4]> = img.pixels().map(|p| p.0.map(f32::from)).collect();
+    for y in 0..height {
+        for x in 0..width {
+            let index = y * width + x;
+            let mut chosen = wanted[index].map(|c| c.round().clamp(0.0, 255.0) as u8);
+            quantizer.map_pixel(&mut chosen);
+            img.put_pixel(x as u32, y as u32, Rgba(chosen));
+
+            // Alpha isn't diffused, it would only fray transparent edges
+            let error: [f32; 3] = std::array::from_fn(|c| wanted[index][c] - chosen[c] as f32);
+            let neighbours = [(1, 0, 7.0), (-1, 1, 3.0), (0, 1, 5.0), (1, 1, 1.0)];
+            for (dx, dy, weight) in neighbours {
+                let (nx, ny) = (x as isize + dx, y + dy);
+                if nx < 0 || nx as usize >= width || ny >= height {
+                    continue;
+                }
+                let neighbour = &mut wanted[ny * width + nx as usize];
+                for (value, error) in neighbour.iter_mut().zip(error) {
+                    *value += error * weight / 16.0;
+                }
+            }
+        }
+    }
+}
+
+/// Encodes an image of at most 256 distinct colors as an indexed PNG,
+/// packing several pixels into each byte when the palette is small enough
+pub fn write_indexed_png(
+    img: &RgbaImage,
+    compression: PngCompression,
+    writer: impl Write,
+) -> anyhow::Result<()> {
+    let mut palette: Vec<[u8; 4]> = Vec::new();
+    let mut lookup = HashMap::new();
+    let mut indices = Vec::with_capacity(img.as_raw().len() / 4);
+    for pixel in img.pixels() {
+        let index = match lookup.entry(pixel.0) {
+            Entry::Occupied(entry) => *entry.get(),
+            Entry::Vacant(entry) => {
+                anyhow::ensure!(palette.len() < 256, "Image has more than 256 colors");
+                palette.push(pixel.0);
+                *entry.insert((palette.len() - 1) as u8)
+            }
+        };
+        indices.push(index);
+    }
+
+    let (depth, bits) = match palette.len() {
+        0..=2 => (png::BitDepth::One, 1),
+        3..=4 => (png::BitDepth::Two, 2),
+        5..=16 => (png::BitDepth::Four, 4),
+        _ => (png::BitDepth::Eight, 8),
+    };
+    let mut data = Vec::new();
+    for row in indices.chunks(img.width().max(1) as usize) {
+        // Rows start on a fresh byte, the leftmost pixel in the high bits
+        for pixels in row.chunks(8 / bits) {
+            let byte = (0..)
+                .zip(pixels)
+                .fold(0u8, |byte, (i, index)| byte | index << (8 - bits * (i + 1)));
+            data.push(byte);
+        }
+    }
+
+    let mut encoder = png::Encoder::new(writer, img.width(), img.height());
+    encoder.set_color(png::ColorType::Indexed);
+    encoder.set_depth(depth);
+    encoder.set_compression(match compression {
+        PngCompression::Fast => png::Compression::Fast,
+        PngCompression::Default => png::Compression::Balanced,
+        PngCompression::Best => png::Compression::High,
+    });
+    encoder.set_palette(
+        palette
+            .iter()
+            .flat_map(|c| [c[0], c[1], c[2]])
+            .collect::<Vec<_>>(),
+    );
+    if palette.iter().any(|c| c[3] < u8::MAX) {
+        encoder.set_trns(palette.iter().map(|c| c[3]).collect::<Vec<_>>());
+    }
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&data)?;
+    writer.finish()?;
+    Ok(())
+}
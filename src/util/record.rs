@@ -0,0 +1,145 @@
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use anyhow::Context;
+use image::{
+    codecs::gif::{GifEncoder, Repeat},
+    Delay, Frame, RgbaImage,
+};
+use winit::dpi::PhysicalPosition;
+
+use super::capture;
+use crate::{
+    args::{Args, Region},
+    project,
+};
+
+// NeuQuant sampling per frame, 1 is best and far too slow for more than a few frames
+const GIF_SPEED: i32 = 10;
+// Time for the compositor to take the overlay off the screen before the first frame
+const HIDE_DELAY: Duration = Duration::from_millis(200);
+
+/// A recording of the selection for `V`, captured and encoded on its own thread so the
+/// event loop keeps running and can stop it early
+pub struct Recording {
+    stop: Arc<AtomicBool>,
+    worker: JoinHandle<anyhow::Result<Recorded>>,
+    // When it stops by itself, after `--record-seconds`
+    pub ends: Instant,
+}
+
+/// What a finished recording wrote
+pub struct Recorded {
+    pub frames: usize,
+    pub bytes: usize,
+    pub output: PathBuf,
+}
+
+impl Recording {
+    /// Starts recording `region` of the monitor whose corner is at `monitor`
+    pub fn start(monitor: PhysicalPosition<i32>, args: Args, region: Region) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let duration = HIDE_DELAY + Duration::from_secs(args.record_seconds);
+        let ends = Instant::now() + duration;
+        let worker = std::thread::spawn({
+            let stop = stop.clone();
+            move || {
+                std::thread::sleep(HIDE_DELAY);
+                let monitor = xcap::Monitor::all()?
+                    .into_iter()
+                    .find(|m| m.x() == monitor.x && m.y() == monitor.y)
+                    .context("Could not find the captured monitor again")?;
+                let frames = record(&monitor, &args, region, args.fps, ends, &stop)?;
+                let frames_recorded = frames.len();
+                let mut gif = Vec::new();
+                write_gif(frames, args.fps, &mut gif)?;
+                let output = if args.record_output == Path::new("-") {
+                    std::io::stdout().lock().write_all(&gif)?;
+                    args.record_output.clone()
+                } else {
+                    let output = project::output_path(&args, &args.record_output)?;
+                    std::fs::write(&output, &gif)?;
+                    output
+                };
+                Ok(Recorded {
+                    frames: frames_recorded,
+                    bytes: gif.len(),
+                    output,
+                })
+            }
+        });
+        Self { stop, worker, ends }
+    }
+
+    /// Ends the recording after the frame being captured, it's then encoded and saved
+    pub fn stop(&self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether it's been encoded and saved, or has failed
+    pub fn is_finished(&self) -> bool {
+        self.worker.is_finished()
+    }
+
+    /// Waits for the recording to be saved
+    pub fn finish(self) -> anyhow::Result<Recorded> {
+        self.stop();
+        self.worker
+            .join()
+            .map_err(|_| anyhow::anyhow!("The recording stopped unexpectedly"))?
+    }
+}
+
+/// Captures `region` of `monitor` `fps` times a second until `ends` or until `stop` is set.
+/// Frames that take longer than their slot to capture are skipped, not queued up
+fn record(
+    monitor: &xcap::Monitor,
+    args: &Args,
+    region: Region,
+    fps: u32,
+    ends: Instant,
+    stop: &AtomicBool,
+) -> anyhow::Result<Vec<RgbaImage>> {
+    let interval = Duration::from_secs(1) / fps;
+    let mut frames = Vec::new();
+    let mut next = Instant::now();
+    while next < ends && !stop.load(Ordering::Relaxed) {
+        let capture = capture::capture_monitor(monitor, args)?;
+        let frame = image::imageops::crop_imm(
+            &capture.image,
+            region.x,
+            region.y,
+            region.width,
+            region.height,
+        );
+        frames.push(frame.to_image());
+
+        next += interval;
+        while next < Instant::now() {
+            next += interval;
+        }
+        std::thread::sleep(next - Instant::now());
+    }
+    Ok(frames)
+}
+
+/// Encodes `frames` as a looping GIF played back at `fps`
+fn write_gif(frames: Vec<RgbaImage>, fps: u32, writer: impl Write) -> anyhow::Result<()> {
+    let delay = Delay::from_numer_denom_ms(1000, fps);
+    let mut encoder = GifEncoder::new_with_speed(writer, GIF_SPEED);
+    encoder.set_repeat(Repeat::Infinite)?;
+    encoder.encode_frames(
+        frames
+            .into_iter()
+            .map(|frame| Frame::from_parts(frame, 0, 0, delay)),
+    )?;
+    Ok(())
+}
@@ -0,0 +1,180 @@
+use std::fmt::Write;
+
+use image::{Rgb, RgbaImage};
+
+use crate::palette;
+
+// Flat UI rarely needs more, and fewer colors keep antialiased edges from splintering
+const COLORS: usize = 16;
+// Regions smaller than this are noise or antialiasing
+const MIN_AREA: usize = 12;
+// Glyph sized regions that don't fill their bounds are treated as text
+const GLYPH_MAX_HEIGHT: u32 = 32;
+const GLYPH_MAX_FILL: f32 = 0.6;
+
+const TRANSPARENT: u8 = u8::MAX;
+
+#[derive(Clone, Copy, Debug)]
+struct Region {
+    color: Rgb<u8>,
+    x0: u32,
+    y0: u32,
+    x1: u32,
+    y1: u32,
+    area: usize,
+}
+
+impl Region {
+    fn bounds_area(&self) -> usize {
+        ((self.x1 - self.x0) * (self.y1 - self.y0)) as usize
+    }
+
+    fn is_glyph(&self) -> bool {
+        self.y1 - self.y0 <= GLYPH_MAX_HEIGHT
+            && (self.area as f32) < self.bounds_area() as f32 * GLYPH_MAX_FILL
+    }
+
+    // Glyphs on the same line, no further apart than the line is tall
+    fn joins_line(&self, line: &Region) -> bool {
+        let overlap = self.y0 < line.y1 && line.y0 < self.y1;
+        let gap = self.x0.saturating_sub(line.x1);
+        overlap && gap <= (line.y1 - line.y0).max(self.y1 - self.y0)
+    }
+
+    fn merge(&mut self, other: &Region) {
+        self.x0 = self.x0.min(other.x0);
+        self.y0 = self.y0.min(other.y0);
+        self.x1 = self.x1.max(other.x1);
+        self.y1 = self.y1.max(other.y1);
+        self.area += other.area;
+    }
+}
+
+/// Approximates a flat colored screenshot as SVG: every connected region of one color
+/// becomes its bounding rectangle, and runs of glyph sized regions become a single
+/// placeholder per line of text. Works for simple UI, photos come out as noise
+pub fn to_svg(img: &RgbaImage) -> String {
+    let (width, height) = img.dimensions();
+    let swatches = palette::extract(img, COLORS);
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" \
+         viewBox=\"0 0 {width} {height}\">\n"
+    );
+    let Some(background) = swatches.first() else {
+        svg.push_str("</svg>\n");
+        return svg;
+    };
+    let _ = writeln!(
+        svg,
+        "  <rect width=\"{width}\" height=\"{height}\" fill=\"{}\"/>",
+        background.hex()
+    );
+
+    let labels: Vec<u8> = img
+        .pixels()
+        .map(|p| {
+            if p[3] == 0 {
+                return TRANSPARENT;
+            }
+            let nearest = swatches.iter().enumerate().min_by_key(|(_, s)| {
+                (0..3)
+                    .map(|c| (s.color[c] as i32 - p[c] as i32).pow(2))
+                    .sum::<i32>()
+            });
+            nearest.map_or(TRANSPARENT, |(i, _)| i as u8)
+        })
+        .collect();
+    let colors: Vec<Rgb<u8>> = swatches.iter().map(|s| s.color).collect();
+
+    let (mut glyphs, mut shapes): (Vec<Region>, Vec<Region>) =
+        regions(&labels, width, height, &colors)
+            .into_iter()
+            .filter(|r| r.area >= MIN_AREA)
+            .partition(Region::is_glyph);
+
+    glyphs.sort_by_key(|r| (r.x0, r.y0));
+    let mut lines: Vec<Region> = Vec::new();
+    for glyph in glyphs {
+        match lines.iter_mut().find(|line| glyph.joins_line(line)) {
+            Some(line) => line.merge(&glyph),
+            None => lines.push(glyph),
+        }
+    }
+
+    // Biggest first, so nested elements end up on top of their containers
+    shapes.sort_by_key(|r| std::cmp::Reverse(r.bounds_area()));
+    for r in shapes {
+        let _ = writeln!(
+            svg,
+            "  <rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\"/>",
+            r.x0,
+            r.y0,
+            r.x1 - r.x0,
+            r.y1 - r.y0,
+            palette::hex(r.color)
+        );
+    }
+    for r in lines {
+        let _ = writeln!(
+            svg,
+            "  <rect class=\"text\" x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" rx=\"2\" \
+             fill=\"{}\" fill-opacity=\"0.5\"/>",
+            r.x0,
+            r.y0,
+            r.x1 - r.x0,
+            r.y1 - r.y0,
+            palette::hex(r.color)
+        );
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+// 4-connected regions of the same label, skipping the background and transparency
+fn regions(labels: &[u8], width: u32, height: u32, colors: &[Rgb<u8>]) -> Vec<Region> {
+    let mut seen = vec![false; labels.len()];
+    let mut regions = Vec::new();
+    let mut stack = Vec::new();
+    for start in 0..labels.len() {
+        let label = labels[start];
+        if seen[start] || label == 0 || label == TRANSPARENT {
+            continue;
+        }
+        let (x, y) = ((start as u32) % width, (start as u32) / width);
+        let mut region = Region {
+            color: colors[label as usize],
+            x0: x,
+            y0: y,
+            x1: x + 1,
+            y1: y + 1,
+            area: 0,
+        };
+        seen[start] = true;
+        stack.push(start);
+        while let Some(index) = stack.pop() {
+            let (x, y) = ((index as u32) % width, (index as u32) / width);
+            region.merge(&Region {
+                x0: x,
+                y0: y,
+                x1: x + 1,
+                y1: y + 1,
+                area: 1,
+                ..region
+            });
+            let neighbours = [
+                (x > 0).then(|| index - 1),
+                (x + 1 < width).then(|| index + 1),
+                (y > 0).then(|| index - width as usize),
+                (y + 1 < height).then(|| index + width as usize),
+            ];
+            for next in neighbours.into_iter().flatten() {
+                if !seen[next] && labels[next] == label {
+                    seen[next] = true;
+                    stack.push(next);
+                }
+            }
+        }
+        regions.push(region);
+    }
+    regions
+}